@@ -6,16 +6,36 @@ pub use bill_filter::BillFilter;
 pub mod cost_query;
 pub mod repository;
 
+pub mod allocation;
+pub mod bill_diff;
 pub mod bill_entry;
 pub mod bills_impl_basic;
 pub mod bills_impl_cost_by_any;
 pub mod bills_impl_cost_by_sub;
 pub mod bills_impl_currency;
 pub mod bills_sum_data;
+pub mod chargeback;
 pub mod cost_type_enum;
+pub mod coverage;
+pub mod date_range;
 pub mod display;
+pub mod filter_expr;
+pub mod html_report;
+pub mod pivot;
+pub mod query;
+pub mod recommend;
+pub mod report;
 pub mod summary;
+pub mod tag_alias;
+pub mod tag_compliance;
+pub mod tag_inherit;
+pub mod tag_predicate;
 pub mod tags;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod tree;
+pub mod trend;
+pub mod variance;
 // use crate::bills::bills_struct::Bills;
 
 use crate::bills::bill_entry::BillEntry;
@@ -27,10 +47,17 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+/// Cap on per-row tag parse warnings logged for one file.
+const MAX_TAG_PARSE_WARNINGS: usize = 10;
+
 pub struct Bills {
     pub bills: Vec<BillEntry>,
     pub billing_currency: Option<String>,
     pub tag_names: HashSet<String>,
+    /// (CSV line number, error) for rows whose Tags column could not be fully parsed.
+    pub tag_parse_errors: Vec<(usize, String)>,
+    /// Raw tag keys / values folded by `apply_tag_aliases`, empty when no alias map is used.
+    pub tag_merges: Vec<tag_alias::TagMerge>,
     pub file_name: String,
     pub file_short_name: String,
     pub summary: summary::Summary,
@@ -103,10 +130,25 @@ impl Bills {
                 );
             }
             bill.line_number_csv = line_number;
+            if let Some(err) = &bill.tags.parse_error {
+                if self.tag_parse_errors.len() < MAX_TAG_PARSE_WARNINGS {
+                    log::warn!(
+                        "Bad tags on line #{line_number} in {source_name}: {err} - '{}'",
+                        bill.tags.value
+                    );
+                }
+                self.tag_parse_errors.push((line_number, err.clone()));
+            }
             // record global tags
             self.tag_names.extend(bill.tags.kv.keys().cloned());
             self.push(bill);
         }
+        if self.tag_parse_errors.len() > MAX_TAG_PARSE_WARNINGS {
+            log::warn!(
+                "{} rows with bad tags in {source_name}, only the first {MAX_TAG_PARSE_WARNINGS} shown",
+                self.tag_parse_errors.len()
+            );
+        }
         self.set_billing_currency()?;
         log::debug!(
             "parse_csv {line_number} lines in {:.3}s",
@@ -124,6 +166,8 @@ impl Default for Bills {
             bills: Vec::new(),
             billing_currency: None,
             tag_names: HashSet::new(),
+            tag_parse_errors: Vec::new(),
+            tag_merges: Vec::new(),
             file_name: "NotSet".to_string(),
             file_short_name: "NotSet".to_string(),
             summary: summary::Summary {
@@ -199,4 +243,50 @@ mod tests {
         assert_eq!(first_bill.quantity, 0.194368534, "quantity mismatch");
         assert_eq!(first_bill.cost, Nzd(0.003025655), "cost mismatch");
     }
+
+    #[test]
+    fn test_combine_months() {
+        let mut latest = super::Bills::default();
+        latest
+            .parse_csv(
+                &PathBuf::from("tests/azure_test_nzd_usd_latest.csv"),
+                &FILTER_OPTS,
+            )
+            .unwrap();
+        let mut prev = super::Bills::default();
+        prev.parse_csv(
+            &PathBuf::from("tests/azure_test_nzd_usd_prev.csv"),
+            &FILTER_OPTS,
+        )
+        .unwrap();
+        let both = super::Bills::combine(&[&prev, &latest], "range").unwrap();
+        assert_eq!(both.len(), prev.len() + latest.len());
+        let sum = prev.summary.total_cost + latest.summary.total_cost;
+        assert!((both.summary.total_cost.amount() - sum.amount()).abs() < 1e-9);
+        assert_eq!(both.billing_currency, latest.billing_currency);
+        assert_eq!(both.file_short_name, "range");
+
+        latest.billing_currency = Some("AUD".to_string());
+        assert!(super::Bills::combine(&[&prev, &latest], "range").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_reports_bad_tags_with_line_number() {
+        let header = std::fs::read_to_string("tests/azure_test_nzd_usd_latest.csv").unwrap();
+        let mut lines = header.lines();
+        let head = lines.next().unwrap();
+        let row = lines.next().unwrap().trim_end_matches(",\"\"");
+        let csv = format!(
+            "{head}\n{row},\"\"\"env\"\": \"\"prod, test\"\"\"\n{row},\"\"\"owner\"\": \"\"ali\"\n"
+        );
+        let mut bills = super::Bills::default();
+        bills
+            .parse_csv_from_reader(csv.as_bytes(), "inline", &FILTER_OPTS)
+            .unwrap();
+        assert_eq!(bills.bills.len(), 2);
+        assert_eq!(bills.bills[0].tags.kv["env"].0, "prod, test");
+        assert_eq!(bills.tag_parse_errors.len(), 1);
+        assert_eq!(bills.tag_parse_errors[0].0, 3);
+        assert!(bills.tag_names.contains("env"));
+    }
 }
@@ -0,0 +1,545 @@
+//! Shared-cost allocation — split shared platform spend across consuming teams.
+//!
+//! Rows are first assigned to the first *pool* whose patterns match (same
+//! dimensions as the CLI filters: `name_regex`, `resource_group`, `subscription`,
+//! `meter_category`, `location`, `reservation`, `tag_filter`, `invoice_section`
+//! and `tags` predicates, see `BillFilter`). All other rows are
+//! *direct* spend of the team named by the `team_tag` tag value (effective
+//! tags, so aliases and inheritance apply), or of `unallocated_team` when the
+//! tag is missing. Each pool's cost is then split by its allocation key:
+//!
+//! - `proportional`: by each team's direct spend (optionally only `teams`)
+//! - `fixed`: by percentage `shares`; any remainder below 100% is unallocated
+//! - `even`: equally across `teams`, or across all teams with direct spend
+//!
+//! Team totals (direct + shared) always sum to the filtered bill total; a pool
+//! that cannot be split (e.g. no direct spend to be proportional to) goes to
+//! `unallocated_team`.
+//!
+//! Rules file (JSON):
+//!
+//! ```json
+//! {
+//!   "team_tag": "team",
+//!   "pools": [
+//!     { "name": "network", "resource_group": "^rg-hub-network$", "allocation": { "method": "proportional" } },
+//!     { "name": "logs", "meter_category": "Log Analytics",
+//!       "allocation": { "method": "fixed", "shares": { "team-a": 60, "team-b": 40 } } },
+//!     { "name": "aks-system", "resource_group": "^mc_", "tags": ["pool=system"],
+//!       "allocation": { "method": "even", "teams": ["team-a", "team-b", "team-c"] } }
+//!   ]
+//! }
+//! ```
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::bills_sum_data::SummaryData;
+use crate::bills::cost_type_enum::CostType;
+use crate::bills::tag_predicate::TagPredicate;
+use crate::cmd_parse::FilterOpts;
+use crate::f64_to_currency;
+
+/// How a pool's cost is split across teams.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "method", rename_all = "lowercase", deny_unknown_fields)]
+pub enum AllocationKey {
+    Proportional {
+        #[serde(default)]
+        teams: Vec<String>,
+    },
+    Fixed {
+        /// Team → percentage, summing to at most 100.
+        shares: BTreeMap<String, f64>,
+    },
+    Even {
+        #[serde(default)]
+        teams: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for AllocationKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllocationKey::Proportional { teams } if teams.is_empty() => write!(f, "proportional"),
+            AllocationKey::Proportional { teams } => {
+                write!(f, "proportional [{}]", teams.join(", "))
+            }
+            AllocationKey::Fixed { shares } => {
+                let shares: Vec<String> = shares.iter().map(|(t, s)| format!("{t}:{s}%")).collect();
+                write!(f, "fixed [{}]", shares.join(", "))
+            }
+            AllocationKey::Even { teams } if teams.is_empty() => write!(f, "even"),
+            AllocationKey::Even { teams } => write!(f, "even [{}]", teams.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoolSpec {
+    name: String,
+    /// Resource name regex, like `--name-regex`.
+    #[serde(default)]
+    name_regex: Option<String>,
+    #[serde(default)]
+    resource_group: Option<String>,
+    #[serde(default)]
+    subscription: Option<String>,
+    #[serde(default)]
+    meter_category: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    reservation: Option<String>,
+    #[serde(default)]
+    tag_filter: Option<String>,
+    #[serde(default)]
+    invoice_section: Option<String>,
+    /// `TagPredicate` specs, ANDed.
+    #[serde(default)]
+    tags: Vec<String>,
+    allocation: AllocationKey,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    team_tag: String,
+    #[serde(default = "default_unallocated")]
+    unallocated_team: String,
+    #[serde(default)]
+    pools: Vec<PoolSpec>,
+}
+
+fn default_unallocated() -> String {
+    "unallocated".to_string()
+}
+
+/// A shared pool with its compiled match filter.
+#[derive(Debug)]
+pub struct SharedPool {
+    pub name: String,
+    pub filter: BillFilter,
+    pub allocation: AllocationKey,
+}
+
+/// Compiled allocation rules, see the module docs for the file format.
+#[derive(Debug)]
+pub struct AllocationRules {
+    /// Lowercase tag key naming the owning team of direct spend.
+    pub team_tag: String,
+    pub unallocated_team: String,
+    pub pools: Vec<SharedPool>,
+}
+
+impl AllocationRules {
+    pub fn from_json(json: &str, filter_opts: &FilterOpts) -> Result<Self, String> {
+        let file: RulesFile = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid allocation rules file: {e}"))?;
+        let mut pools = Vec::with_capacity(file.pools.len());
+        for p in file.pools {
+            if let AllocationKey::Fixed { shares } = &p.allocation {
+                let sum: f64 = shares.values().sum();
+                if shares.values().any(|s| *s < 0.0) || sum > 100.0 + 1e-9 {
+                    return Err(format!(
+                        "Pool '{}': fixed shares must be >= 0 and sum to at most 100, got {sum}",
+                        p.name
+                    ));
+                }
+            }
+            let predicates = TagPredicate::parse_all(&p.tags, filter_opts.case_sensitive)
+                .map_err(|e| format!("Pool '{}': {e}", p.name))?;
+            let filter = BillFilter::new(
+                p.name_regex,
+                p.resource_group,
+                p.subscription,
+                p.meter_category,
+                p.location,
+                p.reservation,
+                None,
+                p.tag_filter,
+                p.invoice_section,
+                filter_opts,
+            )
+            .map_err(|e| format!("Pool '{}': invalid regex: {e}", p.name))?
+            .with_tag_predicates(predicates);
+            pools.push(SharedPool {
+                name: p.name,
+                filter,
+                allocation: p.allocation,
+            });
+        }
+        Ok(AllocationRules {
+            team_tag: file.team_tag.trim().to_lowercase(),
+            unallocated_team: file.unallocated_team,
+            pools,
+        })
+    }
+
+    pub fn load(path: &Path, filter_opts: &FilterOpts) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read allocation rules {path:?}: {e}"))?;
+        Self::from_json(&json, filter_opts).map_err(|e| format!("{e} in {path:?}"))
+    }
+}
+
+/// Cost of one shared pool and how it was split.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolCost {
+    pub name: String,
+    pub cost: f64,
+    pub rows: usize,
+    pub allocation: AllocationKey,
+}
+
+/// Allocated cost of one team, NZD.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TeamAllocation {
+    pub team: String,
+    pub direct: f64,
+    /// Pool name → allocated share.
+    pub shared: BTreeMap<String, f64>,
+    pub total: f64,
+    pub pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AllocationTable {
+    /// Filtered bill total; equals the sum of the team totals.
+    pub total: f64,
+    pub pools: Vec<PoolCost>,
+    /// Sorted by total, largest first.
+    pub teams: Vec<TeamAllocation>,
+}
+
+/// Split `amount` by `weights`, giving any rounding remainder to the last team
+/// so the parts sum exactly to `amount`.
+fn split(amount: f64, weights: &[(String, f64)]) -> Vec<(String, f64)> {
+    let total: f64 = weights.iter().map(|(_, w)| w).sum();
+    let mut parts: Vec<(String, f64)> = Vec::with_capacity(weights.len());
+    let mut given = 0.0;
+    for (i, (team, w)) in weights.iter().enumerate() {
+        let part = if i + 1 == weights.len() {
+            amount - given
+        } else {
+            amount * w / total
+        };
+        given += part;
+        parts.push((team.clone(), part));
+    }
+    parts
+}
+
+fn team_entry<'t>(
+    teams: &'t mut BTreeMap<String, TeamAllocation>,
+    name: &str,
+) -> &'t mut TeamAllocation {
+    teams
+        .entry(name.to_string())
+        .or_insert_with(|| TeamAllocation {
+            team: name.to_string(),
+            direct: 0.0,
+            shared: BTreeMap::new(),
+            total: 0.0,
+            pct: 0.0,
+        })
+}
+
+/// Allocate the rows matching `filter` by `rules`.
+pub fn allocate(bills: &Bills, filter: &BillFilter, rules: &AllocationRules) -> AllocationTable {
+    // direct spend per team and cost per pool, aggregated as CostType::Tag rows
+    let mut direct = SummaryData::default();
+    let mut pools: Vec<SummaryData> = rules.pools.iter().map(|_| SummaryData::default()).collect();
+    let mut pool_rows = vec![0usize; rules.pools.len()];
+    let mut total = 0.0;
+    for bill in bills.bills.iter().filter(|b| filter.matches(b)) {
+        total += bill.cost.amount();
+        let (summary, key) = match rules.pools.iter().position(|p| p.filter.matches(bill)) {
+            Some(i) => {
+                pool_rows[i] += 1;
+                (&mut pools[i], rules.pools[i].name.clone())
+            }
+            None => (
+                &mut direct,
+                bill.tags
+                    .get_value(&rules.team_tag)
+                    .unwrap_or(&rules.unallocated_team)
+                    .to_string(),
+            ),
+        };
+        summary.accumulate(
+            CostType::Tag,
+            key,
+            bill.cost,
+            bill.cost_usd,
+            bill.unit_price * bill.quantity,
+        );
+    }
+
+    let mut teams: BTreeMap<String, TeamAllocation> = BTreeMap::new();
+    let mut direct_weights: Vec<(String, f64)> = Vec::new();
+    for ((_, name), cost) in &direct.per_type {
+        team_entry(&mut teams, name).direct += cost.cost.amount();
+        if *name != rules.unallocated_team && cost.cost.amount() > 0.0 {
+            direct_weights.push((name.clone(), cost.cost.amount()));
+        }
+    }
+    direct_weights.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut pool_costs = Vec::with_capacity(rules.pools.len());
+    for ((pool, summary), rows) in rules.pools.iter().zip(&pools).zip(pool_rows) {
+        let cost: f64 = summary.per_type.values().map(|c| c.cost.amount()).sum();
+        pool_costs.push(PoolCost {
+            name: pool.name.clone(),
+            cost,
+            rows,
+            allocation: pool.allocation.clone(),
+        });
+        if rows == 0 {
+            continue;
+        }
+        let named = |teams: &[String]| -> Vec<(String, f64)> {
+            teams.iter().map(|t| (t.clone(), 1.0)).collect()
+        };
+        let mut weights: Vec<(String, f64)> = match &pool.allocation {
+            AllocationKey::Proportional { teams } if teams.is_empty() => direct_weights.clone(),
+            AllocationKey::Proportional { teams } => direct_weights
+                .iter()
+                .filter(|(t, _)| teams.contains(t))
+                .cloned()
+                .collect(),
+            AllocationKey::Fixed { shares } => {
+                let mut w: Vec<(String, f64)> =
+                    shares.iter().map(|(t, s)| (t.clone(), *s)).collect();
+                let rest = 100.0 - shares.values().sum::<f64>();
+                if rest > 1e-9 {
+                    w.push((rules.unallocated_team.clone(), rest));
+                }
+                w
+            }
+            AllocationKey::Even { teams } if teams.is_empty() => direct_weights
+                .iter()
+                .map(|(t, _)| (t.clone(), 1.0))
+                .collect(),
+            AllocationKey::Even { teams } => named(teams),
+        };
+        weights.retain(|(_, w)| *w > 0.0);
+        if weights.is_empty() {
+            log::warn!(
+                "Allocation pool '{}' has no teams to split to, cost goes to '{}'",
+                pool.name,
+                rules.unallocated_team
+            );
+            weights.push((rules.unallocated_team.clone(), 1.0));
+        }
+        for (name, part) in split(cost, &weights) {
+            let t = team_entry(&mut teams, &name);
+            *t.shared.entry(pool.name.clone()).or_default() += part;
+        }
+    }
+
+    let mut teams: Vec<TeamAllocation> = teams
+        .into_values()
+        .map(|mut t| {
+            t.total = t.direct + t.shared.values().sum::<f64>();
+            t.pct = if total != 0.0 {
+                t.total / total * 100.0
+            } else {
+                0.0
+            };
+            t
+        })
+        .collect();
+    teams.sort_by(|a, b| {
+        b.total
+            .partial_cmp(&a.total)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.team.cmp(&b.team))
+    });
+    AllocationTable {
+        total,
+        pools: pool_costs,
+        teams,
+    }
+}
+
+/// Render the table as CSV: team, direct, one column per pool, shared, total, pct.
+pub fn allocation_to_csv(table: &AllocationTable) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    let mut header = vec!["team".to_string(), "direct".to_string()];
+    header.extend(table.pools.iter().map(|p| format!("pool:{}", p.name)));
+    header.extend(["shared".to_string(), "total".to_string(), "pct".to_string()]);
+    wtr.write_record(&header)?;
+    for t in &table.teams {
+        let mut record = vec![t.team.clone(), format!("{:.2}", t.direct)];
+        record.extend(
+            table
+                .pools
+                .iter()
+                .map(|p| format!("{:.2}", t.shared.get(&p.name).copied().unwrap_or_default())),
+        );
+        record.extend([
+            format!("{:.2}", t.shared.values().sum::<f64>()),
+            format!("{:.2}", t.total),
+            format!("{:.1}", t.pct),
+        ]);
+        wtr.write_record(&record)?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// Print pools and the per-team table as coloured terminal text.
+pub fn print_allocation(table: &AllocationTable, cur: &str) {
+    for p in &table.pools {
+        println!(
+            "Pool {:<20} {:>16} rows:{:<6} {}",
+            format!("'{}'", p.name),
+            format!("{cur} {}", f64_to_currency(p.cost, 2)).yellow(),
+            p.rows,
+            p.allocation,
+        );
+    }
+    println!(
+        "{:>16} {:>16} {:>16} {:>7}  team",
+        "direct", "shared", "total", "pct"
+    );
+    for t in &table.teams {
+        println!(
+            "{:>16} {:>16} {:>16} {:>7}  '{}'",
+            format!("{cur} {}", f64_to_currency(t.direct, 2)),
+            format!(
+                "{cur} {}",
+                f64_to_currency(t.shared.values().sum::<f64>(), 2)
+            )
+            .yellow(),
+            format!("{cur} {}", f64_to_currency(t.total, 2)).bold(),
+            format!("{:.1}%", t.pct),
+            t.team,
+        );
+    }
+    let allocated: f64 = table.teams.iter().map(|t| t.total).sum();
+    println!(
+        "Total allocated {cur} {} of bill {cur} {}",
+        f64_to_currency(allocated, 2).bold(),
+        f64_to_currency(table.total, 2),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, close, row};
+
+    fn sample() -> Bills {
+        bills(vec![
+            row().rg("rg-a").tags(r#""team": "a""#).cost(30.0),
+            row().rg("rg-b").tags(r#""team": "b""#).cost(10.0),
+            row().rg("rg-x").cost(5.0),
+            row().rg("rg-hub").cost(40.0),
+            row().rg("rg-logs").cost(9.0),
+            row().rg("mc_aks").tags(r#""pool": "system""#).cost(10.0),
+            row().rg("mc_aks").tags(r#""pool": "user""#).cost(1.0),
+        ])
+    }
+
+    fn rules(json: &str) -> AllocationRules {
+        AllocationRules::from_json(json, &FilterOpts::default()).unwrap()
+    }
+
+    fn find<'a>(t: &'a AllocationTable, team: &str) -> &'a TeamAllocation {
+        t.teams.iter().find(|t| t.team == team).unwrap()
+    }
+
+    #[test]
+    fn proportional_fixed_even_sum_to_total() {
+        let r = rules(
+            r#"{
+            "team_tag": "Team",
+            "pools": [
+                { "name": "hub", "resource_group": "^rg-hub$", "allocation": { "method": "proportional" } },
+                { "name": "logs", "resource_group": "logs",
+                  "allocation": { "method": "fixed", "shares": { "a": 50, "b": 25 } } },
+                { "name": "aks", "resource_group": "^mc_", "tags": ["pool=system"],
+                  "allocation": { "method": "even", "teams": ["a", "b"] } }
+            ]
+        }"#,
+        );
+        let t = allocate(
+            &sample(),
+            &BillFilter::match_all(&FilterOpts::default()),
+            &r,
+        );
+        assert!(close(t.total, 105.0));
+        let sum: f64 = t.teams.iter().map(|t| t.total).sum();
+        assert!(close(sum, t.total));
+
+        let a = find(&t, "a");
+        assert!(close(a.direct, 30.0));
+        assert!(close(a.shared["hub"], 30.0), "3/4 of hub");
+        assert!(close(a.shared["logs"], 4.5));
+        assert!(close(a.shared["aks"], 5.0));
+        let b = find(&t, "b");
+        assert!(close(b.shared["hub"], 10.0));
+        let un = find(&t, "unallocated");
+        // rg-x untagged 5 + aks user pool row 1 (not in pool) + 25% of logs
+        assert!(close(un.direct, 6.0));
+        assert!(close(un.shared["logs"], 2.25));
+        assert_eq!(t.pools[2].rows, 1);
+        assert_eq!(t.teams[0].team, "a");
+    }
+
+    #[test]
+    fn pool_without_teams_goes_to_unallocated_and_first_pool_wins() {
+        let r = rules(
+            r#"{
+            "team_tag": "owner",
+            "unallocated_team": "platform",
+            "pools": [
+                { "name": "all-rg", "resource_group": "^rg-", "allocation": { "method": "proportional" } },
+                { "name": "hub", "resource_group": "hub", "allocation": { "method": "even" } }
+            ]
+        }"#,
+        );
+        let t = allocate(
+            &sample(),
+            &BillFilter::match_all(&FilterOpts::default()),
+            &r,
+        );
+        assert_eq!(t.pools[1].rows, 0, "rg-hub already matched the first pool");
+        let p = find(&t, "platform");
+        assert!(close(p.shared["all-rg"], 94.0));
+        assert!(close(p.total, t.total));
+    }
+
+    #[test]
+    fn invalid_rules_and_csv() {
+        let opts = FilterOpts::default();
+        let bad_shares = r#"{"team_tag": "t", "pools": [{"name": "p",
+            "allocation": {"method": "fixed", "shares": {"a": 80, "b": 30}}}]}"#;
+        assert!(AllocationRules::from_json(bad_shares, &opts).is_err());
+        let bad_re = r#"{"team_tag": "t", "pools": [{"name": "p", "resource_group": "(",
+            "allocation": {"method": "even"}}]}"#;
+        assert!(AllocationRules::from_json(bad_re, &opts).is_err());
+        assert!(AllocationRules::from_json(r#"{"pools": []}"#, &opts).is_err());
+
+        let r = rules(
+            r#"{"team_tag": "team", "pools": [{"name": "hub", "resource_group": "hub",
+                "allocation": {"method": "proportional", "teams": ["b"]}}]}"#,
+        );
+        let t = allocate(&sample(), &BillFilter::match_all(&opts), &r);
+        assert!(close(find(&t, "b").shared["hub"], 40.0));
+        let csv = allocation_to_csv(&t).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "team,direct,pool:hub,shared,total,pct"
+        );
+        assert_eq!(lines.next().unwrap(), "b,10.00,40.00,40.00,50.00,47.6");
+    }
+}
@@ -0,0 +1,298 @@
+//! Resource-level month-over-month diff.
+//!
+//! `SummaryData::merge_summaries` diffs two bills at the aggregate level
+//! (per `CostType` name). `diff_bills` joins the rows of two `Bills` by
+//! resource ID — optionally also by meter — and reports, per resource, how
+//! much it cost in each bill and how that changed, in NZD and USD.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::bills::Bills;
+use crate::bills::bill_entry::BillEntry;
+use crate::bills::bill_filter::BillFilter;
+use crate::money::{Nzd, Usd};
+
+/// Changes smaller than half a cent are treated as unchanged.
+const UNCHANGED_EPSILON: f64 = 0.005;
+
+/// What happened to a resource between the previous and latest bill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Only in the latest bill.
+    Added,
+    /// Only in the previous bill.
+    Removed,
+    Increased,
+    Decreased,
+    Unchanged,
+}
+
+/// One joined resource (or resource + meter) in a [`BillDiff`].
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffRecord {
+    /// Join key: lowercased resource ID, or `{resource_group}/{resource_name}`
+    /// for rows without a resource ID (e.g. purchases).
+    pub resource_id: String,
+    pub resource_name: String,
+    pub resource_group: String,
+    pub subscription_name: String,
+    pub meter_category: String,
+    /// `"{meter_sub_category}/{meter_name}"` when diffing by meter, else empty.
+    pub meter: String,
+    pub kind: ChangeKind,
+    pub latest_cost: f64,
+    pub prev_cost: f64,
+    pub delta: f64,
+    /// `delta / prev_cost * 100`; `None` when the resource is new.
+    pub delta_pct: Option<f64>,
+    pub latest_cost_usd: f64,
+    pub prev_cost_usd: f64,
+    pub delta_usd: f64,
+    pub delta_pct_usd: Option<f64>,
+    /// Summed `quantity`; only comparable between bills when diffing by meter.
+    pub latest_quantity: f64,
+    pub prev_quantity: f64,
+}
+
+/// Result of [`diff_bills`]. Records are sorted by absolute NZD delta, largest first.
+#[derive(Debug, Serialize)]
+pub struct BillDiff {
+    pub latest_total: f64,
+    pub prev_total: f64,
+    pub latest_total_usd: f64,
+    pub prev_total_usd: f64,
+    pub records: Vec<DiffRecord>,
+}
+
+impl BillDiff {
+    pub fn delta(&self) -> f64 {
+        self.latest_total - self.prev_total
+    }
+    pub fn delta_usd(&self) -> f64 {
+        self.latest_total_usd - self.prev_total_usd
+    }
+    /// Count of records per `ChangeKind`.
+    pub fn count_by_kind(&self) -> HashMap<ChangeKind, usize> {
+        let mut counts = HashMap::new();
+        for r in &self.records {
+            *counts.entry(r.kind).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Join key for one bill row.
+fn diff_key(bill: &BillEntry, by_meter: bool) -> (String, String) {
+    let resource = if bill.resource_id.is_empty() {
+        format!("{}/{}", bill.resource_group, bill.resource_name).to_lowercase()
+    } else {
+        bill.resource_id.to_lowercase()
+    };
+    let meter = if by_meter {
+        format!("{}/{}", bill.meter_sub_category, bill.meter_name)
+    } else {
+        String::new()
+    };
+    (resource, meter)
+}
+
+#[derive(Default)]
+struct Side {
+    cost: Nzd,
+    cost_usd: Usd,
+    quantity: f64,
+    present: bool,
+}
+
+struct Joined<'a> {
+    first: &'a BillEntry,
+    latest: Side,
+    prev: Side,
+}
+
+fn pct(delta: f64, prev: f64) -> Option<f64> {
+    if prev.abs() < f64::EPSILON {
+        None
+    } else {
+        Some(delta / prev * 100.0)
+    }
+}
+
+/// Diff `latest` against `prev`, restricted to rows matching `filter`.
+///
+/// Rows are joined by resource ID; with `by_meter` the meter sub-category and
+/// name are added to the key so a resource that moved between meters shows
+/// as one removed and one added record.
+pub fn diff_bills(latest: &Bills, prev: &Bills, filter: &BillFilter, by_meter: bool) -> BillDiff {
+    let mut joined: HashMap<(String, String), Joined> = HashMap::new();
+    for (bills, is_latest) in [(latest, true), (prev, false)] {
+        for bill in bills.bills.iter().filter(|b| filter.matches(b)) {
+            let j = joined
+                .entry(diff_key(bill, by_meter))
+                .or_insert_with(|| Joined {
+                    first: bill,
+                    latest: Side::default(),
+                    prev: Side::default(),
+                });
+            let side = if is_latest {
+                &mut j.latest
+            } else {
+                &mut j.prev
+            };
+            side.cost += bill.cost;
+            side.cost_usd += bill.cost_usd;
+            side.quantity += bill.quantity;
+            side.present = true;
+        }
+    }
+
+    let mut diff = BillDiff {
+        latest_total: 0.0,
+        prev_total: 0.0,
+        latest_total_usd: 0.0,
+        prev_total_usd: 0.0,
+        records: Vec::with_capacity(joined.len()),
+    };
+    for ((resource_id, meter), j) in joined {
+        let latest_cost = j.latest.cost.amount();
+        let prev_cost = j.prev.cost.amount();
+        let delta = latest_cost - prev_cost;
+        let delta_usd = j.latest.cost_usd.amount() - j.prev.cost_usd.amount();
+        let kind = match (j.latest.present, j.prev.present) {
+            (true, false) => ChangeKind::Added,
+            (false, true) => ChangeKind::Removed,
+            _ if delta > UNCHANGED_EPSILON => ChangeKind::Increased,
+            _ if delta < -UNCHANGED_EPSILON => ChangeKind::Decreased,
+            _ => ChangeKind::Unchanged,
+        };
+        diff.latest_total += latest_cost;
+        diff.prev_total += prev_cost;
+        diff.latest_total_usd += j.latest.cost_usd.amount();
+        diff.prev_total_usd += j.prev.cost_usd.amount();
+        diff.records.push(DiffRecord {
+            resource_id,
+            resource_name: j.first.resource_name.clone(),
+            resource_group: j.first.resource_group.clone(),
+            subscription_name: j.first.subscription_name.clone(),
+            meter_category: j.first.meter_category.clone(),
+            meter,
+            kind,
+            latest_cost,
+            prev_cost,
+            delta,
+            delta_pct: pct(delta, prev_cost),
+            latest_cost_usd: j.latest.cost_usd.amount(),
+            prev_cost_usd: j.prev.cost_usd.amount(),
+            delta_usd,
+            delta_pct_usd: pct(delta_usd, j.prev.cost_usd.amount()),
+            latest_quantity: j.latest.quantity,
+            prev_quantity: j.prev.quantity,
+        });
+    }
+    diff.records.sort_by(|a, b| {
+        b.delta
+            .abs()
+            .partial_cmp(&a.delta.abs())
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.resource_id.cmp(&b.resource_id))
+            .then_with(|| a.meter.cmp(&b.meter))
+    });
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+    use std::path::PathBuf;
+
+    fn no_filter() -> BillFilter {
+        BillFilter::match_all(&FilterOpts::default())
+    }
+
+    fn load(path: &str) -> Bills {
+        let mut bills = Bills::default();
+        bills
+            .parse_csv(&PathBuf::from(path), &FilterOpts::default())
+            .expect("parse failed");
+        bills
+    }
+
+    fn find<'a>(diff: &'a BillDiff, name: &str) -> &'a DiffRecord {
+        diff.records
+            .iter()
+            .find(|r| r.resource_name == name)
+            .unwrap_or_else(|| panic!("{name} missing"))
+    }
+
+    #[test]
+    fn diff_classifies_added_removed_increased() {
+        let latest = load("tests/azure_test_nzd_usd_latest.csv");
+        let prev = load("tests/azure_test_nzd_usd_prev.csv");
+        let diff = diff_bills(&latest, &prev, &no_filter(), false);
+
+        let a = find(&diff, "vm-a");
+        assert_eq!(a.kind, ChangeKind::Increased);
+        assert_eq!(a.delta, 20.0);
+        assert_eq!(a.delta_usd, 12.0);
+        assert_eq!(a.delta_pct, Some(25.0));
+        assert_eq!(a.delta_pct_usd, Some(25.0));
+
+        let b = find(&diff, "vm-b");
+        assert_eq!(b.kind, ChangeKind::Added);
+        assert_eq!(b.delta, 50.0);
+        assert_eq!(b.delta_pct, None);
+
+        let c = find(&diff, "vm-c");
+        assert_eq!(c.kind, ChangeKind::Removed);
+        assert_eq!(c.delta, -40.0);
+        assert_eq!(c.delta_pct, Some(-100.0));
+
+        // Totals reconcile with the aggregate merge_summaries view.
+        assert_eq!(diff.delta(), 30.0);
+        assert_eq!(diff.delta_usd(), 18.0);
+        // Sorted by absolute delta.
+        assert_eq!(diff.records[0].resource_name, "vm-b");
+    }
+
+    #[test]
+    fn diff_subtracts_costs_instead_of_dropping_rows() {
+        let latest = bills(vec![row().id("/x/vm1").meter_name("d4").cost(30.0)]);
+        let prev = bills(vec![row().id("/x/vm1").meter_name("d4").cost(40.0)]);
+        let diff = diff_bills(&latest, &prev, &no_filter(), false);
+        assert_eq!(diff.records.len(), 1);
+        assert_eq!(diff.records[0].kind, ChangeKind::Decreased);
+        assert_eq!(diff.records[0].delta, -10.0);
+    }
+
+    #[test]
+    fn diff_join_ignores_resource_id_case() {
+        let latest = bills(vec![row().id("/X/VM1").meter_name("d4").cost(10.0)]);
+        let prev = bills(vec![row().id("/x/vm1").meter_name("d4").cost(10.0)]);
+        let diff = diff_bills(&latest, &prev, &no_filter(), false);
+        assert_eq!(diff.records.len(), 1);
+        assert_eq!(diff.records[0].kind, ChangeKind::Unchanged);
+    }
+
+    #[test]
+    fn diff_by_meter_splits_records() {
+        let latest = bills(vec![
+            row().id("/x/vm1").meter_name("compute").cost(10.0),
+            row().id("/x/vm1").meter_name("egress").cost(5.0),
+        ]);
+        let prev = bills(vec![row().id("/x/vm1").meter_name("compute").cost(10.0)]);
+
+        let by_resource = diff_bills(&latest, &prev, &no_filter(), false);
+        assert_eq!(by_resource.records.len(), 1);
+        assert_eq!(by_resource.records[0].kind, ChangeKind::Increased);
+
+        let by_meter = diff_bills(&latest, &prev, &no_filter(), true);
+        assert_eq!(by_meter.records.len(), 2);
+        let counts = by_meter.count_by_kind();
+        assert_eq!(counts.get(&ChangeKind::Added), Some(&1));
+        assert_eq!(counts.get(&ChangeKind::Unchanged), Some(&1));
+    }
+}
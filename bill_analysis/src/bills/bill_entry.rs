@@ -6,7 +6,7 @@ use crate::bills::tags::Tags;
 use crate::money::{Nzd, Usd};
 
 //struct to hold bill data for Azure detailed Enrollment csv parsed file
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[allow(unused)]
 pub struct BillEntry {
@@ -1,5 +1,138 @@
+use crate::bills::bill_entry::BillEntry;
+use crate::bills::cost_query::extract_resource_type;
+use crate::bills::date_range::DateRange;
+use crate::bills::filter_expr::FilterExpr;
+use crate::bills::tag_predicate::TagPredicate;
 use crate::cmd_parse::FilterOpts;
 use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+
+/// Row dimension an [`Exclude`] pattern is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDimension {
+    Name,
+    ResourceGroup,
+    Subscription,
+    MeterCategory,
+    Location,
+    Reservation,
+    /// The lowercase tag string, as for `tag_filter`.
+    Tag,
+    InvoiceSection,
+    /// ARM resource type from `resource_id`, e.g. `microsoft.compute/disks`.
+    ResourceType,
+    MeterName,
+    ChargeType,
+    PricingModel,
+    Publisher,
+}
+
+impl FilterDimension {
+    pub const ALL: [FilterDimension; 13] = [
+        FilterDimension::Name,
+        FilterDimension::ResourceGroup,
+        FilterDimension::Subscription,
+        FilterDimension::MeterCategory,
+        FilterDimension::Location,
+        FilterDimension::Reservation,
+        FilterDimension::Tag,
+        FilterDimension::InvoiceSection,
+        FilterDimension::ResourceType,
+        FilterDimension::MeterName,
+        FilterDimension::ChargeType,
+        FilterDimension::PricingModel,
+        FilterDimension::Publisher,
+    ];
+
+    /// Argument name, e.g. `resource_group` (CLI `--exclude-resource-group`, MCP `exclude.resource_group`).
+    pub fn arg_name(&self) -> &'static str {
+        match self {
+            FilterDimension::Name => "name",
+            FilterDimension::ResourceGroup => "resource_group",
+            FilterDimension::Subscription => "subscription",
+            FilterDimension::MeterCategory => "meter_category",
+            FilterDimension::Location => "location",
+            FilterDimension::Reservation => "reservation",
+            FilterDimension::Tag => "tag",
+            FilterDimension::InvoiceSection => "invoice_section",
+            FilterDimension::ResourceType => "resource_type",
+            FilterDimension::MeterName => "meter_name",
+            FilterDimension::ChargeType => "charge_type",
+            FilterDimension::PricingModel => "pricing_model",
+            FilterDimension::Publisher => "publisher",
+        }
+    }
+
+    pub fn from_arg_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.arg_name() == name)
+    }
+
+    pub(crate) fn value<'a>(&self, bill: &'a BillEntry) -> Cow<'a, str> {
+        Cow::Borrowed(match self {
+            FilterDimension::Name => &bill.resource_name,
+            FilterDimension::ResourceGroup => &bill.resource_group,
+            FilterDimension::Subscription => &bill.subscription_name,
+            FilterDimension::MeterCategory => &bill.meter_category,
+            FilterDimension::Location => &bill.resource_location,
+            FilterDimension::Reservation => &bill.benefit_name,
+            FilterDimension::Tag => &bill.tags.value,
+            FilterDimension::InvoiceSection => &bill.invoice_section,
+            FilterDimension::ResourceType => {
+                return Cow::Owned(extract_resource_type(&bill.resource_id));
+            }
+            FilterDimension::MeterName => &bill.meter_name,
+            FilterDimension::ChargeType => &bill.charge_type,
+            FilterDimension::PricingModel => &bill.pricing_model,
+            FilterDimension::Publisher => &bill.publisher_name,
+        })
+    }
+}
+
+/// Drop rows whose `dimension` matches a regex — "everything except ...",
+/// which the `regex` crate cannot express with negative lookahead.
+/// On `Location` the pattern `none` drops rows without a location, as for the include filter.
+#[derive(Debug, Clone)]
+pub struct Exclude {
+    pub dimension: FilterDimension,
+    pub pattern: String,
+    re: Regex,
+}
+
+impl Exclude {
+    pub fn new(
+        dimension: FilterDimension,
+        pattern: &str,
+        case_sensitive: bool,
+    ) -> Result<Self, regex::Error> {
+        Ok(Exclude {
+            dimension,
+            pattern: pattern.to_string(),
+            re: RegexBuilder::new(pattern)
+                .case_insensitive(!case_sensitive)
+                .build()?,
+        })
+    }
+
+    /// `true` when `bill` is excluded.
+    pub fn matches(&self, bill: &BillEntry) -> bool {
+        let value = self.dimension.value(bill);
+        if self.dimension == FilterDimension::Location && self.pattern == "none" {
+            return value.is_empty();
+        }
+        self.re.is_match(&value)
+    }
+
+    /// `true` when any of `excludes` drops `bill`.
+    pub fn any_match(excludes: &[Exclude], bill: &BillEntry) -> bool {
+        excludes.iter().any(|e| e.matches(bill))
+    }
+}
+
+impl std::fmt::Display for Exclude {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}!~{}", self.dimension.arg_name(), self.pattern)
+    }
+}
 
 /// `BillFilter` groups all per-query filter parameters that narrow down which
 /// bill rows are included in a `cost_by_any_summary` result.
@@ -10,7 +143,7 @@ use regex::{Regex, RegexBuilder};
 /// Regex patterns are compiled once at construction time. `new()` returns
 /// `Err` immediately for any invalid regex, so the caller catches bad input
 /// before processing any bill rows.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BillFilter {
     // Pattern strings — kept for `{filter:?}` debug output and display.
     pub name: String,
@@ -25,6 +158,24 @@ pub struct BillFilter {
     pub tag_summarise: String,
     pub tag_filter: String,
     pub invoice_section: String,
+    /// Structured per-key tag predicates, all must match. See `with_tag_predicates`.
+    pub tag_predicates: Vec<TagPredicate>,
+    /// Boolean `--where` expression, ANDed with the other dimensions. See `with_where`.
+    pub where_expr: Option<FilterExpr>,
+    /// Exclude patterns, a row matching any of them is dropped. See `with_excludes`.
+    pub excludes: Vec<Exclude>,
+    /// Extra include patterns on a dimension that already has one, a row must
+    /// match all of them. Kept as `Exclude`s whose match is required. See `and_pattern`.
+    pub and_patterns: Vec<Exclude>,
+    /// Usage-date bounds (`--from` / `--to`), inclusive. See `with_date_range`.
+    pub date_range: DateRange,
+    /// Regex on the ARM resource type (`microsoft.compute/disks`). This and the
+    /// fields below are set with `with_pattern`.
+    pub resource_type: String,
+    pub meter_name: String,
+    pub charge_type: String,
+    pub pricing_model: String,
+    pub publisher: String,
     // Pre-compiled regexes for all pattern fields (not tag_summarise).
     pub(crate) re_name: Regex,
     pub(crate) re_resource_group: Regex,
@@ -34,6 +185,11 @@ pub struct BillFilter {
     pub(crate) re_reservation: Regex,
     pub(crate) re_tag_filter: Regex,
     pub(crate) re_invoice_section: Regex,
+    pub(crate) re_resource_type: Regex,
+    pub(crate) re_meter_name: Regex,
+    pub(crate) re_charge_type: Regex,
+    pub(crate) re_pricing_model: Regex,
+    pub(crate) re_publisher: Regex,
     /// Whether tag key lookups use exact case (`true`) or lowercase (`false`).
     pub(crate) case_sensitive: bool,
 }
@@ -85,6 +241,11 @@ impl BillFilter {
             re_reservation: build_re_with_case(&reservation)?,
             re_tag_filter: build_re_with_case(&tag_filter)?,
             re_invoice_section: build_re_with_case(&invoice_section)?,
+            re_resource_type: build_re_with_case("")?,
+            re_meter_name: build_re_with_case("")?,
+            re_charge_type: build_re_with_case("")?,
+            re_pricing_model: build_re_with_case("")?,
+            re_publisher: build_re_with_case("")?,
             case_sensitive: filter_opts.case_sensitive,
             name,
             resource_group,
@@ -95,6 +256,303 @@ impl BillFilter {
             tag_summarise,
             tag_filter,
             invoice_section,
+            tag_predicates: Vec::new(),
+            where_expr: None,
+            excludes: Vec::new(),
+            and_patterns: Vec::new(),
+            date_range: DateRange::default(),
+            resource_type: String::new(),
+            meter_name: String::new(),
+            charge_type: String::new(),
+            pricing_model: String::new(),
+            publisher: String::new(),
         })
     }
+
+    /// Add structured tag predicates (`env=prod`, `!owner`, ...) on top of the regex filters.
+    pub fn with_tag_predicates(mut self, tag_predicates: Vec<TagPredicate>) -> Self {
+        self.tag_predicates = tag_predicates;
+        self
+    }
+
+    /// Add a boolean expression (`rg ~ prod and not tag.owner`) on top of the other filters.
+    pub fn with_where(mut self, where_expr: Option<FilterExpr>) -> Self {
+        self.where_expr = where_expr;
+        self
+    }
+
+    /// Drop rows matching any of `excludes`, applied after the include filters.
+    pub fn with_excludes(mut self, excludes: Vec<Exclude>) -> Self {
+        self.excludes = excludes;
+        self
+    }
+
+    /// Keep only rows whose usage date lies in `date_range`.
+    pub fn with_date_range(mut self, date_range: DateRange) -> Self {
+        self.date_range = date_range;
+        self
+    }
+
+    /// Set the include regex of `dimension`, e.g. the ARM resource type or meter
+    /// name, which have no `new` argument. An empty pattern matches all rows.
+    pub fn with_pattern(
+        mut self,
+        dimension: FilterDimension,
+        pattern: &str,
+    ) -> Result<Self, regex::Error> {
+        let re = RegexBuilder::new(pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()?;
+        let (field, re_field) = match dimension {
+            FilterDimension::Name => (&mut self.name, &mut self.re_name),
+            FilterDimension::ResourceGroup => {
+                (&mut self.resource_group, &mut self.re_resource_group)
+            }
+            FilterDimension::Subscription => (&mut self.subscription, &mut self.re_subscription),
+            FilterDimension::MeterCategory => {
+                (&mut self.meter_category, &mut self.re_meter_category)
+            }
+            FilterDimension::Location => (&mut self.location, &mut self.re_location),
+            FilterDimension::Reservation => (&mut self.reservation, &mut self.re_reservation),
+            FilterDimension::Tag => (&mut self.tag_filter, &mut self.re_tag_filter),
+            FilterDimension::InvoiceSection => {
+                (&mut self.invoice_section, &mut self.re_invoice_section)
+            }
+            FilterDimension::ResourceType => (&mut self.resource_type, &mut self.re_resource_type),
+            FilterDimension::MeterName => (&mut self.meter_name, &mut self.re_meter_name),
+            FilterDimension::ChargeType => (&mut self.charge_type, &mut self.re_charge_type),
+            FilterDimension::PricingModel => (&mut self.pricing_model, &mut self.re_pricing_model),
+            FilterDimension::Publisher => (&mut self.publisher, &mut self.re_publisher),
+        };
+        *field = pattern.to_string();
+        *re_field = re;
+        Ok(self)
+    }
+
+    /// Narrow `dimension` by `pattern`: a row must match it and the dimension's
+    /// existing pattern, e.g. a REPL `set rg web` on top of `--resource-group prod`.
+    /// Same as `with_pattern` when the dimension is unset; an empty pattern is a no-op.
+    pub fn and_pattern(
+        mut self,
+        dimension: FilterDimension,
+        pattern: &str,
+    ) -> Result<Self, regex::Error> {
+        if pattern.is_empty() {
+            return Ok(self);
+        }
+        let current = match dimension {
+            FilterDimension::Name => &self.name,
+            FilterDimension::ResourceGroup => &self.resource_group,
+            FilterDimension::Subscription => &self.subscription,
+            FilterDimension::MeterCategory => &self.meter_category,
+            FilterDimension::Location if matches!(self.location.as_str(), "any" | "all") => "",
+            FilterDimension::Location => &self.location,
+            FilterDimension::Reservation => &self.reservation,
+            FilterDimension::Tag => &self.tag_filter,
+            FilterDimension::InvoiceSection => &self.invoice_section,
+            FilterDimension::ResourceType => &self.resource_type,
+            FilterDimension::MeterName => &self.meter_name,
+            FilterDimension::ChargeType => &self.charge_type,
+            FilterDimension::PricingModel => &self.pricing_model,
+            FilterDimension::Publisher => &self.publisher,
+        };
+        if current.is_empty() {
+            return self.with_pattern(dimension, pattern);
+        }
+        let and = Exclude::new(dimension, pattern, self.case_sensitive)?;
+        self.and_patterns.push(and);
+        Ok(self)
+    }
+
+    /// A filter with every dimension unset, i.e. matching all rows.
+    pub fn match_all(filter_opts: &FilterOpts) -> Self {
+        Self::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            filter_opts,
+        )
+        .expect("empty patterns are valid regexes")
+    }
+
+    /// Returns `true` when `bill` passes every filter dimension that is set.
+    pub fn matches(&self, bill: &BillEntry) -> bool {
+        if (!self.name.is_empty() && !self.re_name.is_match(&bill.resource_name))
+            || (!self.resource_group.is_empty()
+                && !self.re_resource_group.is_match(&bill.resource_group))
+            || (!self.subscription.is_empty()
+                && !self.re_subscription.is_match(&bill.subscription_name))
+            || (!self.meter_category.is_empty()
+                && !self.re_meter_category.is_match(&bill.meter_category))
+            || (!self.tag_filter.is_empty() && !self.re_tag_filter.is_match(&bill.tags.value))
+            || !TagPredicate::all_match(&self.tag_predicates, &bill.tags)
+            || (!self.reservation.is_empty() && !self.re_reservation.is_match(&bill.benefit_name))
+            || (!self.invoice_section.is_empty()
+                && !self.re_invoice_section.is_match(&bill.invoice_section))
+            || self.where_expr.as_ref().is_some_and(|e| !e.matches(bill))
+            || Exclude::any_match(&self.excludes, bill)
+            || self.and_patterns.iter().any(|p| !p.matches(bill))
+            || !self.date_range.contains(&bill.date)
+            || (!self.resource_type.is_empty()
+                && !self
+                    .re_resource_type
+                    .is_match(&extract_resource_type(&bill.resource_id)))
+            || (!self.meter_name.is_empty() && !self.re_meter_name.is_match(&bill.meter_name))
+            || (!self.charge_type.is_empty() && !self.re_charge_type.is_match(&bill.charge_type))
+            || (!self.pricing_model.is_empty()
+                && !self.re_pricing_model.is_match(&bill.pricing_model))
+            || (!self.publisher.is_empty() && !self.re_publisher.is_match(&bill.publisher_name))
+        {
+            return false;
+        }
+        match (
+            self.location.as_str(),
+            self.re_location.is_match(&bill.resource_location),
+            bill.resource_location.len(),
+        ) {
+            ("any", _, _) => true, // any(default) any region ok
+            ("all", _, _) => true,
+            ("none", _, 0) => true,  // no resource_location set
+            ("none", _, _) => false, // value set for resource_location, skip
+            (_, true, _) => true,    // location_regex set and match
+            (_, false, _) => false,  // location_regex set and no match
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::test_support::row;
+
+    #[test]
+    fn excludes_drop_matching_rows_after_includes() {
+        let opts = FilterOpts::default();
+        let filter = BillFilter::new(
+            None,
+            Some("prod".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &opts,
+        )
+        .unwrap()
+        .with_excludes(vec![
+            Exclude::new(FilterDimension::ResourceGroup, "^mc_", false).unwrap(),
+            Exclude::new(FilterDimension::Subscription, "SANDBOX", false).unwrap(),
+        ]);
+        assert!(filter.matches(&row().sub("core").rg("rg-prod").location("eastus")));
+        assert!(!filter.matches(&row().sub("core").rg("MC_prod_aks").location("eastus")));
+        assert!(!filter.matches(&row().sub("sandbox-1").rg("rg-prod").location("eastus")));
+        assert!(
+            !filter.matches(&row().sub("core").rg("rg-dev").location("eastus")),
+            "include still applies"
+        );
+    }
+
+    #[test]
+    fn location_none_keeps_only_rows_without_location() {
+        let opts = FilterOpts::default();
+        let location = |l: &str| {
+            BillFilter::new(
+                None,
+                None,
+                None,
+                None,
+                Some(l.to_string()),
+                None,
+                None,
+                None,
+                None,
+                &opts,
+            )
+            .unwrap()
+        };
+        let none = location("none");
+        assert!(none.matches(&row().sub("sub").rg("rg")));
+        assert!(!none.matches(&row().sub("sub").rg("rg").location("eastus")));
+        for all in ["any", "all"] {
+            assert!(location(all).matches(&row().sub("sub").rg("rg")));
+            assert!(location(all).matches(&row().sub("sub").rg("rg").location("eastus")));
+        }
+        let east = location("^east");
+        assert!(east.matches(&row().sub("sub").rg("rg").location("eastus")));
+        assert!(!east.matches(&row().sub("sub").rg("rg").location("westus")));
+    }
+
+    #[test]
+    fn exclude_location_none_and_arg_names() {
+        let none = Exclude::new(FilterDimension::Location, "none", false).unwrap();
+        assert!(none.matches(&row()));
+        assert!(!none.matches(&row().location("eastus")));
+        assert_eq!(none.to_string(), "location!~none");
+        for d in FilterDimension::ALL {
+            assert_eq!(FilterDimension::from_arg_name(d.arg_name()), Some(d));
+        }
+        assert!(Exclude::new(FilterDimension::Tag, "(", false).is_err());
+    }
+
+    #[test]
+    fn and_pattern_keeps_the_existing_pattern() {
+        let opts = FilterOpts::default();
+        let filter = BillFilter::match_all(&opts)
+            .with_pattern(FilterDimension::ResourceGroup, "prod")
+            .unwrap()
+            .and_pattern(FilterDimension::ResourceGroup, "web")
+            .unwrap();
+        assert!(filter.matches(&row().rg("prod-web")));
+        assert!(!filter.matches(&row().rg("prod-db")));
+        assert!(!filter.matches(&row().rg("dev-web")));
+
+        // unset dimension: same as with_pattern
+        let filter = BillFilter::match_all(&opts)
+            .and_pattern(FilterDimension::Location, "east")
+            .unwrap();
+        assert_eq!(filter.location, "east");
+        assert!(filter.and_patterns.is_empty());
+        assert!(filter.matches(&row().rg("rg").location("eastus")));
+        assert!(!filter.matches(&row().rg("rg").location("westus")));
+    }
+
+    #[test]
+    fn with_pattern_filters_added_dimensions() {
+        let mut disk = row().sub("sub").rg("rg").location("eastus");
+        disk.resource_id =
+            "/subscriptions/1/resourceGroups/rg/providers/Microsoft.Compute/disks/d1".to_string();
+        disk.pricing_model = "OnDemand".to_string();
+        let mut spot = row().sub("sub").rg("rg").location("eastus");
+        spot.pricing_model = "Spot".to_string();
+        spot.charge_type = "Usage".to_string();
+
+        let filter = BillFilter::match_all(&FilterOpts::default())
+            .with_pattern(FilterDimension::ResourceType, "compute/disks$")
+            .unwrap();
+        assert_eq!(filter.resource_type, "compute/disks$");
+        assert!(filter.matches(&disk) && !filter.matches(&spot));
+
+        let filter = BillFilter::match_all(&FilterOpts::default())
+            .with_pattern(FilterDimension::PricingModel, "spot")
+            .unwrap()
+            .with_pattern(FilterDimension::ChargeType, "^usage$")
+            .unwrap();
+        assert!(!filter.matches(&disk) && filter.matches(&spot));
+
+        let exclude = Exclude::new(FilterDimension::ResourceType, "disks", false).unwrap();
+        assert!(exclude.matches(&disk) && !exclude.matches(&spot));
+        assert!(
+            BillFilter::match_all(&FilterOpts::default())
+                .with_pattern(FilterDimension::Publisher, "(")
+                .is_err()
+        );
+    }
 }
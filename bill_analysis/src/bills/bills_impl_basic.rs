@@ -1,5 +1,8 @@
 use crate::bills::Bills;
 use crate::bills::bill_entry::BillEntry;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::bills_sum_data::reservation_flex_group;
+use crate::bills::coverage::CoverageRow;
 use crate::bills::summary::Summary;
 use crate::money::{Nzd, Usd};
 // use crate::bills::bills_struct::Bills;
@@ -8,16 +11,17 @@ use std::collections::HashMap;
 // use summary::Summary;
 
 impl Bills {
-    pub fn remove(&mut self, other: Bills) {
-        // ToDo: Probaly faulty, only drops matching values.
-        //       Move logic into summary to use both bills.
-        // create HashMap from other.bills to use as lookup from self.bills
-        let b2: HashMap<&BillEntry, ()> = HashMap::from_iter(other.bills.iter().map(|b| (b, ())));
-        // retain only the bills that are not in other.bills(b2) using hash lookup
-        self.bills.retain(|x| !b2.contains_key(x));
+    pub fn calc_all_totals(&mut self) {
+        self.summary = Self::calc_totals(self.bills.iter());
     }
 
-    pub fn calc_all_totals(&mut self) {
+    /// Like `calc_all_totals`, but only over the rows that pass `filter`.
+    /// Returns the `Summary` instead of storing it on `self`.
+    pub fn calc_filtered_totals(&self, filter: &BillFilter) -> Summary {
+        Self::calc_totals(self.bills.iter().filter(|bill| filter.matches(bill)))
+    }
+
+    fn calc_totals<'a>(bills: impl Iterator<Item = &'a BillEntry>) -> Summary {
         let mut total_cost = Nzd::default();
         let mut total_cost_usd = Usd::default();
         let mut total_no_reservation = Usd::default();
@@ -26,7 +30,7 @@ impl Bills {
         let mut total_savings_un_used = Usd::default();
         let mut total_savings_meter_category_map: HashMap<String, (Usd, Usd)> = HashMap::new();
         // Loop over all bills.
-        for bill in &self.bills {
+        for bill in bills {
             total_cost += bill.cost;
             total_cost_usd += bill.cost_usd;
             total_no_reservation += Usd(bill.unit_price * bill.quantity);
@@ -60,7 +64,7 @@ impl Bills {
                 );
             }
         }
-        self.summary = Summary {
+        Summary {
             total_cost,
             total_cost_usd,
             exchange_rate: if total_cost_usd.amount() != 0.0 {
@@ -161,6 +165,58 @@ impl Bills {
         }
         savings_map
     }
+    /// Reservation / savings plan coverage of eligible usage, per flex group and region,
+    /// restricted to rows matching `filter`. Sorted by on-demand cost, largest first.
+    ///
+    /// Covered = `PricingModel` "Reservation" or "SavingsPlan" usage, uncovered = "OnDemand".
+    /// Spot usage and unused reservation / savings plan rows are not counted.
+    pub fn reservation_coverage(&self, filter: &BillFilter) -> Vec<CoverageRow> {
+        let mut rows: HashMap<(String, String), CoverageRow> = HashMap::new();
+        for bill in self.bills.iter().filter(|b| filter.matches(b)) {
+            if bill.charge_type == "UnusedReservation" || bill.charge_type == "UnusedSavingsPlan" {
+                continue;
+            }
+            let covered = match bill.pricing_model.as_str() {
+                "Reservation" | "SavingsPlan" => true,
+                "OnDemand" | "" => false,
+                _ => continue, // e.g. Spot, not reservable
+            };
+            let Some(flex_group) = reservation_flex_group(bill) else {
+                continue;
+            };
+            let region = if bill.resource_location.is_empty() {
+                "none".to_string()
+            } else {
+                bill.resource_location.to_lowercase()
+            };
+            let row = rows
+                .entry((flex_group.clone(), region.clone()))
+                .or_insert_with(|| CoverageRow {
+                    flex_group,
+                    region,
+                    covered_hours: 0.0,
+                    uncovered_hours: 0.0,
+                    on_demand_cost: 0.0,
+                    on_demand_cost_usd: 0.0,
+                });
+            if covered {
+                row.covered_hours += bill.quantity;
+            } else {
+                row.uncovered_hours += bill.quantity;
+                row.on_demand_cost += bill.cost.amount();
+                row.on_demand_cost_usd += bill.cost_usd.amount();
+            }
+        }
+        let mut rows: Vec<CoverageRow> = rows.into_values().collect();
+        rows.sort_by(|a, b| {
+            b.on_demand_cost
+                .partial_cmp(&a.on_demand_cost)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.flex_group.cmp(&b.flex_group))
+                .then_with(|| a.region.cmp(&b.region))
+        });
+        rows
+    }
     // filter cost for specific resource e.g. disk
     pub fn cost_by_resource_name(&self, resource_name: &str) -> Nzd {
         self.bills.iter().fold(Nzd::default(), |acc, bill| {
@@ -184,6 +240,39 @@ impl Bills {
         self.bills.push(bill);
     }
 
+    /// One `Bills` with the rows of every bill in `months` (e.g. each month of a
+    /// `--from`/`--to` range) named `name`, totals recalculated.
+    /// Fails when the bills are in different billing currencies.
+    pub fn combine(months: &[&Bills], name: &str) -> Result<Bills, String> {
+        let mut combined = Bills {
+            file_name: name.to_string(),
+            file_short_name: name.to_string(),
+            ..Bills::default()
+        };
+        for month in months {
+            match (&combined.billing_currency, &month.billing_currency) {
+                (Some(cur), Some(other)) if cur != other => {
+                    return Err(format!(
+                        "Billing currency mismatch: {cur} and {other} in {}",
+                        month.file_short_name
+                    ));
+                }
+                (None, other) => combined.billing_currency = other.clone(),
+                _ => {}
+            }
+            combined.bills.extend(month.bills.iter().cloned());
+            combined.tag_names.extend(month.tag_names.iter().cloned());
+            combined
+                .tag_parse_errors
+                .extend(month.tag_parse_errors.iter().cloned());
+        }
+        combined.tag_merges = crate::bills::tag_alias::sum_tag_merges(
+            months.iter().flat_map(|m| m.tag_merges.iter().cloned()),
+        );
+        combined.calc_all_totals();
+        Ok(combined)
+    }
+
     /// Merge another `Bills` into `self`, appending all entries and recalculating totals.
     /// Used when combining multiple part CSVs from a single blob export into one dataset.
     pub fn extend_with(&mut self, other: Bills) {
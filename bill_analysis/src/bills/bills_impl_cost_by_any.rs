@@ -1,11 +1,9 @@
 use crate::bills::Bills;
 use crate::bills::bill_filter::BillFilter;
 
-use crate::bills::cost_type_enum::CostType;
-// use crate::bills::ReservationInfo;
 use crate::bills::bills_sum_data::SummaryData;
+use crate::bills::cost_type_enum::CostType;
 use crate::money::{Nzd, Usd};
-// use crate::RESERVATION_SUMMARY;
 
 impl Bills {
     // function cost_by_any
@@ -14,38 +12,25 @@ impl Bills {
     // returns total_filtered_cost,
     //         set of filtered resource groups,
     //     and HashMap of filtered cost per category(each category total - total filtered cost)
-    pub fn cost_by_any_summary(&self, filter: &BillFilter) -> SummaryData<'_> {
+    // only the `cost_types` asked for are keyed into per_type, e.g. the report sections.
+    pub fn cost_by_any_summary(
+        &self,
+        filter: &BillFilter,
+        cost_types: &[CostType],
+    ) -> SummaryData<'_> {
         // collect set of resource groups in set rgs
         let mut summary_data = SummaryData::default();
         // bill_details record cost per filter category e.g. name_regex, rg_regex, subs_regex, meter_category
         // per_type
         // iter through bills, get total and update new bill_details for each category.
+        let tag_key = if filter.case_sensitive {
+            filter.tag_summarise.to_string()
+        } else {
+            filter.tag_summarise.to_lowercase()
+        };
         let filtered_total = self.bills.iter().fold((Nzd::default(), Usd::default()), |acc, bill| {
-            let mut flag_match = true;
-            if (!filter.name.is_empty() && !filter.re_name.is_match(&bill.resource_name))
-                || (!filter.resource_group.is_empty() && !filter.re_resource_group.is_match(&bill.resource_group))
-                || (!filter.subscription.is_empty() && !filter.re_subscription.is_match(&bill.subscription_name))
-                || (!filter.meter_category.is_empty() && !filter.re_meter_category.is_match(&bill.meter_category))
-                || (!filter.tag_filter.is_empty() && !filter.re_tag_filter.is_match(&bill.tags.value)) // Check tags hashmap for match
-                || (!filter.reservation.is_empty() && !filter.re_reservation.is_match(&bill.benefit_name))
-                || (!filter.invoice_section.is_empty() && !filter.re_invoice_section.is_match(&bill.invoice_section))
-                || match (
-                    filter.location.as_str(),
-                    filter.location.len(),
-                    filter.re_location.is_match(&bill.resource_location),
-                    bill.resource_location.len(),
-                ) {
-                    ("any" , _, _    , _) => false, // any(default) any region ok, leave flag_match unchanged
-                    ("all" , _, _    , _) => false, // any(default) any region ok, leave flag_match unchanged
-                    ("none", _, _    , 1..) => true, // if value set for resource_location, set to false to skip
-                    (_, _, true, _) => false, // if location_regex set and match, leave unchanged
-                    (_     , _, false, _) => true, // if location_regex set and no match, set to false to skip
-                }
-            {
-                flag_match = false; // if filter set and no match skip
-            }
-            if flag_match {
-                // if flag_match still true (no filter above excluded this bill) add to summary_data
+            if filter.matches(bill) {
+                // no filter excluded this bill, add to summary_data
                 // record cost against resource_name, resource_group, subscription_name, meter_category, tag
                 let cost_unreserved = bill.unit_price * bill.quantity;
                 // do some sanity checks / assert's
@@ -66,166 +51,20 @@ impl Bills {
                     // currency (NZD). With FX conversion these are not equal, so no assertion here.
                 };
 
-                summary_data.accumulate(
-                    CostType::ResourceName,
-                    bill.resource_name.clone(),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                summary_data.accumulate(
-                    CostType::ResourceGroup,
-                    bill.resource_group.clone(),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                summary_data.accumulate(
-                    CostType::Subscription,
-                    bill.subscription_name.clone(),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                summary_data.accumulate(
-                    CostType::MeterCategory,
-                    bill.meter_category.clone(),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                summary_data.accumulate(
-                    CostType::Reservation,
-                    bill.benefit_name.clone(),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                let region = if bill.resource_location.is_empty() { "none" } else { &bill.resource_location };
-                summary_data.accumulate(
-                    CostType::Region,
-                    region.to_string(),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                let section = if !bill.invoice_section.is_empty() {
-                    bill.invoice_section.clone()
-                } else if !bill.meter_sub_category.is_empty() {
-                    format!("({})", bill.meter_sub_category)
-                } else {
-                    "none".to_string()
-                };
-                summary_data.accumulate(
-                    CostType::InvoiceSection,
-                    section,
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-
-                // add bill_details for tags, using the matched tag and value
-                if !filter.tag_summarise.is_empty() {
-                    let tag_summarize_lowercase = if filter.case_sensitive {
-                        filter.tag_summarise.to_string()
-                    } else {
-                        filter.tag_summarise.to_lowercase()
-                    };
-                    let tag_key = if bill.tags.kv.contains_key(&tag_summarize_lowercase) {
-                        let v = bill.tags.kv.get(&tag_summarize_lowercase).unwrap();
-                        format!("tag:{}={}", v.1, v.0)
-                    } else {
-                        "tag:none".to_string()
-                    };
+                // one row per requested dimension, keys as in CostType::key_of (shared with the query engine)
+                for &cost_type in cost_types {
+                    if cost_type == CostType::Tag && filter.tag_summarise.is_empty() {
+                        continue;
+                    }
                     summary_data.accumulate(
-                        CostType::Tag,
-                        tag_key,
+                        cost_type,
+                        cost_type.key_of(bill, &tag_key),
                         bill.cost,
                         bill.cost_usd,
                         cost_unreserved,
                     );
-                } // end tag_summarise
-
-                summary_data.accumulate(
-                    CostType::MeterSubCategory,
-                    format!("{}__{}", bill.meter_category, bill.meter_sub_category),
-                    bill.cost,
-                    bill.cost_usd,
-                    cost_unreserved,
-                );
-                // TODO: Add RESERVATION SUMMARY, struct added to Bills
-                // if RESERVATION_SUMMARY
-                //     .iter()
-                //     // check if unit_price > 0.0 to filter SQL Licence and storage at zero cost
-                //     .any(|(k,v)| {
-                //         *k == bill.meter_category &&
-                //         bill.unit_price > 0.0 &&
-                //         !v.iter().any(|rule| bill.meter_sub_category.contains(rule) )
-                //     })
-                //     {
-                //     // add to reservation summary
-                //     let savings = cost_unreserved - bill.cost;
-                //     if savings < -0.0001 && bill.charge_type != "UnusedReservation" {
-                //         println!(
-                //             "Over charge cost > unitprice*quantity:{} Name:{} RG:{} cost_unreserverd:{}, ChargeType:{}, LineCSV:{}",
-                //             savings,
-                //             bill.resource_name,
-                //             bill.resource_name,
-                //             cost_unreserved,
-                //             bill.charge_type,
-                //             bill.line_number_csv,
-                //         );
-                //     };
-                //     // assert!(bill.reservation_name != "", "No reservation name meter_category:{}, ChargeType:{}, LineCSV:{}",
-                //     //     bill.meter_category,
-                //     //     bill.charge_type,
-                //     //     bill.line_number_csv,
-                //     // );
-                //     summary_data
-                //         .reservations
-                //         .entry((
-                //             // TODO: make meter_sub_category complex, add MeterCategory, MeterSubCategory, MeterName and MeterRegion
-                //             format!("MC:{}__MSubC:{}",bill.meter_category,bill.meter_sub_category), // flex type e.g. "Dav4/Dasv4 Series"
-                //             bill.date[3..5].parse().expect(
-                //                 format!("Invalid date expected fmt mm/dd/yyyy {}", bill.date)
-                //                     .as_str(),
-                //             ),
-                //         ))
-                //         .and_modify(|e| {
-                //             e.cost_full += cost_unreserved;
-                //             e.cost_savings += savings;
-                //             e.hr_saving += if savings > 0.01 { bill.quantity } else { 0.0 };
-                //             e.hr_total += bill.quantity;
-                //             if bill.pricing_model == "Reservation" {
-                //                 e.cost_unused += if bill.charge_type == "UnusedReservation" {
-                //                     bill.cost
-                //                 } else { 0.00 };
-                //                 e.reservation_names.insert(&bill.reservation_name);
-                //                 e.vm_names_reserved.push(&bill.resource_name);
-                //             } else {
-                //                 e.vm_names_not_reserved.push(&bill.resource_name);
-                //             }
-                //         })
-                //         .or_insert(ReservationInfo {
-                //             cost_full: cost_unreserved,
-                //             cost_savings: savings,
-                //             hr_total: bill.quantity,
-                //             hr_saving: if savings > 0.01 { bill.quantity } else { 0.0 },
-                //             cost_unused: if bill.charge_type == "UnusedReservation" {
-                //                 bill.cost
-                //             } else { 0.00 },
-                //             reservation_names: if bill.reservation_name != "" { let mut rn = HashSet::<&str>::new(); rn.insert(&bill.reservation_name); rn } else { HashSet::new() },
-                //             vm_names_reserved: if bill.pricing_model == "Reservation" { vec![&bill.resource_name] } else { Vec::new() },
-                //             vm_names_not_reserved: if bill.pricing_model != "Reservation" { vec![&bill.resource_name] } else { Vec::new() },
-                //             meter_category: bill.meter_category.clone(),
-                //         });
-                // }
+                }
+                summary_data.accumulate_reservation(bill);
                 summary_data.details.insert(format!(
                     "{rg}_____{rn}_____{mc}",
                     rg = bill.resource_group.clone(),
@@ -334,7 +173,7 @@ mod tests {
             &FILTER_OPTS,
         )
         .expect("valid test filter");
-        let summary = bills.cost_by_any_summary(&filter);
+        let summary = bills.cost_by_any_summary(&filter, &CostType::ALL);
 
         // Both rows have MeterCategory=Compute → aggregate across both
         let mc_key = (CostType::MeterCategory, "Compute".to_string());
@@ -393,7 +232,7 @@ mod tests {
             &FILTER_OPTS,
         )
         .expect("valid test filter");
-        let summary = bills.cost_by_any_summary(&filter);
+        let summary = bills.cost_by_any_summary(&filter, &CostType::ALL);
 
         // Matching RG present with full row cost
         let rg_key = (CostType::ResourceGroup, "rg-delta-test".to_string());
@@ -407,7 +246,7 @@ mod tests {
         // Non-matching RG must be absent
         let excluded_key = (CostType::ResourceGroup, "rg-new-only".to_string());
         assert!(
-            summary.per_type.get(&excluded_key).is_none(),
+            !summary.per_type.contains_key(&excluded_key),
             "rg-new-only should be excluded by rg filter"
         );
 
@@ -445,7 +284,7 @@ mod tests {
             &FILTER_OPTS,
         )
         .expect("valid test filter");
-        let summary = bills.cost_by_any_summary(&filter);
+        let summary = bills.cost_by_any_summary(&filter, &CostType::ALL);
 
         let (rg_nzd_sum, rg_usd_sum) = summary
             .per_type
@@ -498,8 +337,8 @@ mod tests {
             &FILTER_OPTS,
         )
         .expect("valid test filter");
-        let latest_summary = latest_bills.cost_by_any_summary(&filter);
-        let prev_summary = prev_bills.cost_by_any_summary(&filter);
+        let latest_summary = latest_bills.cost_by_any_summary(&filter, &CostType::ALL);
+        let prev_summary = prev_bills.cost_by_any_summary(&filter, &CostType::ALL);
 
         // Simulate the merge/subtract from display.rs
         let mut merged = latest_summary.per_type;
@@ -548,4 +387,175 @@ mod tests {
         assert_eq!(gone_item.cost, Nzd(-40.0), "NZD should be -40.0");
         assert_eq!(gone_item.cost_usd, Usd(-24.0), "USD should be -24.0");
     }
+
+    fn no_filter() -> BillFilter {
+        BillFilter::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &FILTER_OPTS,
+        )
+        .expect("valid test filter")
+    }
+
+    const FLEX_KEY: &str = "MC:Compute__MSubC:Virtual Machines";
+
+    /// On-demand VM rows are summarised per flex group and day, with no savings or utilisation.
+    #[test]
+    fn test_reservation_summary_on_demand() {
+        let path = PathBuf::from("tests/azure_test_nzd_usd_latest.csv");
+        let mut bills = crate::bills::Bills::default();
+        bills.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        let summary = bills.cost_by_any_summary(&no_filter(), &CostType::ALL);
+
+        assert_eq!(summary.reservations.len(), 1, "one flex group, one day");
+        let day = summary
+            .reservations
+            .get(&(FLEX_KEY.to_string(), "2026-01-01".to_string()))
+            .expect("flex group 2026-01-01 missing");
+        assert!((day.cost_full - 0.134 * 744.0 * 2.0).abs() < 1e-9);
+        assert!(day.cost_savings.abs() < 1e-9);
+        assert_eq!(day.hr_total, 1488.0);
+        assert_eq!(day.hr_reserved, 0.0);
+        assert!(day.vm_names_reserved.is_empty());
+        assert_eq!(day.vm_names_not_reserved, vec!["vm-a", "vm-b"]);
+
+        let months = summary.reservation_month_totals();
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].0, FLEX_KEY);
+        assert_eq!(months[0].1.utilisation_pct(), None);
+    }
+
+    /// Reserved and unused-reservation rows give savings, unused cost and a utilisation %.
+    #[test]
+    fn test_reservation_summary_utilisation() {
+        let path = PathBuf::from("tests/azure_test_nzd_usd_prev.csv");
+        let mut bills = crate::bills::Bills::default();
+        bills.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        // vm-a ran under a reservation; 186 h of that reservation went unused.
+        let vm_a = &mut bills.bills[0];
+        vm_a.pricing_model = "Reservation".to_string();
+        vm_a.effective_price = 0.08;
+        vm_a.reservation_name = "ri-d4s".to_string();
+        let unused = crate::bills::bill_entry::BillEntry {
+            date: vm_a.date.clone(),
+            meter_category: vm_a.meter_category.clone(),
+            meter_sub_category: vm_a.meter_sub_category.clone(),
+            resource_group: vm_a.resource_group.clone(),
+            reservation_name: "ri-d4s".to_string(),
+            pricing_model: "Reservation".to_string(),
+            charge_type: "UnusedReservation".to_string(),
+            quantity: 186.0,
+            unit_price: 0.134,
+            effective_price: 0.08,
+            ..Default::default()
+        };
+        bills.bills.push(unused);
+        let summary = bills.cost_by_any_summary(&no_filter(), &CostType::ALL);
+
+        let day = summary
+            .reservations
+            .get(&(FLEX_KEY.to_string(), "2025-12-01".to_string()))
+            .expect("flex group 2025-12-01 missing");
+        assert_eq!(day.vm_names_reserved, vec!["vm-a"]);
+        assert_eq!(day.vm_names_not_reserved, vec!["vm-c"]);
+        assert!(day.reservation_names.contains("ri-d4s"));
+
+        let (_, month) = &summary.reservation_month_totals()[0];
+        assert!((month.cost_savings - (0.134 - 0.08) * 744.0).abs() < 1e-9);
+        assert!((month.cost_unused - 0.08 * 186.0).abs() < 1e-9);
+        assert_eq!(month.hr_total, 1488.0, "unused hours are not usage");
+        assert_eq!(month.hr_reserved, 744.0);
+        assert_eq!(month.hr_unused, 186.0);
+        assert_eq!(month.utilisation_pct(), Some(80.0));
+    }
+
+    /// A reservation row with a malformed date is skipped instead of aborting the report.
+    #[test]
+    fn test_reservation_summary_skips_bad_date() {
+        let path = PathBuf::from("tests/azure_test_nzd_usd_latest.csv");
+        let mut bills = crate::bills::Bills::default();
+        bills.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        bills.bills[0].date = "2025-10".to_string();
+        let summary = bills.cost_by_any_summary(&no_filter(), &CostType::ALL);
+
+        let (_, month) = &summary.reservation_month_totals()[0];
+        assert_eq!(month.hr_total, 744.0, "only the well-formed row is counted");
+    }
+
+    /// The same day of two combined months stays two entries, the total covers both.
+    #[test]
+    fn test_reservation_summary_combined_months() {
+        let path = PathBuf::from("tests/azure_test_nzd_usd_latest.csv");
+        let mut jan = crate::bills::Bills::default();
+        jan.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        let mut feb = crate::bills::Bills::default();
+        feb.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        for bill in &mut feb.bills {
+            bill.date = "2026-02-01".to_string();
+        }
+        let bills = crate::bills::Bills::combine(&[&jan, &feb], "2026-01..2026-02").unwrap();
+        let summary = bills.cost_by_any_summary(&no_filter(), &CostType::ALL);
+
+        assert_eq!(summary.reservations.len(), 2, "one entry per date");
+        for date in ["2026-01-01", "2026-02-01"] {
+            let day = summary
+                .reservations
+                .get(&(FLEX_KEY.to_string(), date.to_string()))
+                .unwrap_or_else(|| panic!("{date} missing"));
+            assert_eq!(day.hr_total, 1488.0, "{date}");
+        }
+        let (_, month) = &summary.reservation_month_totals()[0];
+        assert_eq!(month.hr_total, 2976.0);
+    }
+
+    /// Rows excluded by the filter do not reach the reservation summary.
+    #[test]
+    fn test_reservation_summary_respects_filter() {
+        let path = PathBuf::from("tests/azure_test_nzd_usd_latest.csv");
+        let mut bills = crate::bills::Bills::default();
+        bills.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        let filter = BillFilter::new(
+            None,
+            Some("rg-new-only".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &FILTER_OPTS,
+        )
+        .expect("valid test filter");
+        let summary = bills.cost_by_any_summary(&filter, &CostType::ALL);
+        let (_, month) = &summary.reservation_month_totals()[0];
+        assert_eq!(month.hr_total, 744.0);
+    }
+
+    /// Only the requested cost types are keyed; the totals still cover every matching row.
+    #[test]
+    fn test_summary_only_requested_cost_types() {
+        let path = PathBuf::from("tests/azure_test_nzd_usd_latest.csv");
+        let mut bills = crate::bills::Bills::default();
+        bills.parse_csv(&path, &FILTER_OPTS).expect("parse failed");
+        let all = bills.cost_by_any_summary(&no_filter(), &CostType::ALL);
+        let summary = bills.cost_by_any_summary(&no_filter(), &[CostType::ResourceGroup]);
+
+        assert!(!summary.per_type.is_empty());
+        assert!(
+            summary
+                .per_type
+                .keys()
+                .all(|(t, _)| *t == CostType::ResourceGroup)
+        );
+        assert_eq!(summary.filtered_cost_total, all.filtered_cost_total);
+        assert_eq!(summary.reservations.len(), all.reservations.len());
+    }
 }
@@ -1,6 +1,7 @@
 /// This module contains summary data structures derived from bills.
 /// see bill/calc/summary.rs for actual implementation.
 ///
+use crate::bills::bill_entry::BillEntry;
 use crate::bills::cost_type_enum::CostType;
 use crate::money::{Nzd, Usd};
 
@@ -10,11 +11,14 @@ pub struct CostTotal {
     pub source: CostSource,
     pub cost_unreserved: f64,
 }
+/// Reservation usage for one flex group on one day, costs in the pricing currency (USD).
 pub struct ReservationInfo<'a> {
     pub cost_full: f64,    // unreserved cost
     pub cost_savings: f64, // unreseved cost - actual cost
     pub hr_total: f64,
     pub hr_saving: f64,
+    pub hr_reserved: f64, // hours billed under a reservation (PricingModel "Reservation")
+    pub hr_unused: f64,   // hours of reservation capacity not used (ChargeType "UnusedReservation")
     pub cost_unused: f64, // cost of unused reservation
     // set of strings
     pub reservation_names: std::collections::HashSet<&'a str>,
@@ -22,6 +26,57 @@ pub struct ReservationInfo<'a> {
     pub vm_names_not_reserved: Vec<&'a str>,
     pub meter_category: String,
 }
+/// Month total of all days for one reservation flex group, see `SummaryData::reservation_month_totals`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReservationMonth {
+    pub cost_full: f64,
+    pub cost_savings: f64,
+    pub cost_unused: f64,
+    pub hr_total: f64,
+    pub hr_reserved: f64,
+    pub hr_unused: f64,
+}
+impl ReservationMonth {
+    /// Reserved hours used / reserved hours purchased; `None` when nothing was reserved.
+    pub fn utilisation_pct(&self) -> Option<f64> {
+        let purchased = self.hr_reserved + self.hr_unused;
+        if purchased > 0.0 {
+            Some(self.hr_reserved / purchased * 100.0)
+        } else {
+            None
+        }
+    }
+    /// Savings as a share of the full (unreserved + unused) cost; 0 when there is no cost.
+    pub fn saving_pct(&self) -> f64 {
+        let full = self.cost_full + self.cost_unused;
+        if full > 0.0 {
+            self.cost_savings / full * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Reservation flex group key, e.g. `"MC:Virtual Machines__MSubC:Dav4/Dasv4 Series"`,
+/// or `None` if the row is not reservation eligible (see `crate::RESERVATION_SUMMARY`).
+pub fn reservation_flex_group(bill: &BillEntry) -> Option<String> {
+    let eligible = bill.pricing_model == "Reservation"
+        || crate::RESERVATION_SUMMARY.iter().any(|(k, exclude)| {
+            // check unit_price > 0.0 to filter SQL Licence and storage at zero cost
+            (*k == bill.meter_category || *k == bill.meter_sub_category)
+                && bill.unit_price > 0.0
+                && !exclude
+                    .iter()
+                    .any(|rule| bill.meter_sub_category.contains(rule))
+        });
+    eligible.then(|| {
+        format!(
+            "MC:{}__MSubC:{}",
+            bill.meter_category, bill.meter_sub_category
+        )
+    })
+}
+
 // set copy trait for CostTotal
 #[derive(Debug, Clone, Copy)]
 pub enum CostSource {
@@ -39,7 +94,7 @@ pub struct SummaryData<'a> {
     pub details: std::collections::HashSet<String>,
     pub filtered_cost_total: Nzd,
     pub filtered_cost_total_usd: Usd,
-    pub reservations: std::collections::HashMap<(String, u8), ReservationInfo<'a>>, // flex type, day of month
+    pub reservations: std::collections::HashMap<(String, String), ReservationInfo<'a>>, // flex type, date YYYY-MM-DD
 }
 impl<'a> SummaryData<'a> {
     /// Accumulate a single bill row's cost into `per_type` under the given key.
@@ -67,6 +122,102 @@ impl<'a> SummaryData<'a> {
             });
     }
 
+    /// Accumulate a reservation-eligible bill row into `reservations` per (flex group, date).
+    /// Keyed on the full date so the days of a multi-month bill (`Bills::combine`) stay apart.
+    /// Rows that are not eligible, or whose date has no day of month, are ignored.
+    /// Costs are in the pricing currency (USD), matching `Summary::total_savings_used`.
+    pub fn accumulate_reservation(&mut self, bill: &'a BillEntry) {
+        let Some(flex_group) = reservation_flex_group(bill) else {
+            return;
+        };
+        // date is normalised to YYYY-MM-DD by BillEntry::deserialize_date
+        let Some(date) = bill
+            .date
+            .get(..10)
+            .filter(|d| d.get(8..10).is_some_and(|day| day.parse::<u8>().is_ok()))
+        else {
+            log::warn!(
+                "Invalid date '{}' expected fmt yyyy-mm-dd, skipping reservation row LineCSV:{}",
+                bill.date,
+                bill.line_number_csv,
+            );
+            return;
+        };
+        let unused = bill.charge_type == "UnusedReservation";
+        let reserved = bill.pricing_model == "Reservation";
+        let cost_unreserved = bill.unit_price * bill.quantity;
+        let savings = if unused {
+            0.0
+        } else {
+            (bill.unit_price - bill.effective_price) * bill.quantity
+        };
+        if savings < -0.0001 {
+            log::warn!(
+                "Over charge cost > unitprice*quantity:{} Name:{} RG:{} cost_unreserved:{}, ChargeType:{}, LineCSV:{}",
+                savings,
+                bill.resource_name,
+                bill.resource_group,
+                cost_unreserved,
+                bill.charge_type,
+                bill.line_number_csv,
+            );
+        }
+        let e = self
+            .reservations
+            .entry((flex_group, date.to_string()))
+            .or_insert_with(|| ReservationInfo {
+                cost_full: 0.0,
+                cost_savings: 0.0,
+                hr_total: 0.0,
+                hr_saving: 0.0,
+                hr_reserved: 0.0,
+                hr_unused: 0.0,
+                cost_unused: 0.0,
+                reservation_names: std::collections::HashSet::new(),
+                vm_names_reserved: Vec::new(),
+                vm_names_not_reserved: Vec::new(),
+                meter_category: bill.meter_category.clone(),
+            });
+        if !bill.reservation_name.is_empty() {
+            e.reservation_names.insert(&bill.reservation_name);
+        }
+        if unused {
+            e.cost_unused += bill.effective_price * bill.quantity;
+            e.hr_unused += bill.quantity;
+            return;
+        }
+        e.cost_full += cost_unreserved;
+        e.cost_savings += savings;
+        e.hr_total += bill.quantity;
+        e.hr_saving += if savings > 0.01 { bill.quantity } else { 0.0 };
+        let names = if reserved {
+            e.hr_reserved += bill.quantity;
+            &mut e.vm_names_reserved
+        } else {
+            &mut e.vm_names_not_reserved
+        };
+        if !names.contains(&bill.resource_name.as_str()) {
+            names.push(&bill.resource_name);
+        }
+    }
+
+    /// Sum the per-day `reservations` into one total per flex group, sorted by key;
+    /// every month of a multi-month bill is included.
+    pub fn reservation_month_totals(&self) -> Vec<(String, ReservationMonth)> {
+        let mut months: std::collections::BTreeMap<String, ReservationMonth> =
+            std::collections::BTreeMap::new();
+        for ((key, _date), r) in &self.reservations {
+            let m = months.entry(key.clone()).or_default();
+            m.cost_full += r.cost_full;
+            m.cost_savings += r.cost_savings;
+            m.cost_unused += r.cost_unused;
+            m.hr_total += r.hr_total;
+            m.hr_reserved += r.hr_reserved;
+            m.hr_unused += r.hr_unused;
+        }
+        months.into_iter().collect()
+    }
+
     /// Subtract `prev` from `self` in place, producing a diff view.
     ///
     /// Entries present in both → `Combined` with delta costs.
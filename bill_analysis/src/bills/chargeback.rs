@@ -0,0 +1,545 @@
+//! Chargeback statements — one Markdown and one CSV file per team / invoice section.
+//!
+//! `build_statements` groups the filtered rows of a month by a dimension value
+//! (invoice section, a tag key or subscription) and lists per value its
+//! resources and meter categories with the previous month alongside, the
+//! reservation savings it received and its total in the billing currency.
+//! `write_statements` renders each statement with `money::Nzd` formatting.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::bills::Bills;
+use crate::bills::bill_entry::BillEntry;
+use crate::bills::bill_filter::BillFilter;
+use crate::money::{Nzd, Usd};
+
+/// What a statement is issued for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChargebackDimension {
+    InvoiceSection,
+    /// Lowercase tag key; rows without the tag go to `untagged`.
+    Tag(String),
+    Subscription,
+}
+
+impl ChargebackDimension {
+    /// The statement a row belongs to.
+    pub fn value_of(&self, bill: &BillEntry) -> String {
+        let value = match self {
+            ChargebackDimension::InvoiceSection => bill.invoice_section.as_str(),
+            ChargebackDimension::Tag(key) => bill.tags.get_value(key).unwrap_or("untagged"),
+            ChargebackDimension::Subscription => bill.subscription_name.as_str(),
+        };
+        if value.is_empty() {
+            "none".to_string()
+        } else {
+            value.to_string()
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ChargebackDimension::InvoiceSection => "Invoice section".to_string(),
+            ChargebackDimension::Tag(key) => format!("Tag {key}"),
+            ChargebackDimension::Subscription => "Subscription".to_string(),
+        }
+    }
+}
+
+/// Latest and previous month cost of one statement line, billing currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MonthCost {
+    pub cost: f64,
+    pub prev_cost: f64,
+}
+
+impl MonthCost {
+    pub fn delta(&self) -> f64 {
+        self.cost - self.prev_cost
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceLine {
+    pub resource_name: String,
+    pub resource_group: String,
+    pub subscription: String,
+    #[serde(flatten)]
+    pub cost: MonthCost,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Statement {
+    pub dimension: String,
+    pub value: String,
+    pub month: String,
+    /// `None` when no previous month bill was available.
+    pub prev_month: Option<String>,
+    pub total: MonthCost,
+    /// Savings of reserved usage over on-demand price, in USD and converted at the bill rate.
+    pub reservation_savings_usd: f64,
+    pub reservation_savings: f64,
+    /// Sorted by cost, largest first; includes lines only present last month.
+    pub meter_categories: Vec<(String, MonthCost)>,
+    pub resources: Vec<ResourceLine>,
+}
+
+#[derive(Default)]
+struct Acc<'a> {
+    total: MonthCost,
+    savings_usd: f64,
+    meters: HashMap<&'a str, MonthCost>,
+    resources: HashMap<&'a str, ResourceLine>,
+}
+
+fn add<'a>(acc: &mut Acc<'a>, bill: &'a BillEntry, latest: bool) {
+    let cost = bill.cost.amount();
+    let slot = |m: &mut MonthCost| {
+        if latest {
+            m.cost += cost
+        } else {
+            m.prev_cost += cost
+        }
+    };
+    slot(&mut acc.total);
+    slot(acc.meters.entry(&bill.meter_category).or_default());
+    let id = if bill.resource_id.is_empty() {
+        &bill.resource_name
+    } else {
+        &bill.resource_id
+    };
+    slot(
+        &mut acc
+            .resources
+            .entry(id)
+            .or_insert_with(|| ResourceLine {
+                resource_name: bill.resource_name.clone(),
+                resource_group: bill.resource_group.clone(),
+                subscription: bill.subscription_name.clone(),
+                cost: MonthCost::default(),
+            })
+            .cost,
+    );
+    // same rule as Summary::total_savings_used in calc_all_totals
+    if latest && !bill.reservation_name.is_empty() && bill.charge_type == "Usage" {
+        acc.savings_usd += (bill.unit_price - bill.effective_price) * bill.quantity;
+    }
+}
+
+fn by_cost<T>(items: &mut [T], cost: impl Fn(&T) -> MonthCost, name: impl Fn(&T) -> &str) {
+    items.sort_by(|a, b| {
+        let (ca, cb) = (cost(a), cost(b));
+        cb.cost
+            .partial_cmp(&ca.cost)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| {
+                cb.prev_cost
+                    .partial_cmp(&ca.prev_cost)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .then_with(|| name(a).cmp(name(b)))
+    });
+}
+
+/// One statement per dimension value with spend in `latest`, sorted by total.
+/// `prev` (bill and its "YYYY-MM") adds the previous month columns; values only
+/// present in `prev` get no statement.
+pub fn build_statements(
+    latest: &Bills,
+    month: &str,
+    prev: Option<(&Bills, &str)>,
+    filter: &BillFilter,
+    dimension: &ChargebackDimension,
+) -> Vec<Statement> {
+    let mut accs: HashMap<String, Acc> = HashMap::new();
+    for bill in latest.bills.iter().filter(|b| filter.matches(b)) {
+        add(
+            accs.entry(dimension.value_of(bill)).or_default(),
+            bill,
+            true,
+        );
+    }
+    for bill in prev.iter().flat_map(|(p, _)| p.bills.iter()) {
+        if !filter.matches(bill) {
+            continue;
+        }
+        if let Some(acc) = accs.get_mut(&dimension.value_of(bill)) {
+            add(acc, bill, false);
+        }
+    }
+    let rate = latest.summary.exchange_rate;
+    let mut statements: Vec<Statement> = accs
+        .into_iter()
+        .map(|(value, acc)| {
+            let mut meter_categories: Vec<(String, MonthCost)> = acc
+                .meters
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect();
+            by_cost(&mut meter_categories, |m| m.1, |m| &m.0);
+            let mut resources: Vec<ResourceLine> = acc.resources.into_values().collect();
+            by_cost(&mut resources, |r| r.cost, |r| &r.resource_name);
+            Statement {
+                dimension: dimension.label(),
+                value,
+                month: month.to_string(),
+                prev_month: prev.map(|(_, m)| m.to_string()),
+                total: acc.total,
+                reservation_savings_usd: acc.savings_usd,
+                reservation_savings: if rate > 0.0 {
+                    Usd(acc.savings_usd).to_nzd(rate).amount()
+                } else {
+                    0.0
+                },
+                meter_categories,
+                resources,
+            }
+        })
+        .collect();
+    by_cost(&mut statements, |s| s.total, |s| &s.value);
+    statements
+}
+
+fn md_escape(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+fn pct_change(c: &MonthCost) -> String {
+    if c.prev_cost != 0.0 {
+        format!("{:+.1}%", c.delta() / c.prev_cost.abs() * 100.0)
+    } else if c.cost != 0.0 {
+        "new".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Render a statement as a Markdown document.
+pub fn statement_to_markdown(s: &Statement) -> String {
+    let prev = s.prev_month.as_deref();
+    let mut md = format!(
+        "# Chargeback statement — {}: {}\n\nMonth: **{}**{}\n\n",
+        s.dimension,
+        md_escape(&s.value),
+        s.month,
+        prev.map(|p| format!(" (compared with {p})"))
+            .unwrap_or_default(),
+    );
+    md.push_str(&format!("- Total: **{}**\n", Nzd(s.total.cost)));
+    if prev.is_some() {
+        md.push_str(&format!(
+            "- Previous month: {}\n- Change: {} {}\n",
+            Nzd(s.total.prev_cost),
+            Nzd(s.total.delta()),
+            pct_change(&s.total)
+        ));
+    }
+    md.push_str(&format!(
+        "- Reservation savings received: {} ({})\n",
+        Nzd(s.reservation_savings),
+        Usd(s.reservation_savings_usd)
+    ));
+
+    let (head, sep) = match prev {
+        Some(p) => (format!(" {} | {p} | Change |", s.month), "---:|---:|---:|"),
+        None => (format!(" {} |", s.month), "---:|"),
+    };
+    let cols = |c: &MonthCost| match prev {
+        Some(_) => format!(
+            " {} | {} | {} {} |",
+            Nzd(c.cost),
+            Nzd(c.prev_cost),
+            Nzd(c.delta()),
+            pct_change(c)
+        ),
+        None => format!(" {} |", Nzd(c.cost)),
+    };
+    md.push_str(&format!(
+        "\n## Meter categories\n\n| Meter category |{head}\n|---|{sep}\n"
+    ));
+    for (name, c) in &s.meter_categories {
+        md.push_str(&format!("| {} |{}\n", md_escape(name), cols(c)));
+    }
+    md.push_str(&format!(
+        "\n## Resources\n\n| Resource | Resource group | Subscription |{head}\n|---|---|---|{sep}\n"
+    ));
+    for r in &s.resources {
+        md.push_str(&format!(
+            "| {} | {} | {} |{}\n",
+            md_escape(&r.resource_name),
+            md_escape(&r.resource_group),
+            md_escape(&r.subscription),
+            cols(&r.cost)
+        ));
+    }
+    md
+}
+
+/// Render a statement as CSV; `section` is `total`, `reservation_savings`, `meter_category` or `resource`.
+pub fn statement_to_csv(s: &Statement) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "section",
+        "name",
+        "resource_group",
+        "subscription",
+        "cost",
+        "prev_cost",
+        "delta",
+    ])?;
+    let mut row = |section: &str, name: &str, rg: &str, sub: &str, c: MonthCost| {
+        wtr.write_record([
+            section,
+            name,
+            rg,
+            sub,
+            &format!("{:.2}", c.cost),
+            &format!("{:.2}", c.prev_cost),
+            &format!("{:.2}", c.delta()),
+        ])
+    };
+    row("total", &s.value, "", "", s.total)?;
+    row(
+        "reservation_savings",
+        &s.value,
+        "",
+        "",
+        MonthCost {
+            cost: s.reservation_savings,
+            prev_cost: 0.0,
+        },
+    )?;
+    for (name, c) in &s.meter_categories {
+        row("meter_category", name, "", "", *c)?;
+    }
+    for r in &s.resources {
+        row(
+            "resource",
+            &r.resource_name,
+            &r.resource_group,
+            &r.subscription,
+            r.cost,
+        )?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// File name stem for a statement: `{month}_{value}` with unsafe characters replaced.
+fn statement_file_stem(s: &Statement) -> String {
+    format!("{}_{}", s.month, s.value)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// File name stem per statement, in order. Values that sanitise to the same stem, compared
+/// case-insensitively for case-insensitive file systems, get `_2`, `_3`, ... appended so no
+/// statement overwrites another.
+pub fn statement_file_stems(statements: &[Statement]) -> Vec<String> {
+    let mut used: HashSet<String> = HashSet::new();
+    statements
+        .iter()
+        .map(|s| {
+            let base = statement_file_stem(s);
+            let mut stem = base.clone();
+            let mut n = 1;
+            while !used.insert(stem.to_lowercase()) {
+                n += 1;
+                stem = format!("{base}_{n}");
+            }
+            stem
+        })
+        .collect()
+}
+
+/// Write `{stem}.md` and `{stem}.csv` per statement into `out_dir` (created if missing),
+/// with the stems of `statement_file_stems`.
+pub fn write_statements(
+    statements: &[Statement],
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(out_dir)?;
+    let mut written = Vec::with_capacity(statements.len() * 2);
+    for (s, stem) in statements.iter().zip(statement_file_stems(statements)) {
+        let md = out_dir.join(format!("{stem}.md"));
+        std::fs::write(&md, statement_to_markdown(s))?;
+        let csv = out_dir.join(format!("{stem}.csv"));
+        std::fs::write(&csv, statement_to_csv(s)?)?;
+        written.push(md);
+        written.push(csv);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    #[test]
+    fn statements_per_section_with_previous_month() {
+        let mut reserved = row()
+            .id("vm1")
+            .rg("rg")
+            .sub("sub")
+            .section("team-a")
+            .meter("Compute")
+            .charge_type("Usage")
+            .quantity(10.0)
+            .cost(50.0)
+            .usd(15.0);
+        reserved.reservation_name = "ri-vm".to_string();
+        reserved.unit_price = 2.0;
+        reserved.effective_price = 1.5;
+        let latest = bills(vec![
+            reserved,
+            row()
+                .id("disk1")
+                .section("team-a")
+                .meter("Storage")
+                .cost(10.0),
+            row().id("vm9").section("team-b").meter("Compute").cost(5.0),
+            row().id("x").meter("Storage").cost(1.0),
+        ]);
+        let prev = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg")
+                .sub("sub")
+                .section("team-a")
+                .meter("Compute")
+                .cost(40.0),
+            row()
+                .id("old")
+                .rg("rg")
+                .sub("sub")
+                .section("team-a")
+                .meter("Compute")
+                .cost(7.0),
+            row()
+                .id("gone")
+                .section("team-c")
+                .meter("Compute")
+                .cost(99.0),
+        ]);
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let st = build_statements(
+            &latest,
+            "2026-01",
+            Some((&prev, "2025-12")),
+            &filter,
+            &ChargebackDimension::InvoiceSection,
+        );
+        let values: Vec<&str> = st.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(values, vec!["team-a", "team-b", "none"]);
+        let a = &st[0];
+        assert_eq!(
+            a.total,
+            MonthCost {
+                cost: 60.0,
+                prev_cost: 47.0
+            }
+        );
+        assert_eq!(a.reservation_savings_usd, 5.0);
+        assert_eq!(a.meter_categories[0].0, "Compute");
+        assert_eq!(a.meter_categories[0].1.prev_cost, 47.0);
+        let names: Vec<&str> = a
+            .resources
+            .iter()
+            .map(|r| r.resource_name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["vm1", "disk1", "old"],
+            "resource gone this month still listed"
+        );
+        assert_eq!(st[1].total.prev_cost, 0.0);
+
+        let md = statement_to_markdown(a);
+        assert!(md.starts_with("# Chargeback statement — Invoice section: team-a"));
+        assert!(md.contains("(compared with 2025-12)"));
+        assert!(
+            md.contains("| vm1 | rg | sub | NZ$ 50.00 | NZ$ 40.00 | NZ$ 10.00 +25.0% |"),
+            "{md}"
+        );
+        let csv = statement_to_csv(a).unwrap();
+        assert!(
+            csv.contains("\ntotal,team-a,,,60.00,47.00,13.00\n"),
+            "{csv}"
+        );
+        assert!(
+            csv.contains("\nresource,old,rg,sub,0.00,7.00,-7.00\n"),
+            "{csv}"
+        );
+    }
+
+    #[test]
+    fn tag_dimension_writes_files() {
+        let latest = bills(vec![
+            row()
+                .id("vm1")
+                .meter("Compute")
+                .tags(r#""team": "a/b""#)
+                .cost(3.0),
+            row().id("vm2").meter("Compute").cost(1.0),
+        ]);
+        let st = build_statements(
+            &latest,
+            "2026-01",
+            None,
+            &BillFilter::match_all(&FilterOpts::default()),
+            &ChargebackDimension::Tag("team".to_string()),
+        );
+        assert_eq!(st[1].value, "untagged");
+        assert!(!statement_to_markdown(&st[0]).contains("Previous month"));
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_statements(&st, dir.path()).unwrap();
+        assert_eq!(files.len(), 4);
+        assert!(dir.path().join("2026-01_a_b.md").exists());
+        assert!(dir.path().join("2026-01_untagged.csv").exists());
+    }
+
+    #[test]
+    fn colliding_stems_get_a_suffix() {
+        let latest = bills(vec![
+            row().id("vm1").section("Team A").meter("Compute").cost(3.0),
+        ]);
+        let base = build_statements(
+            &latest,
+            "2026-01",
+            None,
+            &BillFilter::match_all(&FilterOpts::default()),
+            &ChargebackDimension::InvoiceSection,
+        )
+        .remove(0);
+        let st: Vec<Statement> = ["Team A", "team_a", "Team/A", "team-a"]
+            .iter()
+            .map(|v| Statement {
+                value: v.to_string(),
+                ..base.clone()
+            })
+            .collect();
+        assert_eq!(
+            statement_file_stems(&st),
+            vec![
+                "2026-01_Team_A",
+                "2026-01_team_a_2",
+                "2026-01_Team_A_3",
+                "2026-01_team-a"
+            ]
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_statements(&st, dir.path()).unwrap();
+        assert_eq!(files.len(), 8);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 8);
+    }
+}
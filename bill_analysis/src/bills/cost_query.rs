@@ -1,7 +1,13 @@
 use serde::Serialize;
-use std::collections::HashMap;
 
 use crate::bills::Bills;
+use crate::bills::bill_filter::{BillFilter, Exclude, FilterDimension};
+use crate::bills::cost_type_enum::CostType;
+use crate::bills::date_range::DateRange;
+use crate::bills::filter_expr::FilterExpr;
+use crate::bills::query::{self as engine, GroupBy, Metric, Query, SortOrder};
+use crate::bills::tag_predicate::TagPredicate;
+use crate::cmd_parse::FilterOpts;
 
 // ---------------------------------------------------------------------------
 // resource_type extraction
@@ -38,9 +44,35 @@ pub struct CostQuery {
     pub rg_filter: String,
     pub name_filter: String,
     pub tag_filter: String,
+    /// Structured per-key tag predicates, all must match.
+    pub tag_predicates: Vec<TagPredicate>,
+    /// Boolean filter expression (MCP `where`), ANDed with the other filters.
+    pub where_expr: Option<FilterExpr>,
+    /// Exclude patterns, a row matching any of them is dropped.
+    pub excludes: Vec<Exclude>,
     /// When `Some`, only entries whose `date` field equals this ISO date string
     /// (`YYYY-MM-DD`) are included.
     pub date_filter: Option<String>,
+    /// Inclusive usage-date bounds; see [`query_cost_months`] for ranges spanning months.
+    pub date_range: DateRange,
+}
+
+impl CostQuery {
+    /// The [`BillFilter`] these filters describe (case-insensitive).
+    pub fn to_filter(&self) -> Result<BillFilter, String> {
+        let mut date_range = self.date_range.clone();
+        if let Some(date) = &self.date_filter {
+            date_range.from = date_range.from.max(Some(date.clone()));
+            date_range.to = Some(date_range.to.map_or(date.clone(), |t| t.min(date.clone())));
+        }
+        Ok(
+            build_filter(&self.rg_filter, &self.name_filter, &self.tag_filter, "", "")?
+                .with_tag_predicates(self.tag_predicates.clone())
+                .with_where(self.where_expr.clone())
+                .with_excludes(self.excludes.clone())
+                .with_date_range(date_range),
+        )
+    }
 }
 
 /// Aggregated cost result returned by [`query_cost`].
@@ -59,15 +91,28 @@ pub struct Contributor {
     pub row_count: usize,
 }
 
-/// Compile a non-empty pattern into a case-insensitive regex, or return `None`
-/// for an empty string (meaning "match all").
-pub(crate) fn compile_filter(pattern: &str) -> Result<Option<regex::Regex>, String> {
-    if pattern.is_empty() {
-        return Ok(None);
-    }
-    regex::Regex::new(&format!("(?i){pattern}"))
-        .map(Some)
-        .map_err(|e| format!("Invalid filter regex '{pattern}': {e}"))
+/// Case-insensitive [`BillFilter`] from MCP-style regex arguments; empty means "match all".
+fn build_filter(
+    rg: &str,
+    name: &str,
+    tag: &str,
+    meter_category: &str,
+    subscription: &str,
+) -> Result<BillFilter, String> {
+    let opt = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    BillFilter::new(
+        opt(name),
+        opt(rg),
+        opt(subscription),
+        opt(meter_category),
+        None,
+        None,
+        None,
+        opt(tag),
+        None,
+        &FilterOpts::default(),
+    )
+    .map_err(|e| format!("Invalid filter regex: {e}"))
 }
 
 /// Round to 2 decimal places for JSON output.
@@ -84,77 +129,50 @@ pub fn round2(v: f64) -> f64 {
 /// otherwise by `resource_group`. At most 10 contributors are returned, sorted
 /// by cost descending.
 pub fn query_cost(bills: &Bills, query: &CostQuery) -> Result<CostSummary, String> {
+    Ok(query_cost_months(&[bills], query.to_filter()?))
+}
+
+/// [`query_cost`] over several monthly bills (e.g. from `BillRepository::get_range`)
+/// with any `BillFilter`, such as the MCP server's shared filter arguments.
+pub fn query_cost_months(months: &[&Bills], filter: BillFilter) -> CostSummary {
     use std::time::Instant;
 
     let t = Instant::now();
-    let rg_re = compile_filter(&query.rg_filter)?;
-    let name_re = compile_filter(&query.name_filter)?;
-    let tag_re = compile_filter(&query.tag_filter)?;
-    let group_by_name = name_re.is_some();
-
-    let mut total_usd = 0.0f64;
-    let mut row_count = 0usize;
-    let mut by_key: HashMap<String, (f64, usize)> = HashMap::new();
-
-    for entry in &bills.bills {
-        if let Some(date) = &query.date_filter
-            && &entry.date != date
-        {
-            continue;
-        }
-        if let Some(re) = &rg_re
-            && !re.is_match(&entry.resource_group)
-        {
-            continue;
-        }
-        if let Some(re) = &name_re
-            && !re.is_match(&entry.resource_name)
-        {
-            continue;
-        }
-        if let Some(re) = &tag_re
-            && !re.is_match(&entry.tags.value)
-        {
-            continue;
-        }
-
-        let cost = entry.cost_usd.0;
-        total_usd += cost;
-        row_count += 1;
-
-        let key = if group_by_name {
-            entry.resource_name.clone()
-        } else {
-            entry.resource_group.clone()
-        };
-        let e = by_key.entry(key).or_insert((0.0, 0));
-        e.0 += cost;
-        e.1 += 1;
-    }
-
-    let mut entries: Vec<(String, f64, usize)> =
-        by_key.into_iter().map(|(k, (c, n))| (k, c, n)).collect();
-    entries.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    entries.truncate(10);
+    let group_by = if filter.name.is_empty() {
+        GroupBy::Dimension(CostType::ResourceGroup)
+    } else {
+        GroupBy::Dimension(CostType::ResourceName)
+    };
+    let result = engine::run(
+        months,
+        &Query {
+            filter,
+            group_by,
+            metric: Metric::CostUsd,
+            sort: SortOrder::Desc,
+            limit: Some(10),
+        },
+    );
 
     log::debug!(
         "[query_cost] {} rows in {:.1}ms",
-        row_count,
+        result.row_count,
         t.elapsed().as_secs_f64() * 1000.0
     );
 
-    Ok(CostSummary {
-        cost_usd: total_usd,
-        row_count,
-        top_contributors: entries
+    CostSummary {
+        cost_usd: result.total_cost_usd,
+        row_count: result.row_count,
+        top_contributors: result
+            .rows
             .into_iter()
-            .map(|(name, cost_usd, row_count)| Contributor {
-                name,
-                cost_usd: round2(cost_usd),
-                row_count,
+            .map(|r| Contributor {
+                name: r.key,
+                cost_usd: round2(r.cost_usd),
+                row_count: r.rows,
             })
             .collect(),
-    })
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -168,6 +186,14 @@ pub struct ResourceSearchQuery {
     pub rg_filter: String,
     pub name_filter: String,
     pub tag_filter: String,
+    /// Structured per-key tag predicates, all must match.
+    pub tag_predicates: Vec<TagPredicate>,
+    /// Boolean filter expression (MCP `where`), ANDed with the other filters.
+    pub where_expr: Option<FilterExpr>,
+    /// Exclude patterns, a row matching any of them is dropped.
+    pub excludes: Vec<Exclude>,
+    /// Inclusive usage-date bounds; see [`search_resources_months`] for ranges spanning months.
+    pub date_range: DateRange,
     pub meter_category_filter: String,
     pub subscription_filter: String,
     /// Matched against the resource type extracted from `resource_id`,
@@ -177,6 +203,25 @@ pub struct ResourceSearchQuery {
     pub limit: Option<usize>,
 }
 
+impl ResourceSearchQuery {
+    /// The [`BillFilter`] these filters describe (case-insensitive).
+    pub fn to_filter(&self) -> Result<BillFilter, String> {
+        build_filter(
+            &self.rg_filter,
+            &self.name_filter,
+            &self.tag_filter,
+            &self.meter_category_filter,
+            &self.subscription_filter,
+        )?
+        .with_tag_predicates(self.tag_predicates.clone())
+        .with_where(self.where_expr.clone())
+        .with_excludes(self.excludes.clone())
+        .with_date_range(self.date_range.clone())
+        .with_pattern(FilterDimension::ResourceType, &self.resource_type_filter)
+        .map_err(|e| format!("Invalid filter regex: {e}"))
+    }
+}
+
 /// One resource row in [`ResourceSearchResult`].
 #[derive(Serialize)]
 pub struct ResourceRow {
@@ -207,112 +252,58 @@ pub fn search_resources(
     bills: &Bills,
     query: &ResourceSearchQuery,
 ) -> Result<ResourceSearchResult, String> {
+    Ok(search_resources_months(
+        &[bills],
+        query.to_filter()?,
+        query.limit,
+    ))
+}
+
+/// [`search_resources`] over several monthly bills with any `BillFilter`;
+/// a resource's cost is summed across them. `limit` defaults to 50.
+pub fn search_resources_months(
+    months: &[&Bills],
+    filter: BillFilter,
+    limit: Option<usize>,
+) -> ResourceSearchResult {
     use std::time::Instant;
 
     let t = Instant::now();
-    let rg_re = compile_filter(&query.rg_filter)?;
-    let name_re = compile_filter(&query.name_filter)?;
-    let tag_re = compile_filter(&query.tag_filter)?;
-    let cat_re = compile_filter(&query.meter_category_filter)?;
-    let sub_re = compile_filter(&query.subscription_filter)?;
-    let type_re = compile_filter(&query.resource_type_filter)?;
-    let limit = query.limit.unwrap_or(50);
-
-    // Key: (resource_name, resource_group) → (subscription_name, meter_category, resource_type, cost, rows)
-    #[derive(Default)]
-    struct Acc {
-        subscription_name: String,
-        meter_category: String,
-        resource_type: String,
-        cost: f64,
-        rows: usize,
-    }
-    let mut by_resource: HashMap<(String, String), Acc> = HashMap::new();
-
-    for entry in &bills.bills {
-        if let Some(re) = &rg_re
-            && !re.is_match(&entry.resource_group)
-        {
-            continue;
-        }
-        if let Some(re) = &name_re
-            && !re.is_match(&entry.resource_name)
-        {
-            continue;
-        }
-        if let Some(re) = &tag_re
-            && !re.is_match(&entry.tags.value)
-        {
-            continue;
-        }
-        if let Some(re) = &cat_re
-            && !re.is_match(&entry.meter_category)
-        {
-            continue;
-        }
-        if let Some(re) = &sub_re
-            && !re.is_match(&entry.subscription_name)
-        {
-            continue;
-        }
-
-        let rtype = extract_resource_type(&entry.resource_id);
-        if let Some(re) = &type_re
-            && !re.is_match(&rtype)
-        {
-            continue;
-        }
-
-        let key = (entry.resource_name.clone(), entry.resource_group.clone());
-        let acc = by_resource.entry(key).or_default();
-        acc.cost += entry.cost_usd.0;
-        acc.rows += 1;
-        if acc.subscription_name.is_empty() {
-            acc.subscription_name = entry.subscription_name.clone();
-        }
-        if acc.meter_category.is_empty() {
-            acc.meter_category = entry.meter_category.clone();
-        }
-        if acc.resource_type.is_empty() {
-            acc.resource_type = rtype;
-        }
-    }
-
-    let total_resources = by_resource.len();
-    let total_cost_usd: f64 = by_resource.values().map(|a| a.cost).sum();
-
-    let mut rows: Vec<ResourceRow> = by_resource
-        .into_iter()
-        .map(|((resource_name, resource_group), acc)| ResourceRow {
-            resource_name,
-            resource_group,
-            subscription_name: acc.subscription_name,
-            meter_category: acc.meter_category,
-            resource_type: acc.resource_type,
-            total_cost_usd: round2(acc.cost),
-            charge_rows: acc.rows,
-        })
-        .collect();
-
-    rows.sort_by(|a, b| {
-        b.total_cost_usd
-            .partial_cmp(&a.total_cost_usd)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    rows.truncate(limit);
+    let result = engine::run(
+        months,
+        &Query {
+            filter,
+            group_by: GroupBy::Resource,
+            metric: Metric::CostUsd,
+            sort: SortOrder::Desc,
+            limit: Some(limit.unwrap_or(50)),
+        },
+    );
 
     log::debug!(
         "[search_resources] {} resources ({} unique) in {:.1}ms",
-        total_resources,
-        rows.len(),
+        result.total_groups,
+        result.rows.len(),
         t.elapsed().as_secs_f64() * 1000.0
     );
 
-    Ok(ResourceSearchResult {
-        total_resources,
-        total_cost_usd: round2(total_cost_usd),
-        resources: rows,
-    })
+    ResourceSearchResult {
+        total_resources: result.total_groups,
+        total_cost_usd: round2(result.total_cost_usd),
+        resources: result
+            .rows
+            .into_iter()
+            .map(|r| ResourceRow {
+                resource_name: r.key,
+                resource_group: r.resource_group,
+                subscription_name: r.subscription_name,
+                meter_category: r.meter_category,
+                resource_type: r.resource_type,
+                total_cost_usd: round2(r.cost_usd),
+                charge_rows: r.rows,
+            })
+            .collect(),
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -336,9 +327,10 @@ mod tests {
     }
 
     fn make_bills(entries: Vec<BillEntry>) -> Bills {
-        let mut b = Bills::default();
-        b.bills = entries;
-        b
+        Bills {
+            bills: entries,
+            ..Bills::default()
+        }
     }
 
     #[test]
@@ -619,4 +611,149 @@ mod tests {
         assert_eq!(r.total_resources, 10);
         assert_eq!(r.resources.len(), 3);
     }
+
+    fn with_tags(mut entry: BillEntry, pairs: &[(&str, &str)]) -> BillEntry {
+        for (k, v) in pairs {
+            entry
+                .tags
+                .kv
+                .insert(k.to_lowercase(), (v.to_string(), k.to_string()));
+        }
+        entry
+    }
+
+    #[test]
+    fn tag_predicates_filter_query_cost_and_search() {
+        let bills = make_bills(vec![
+            with_tags(
+                make_entry("rg-a", "vm-1", 10.0, "2026-04-01"),
+                &[("Env", "prod"), ("Owner", "alice")],
+            ),
+            with_tags(
+                make_entry("rg-a", "vm-2", 20.0, "2026-04-01"),
+                &[("Env", "prod")],
+            ),
+            with_tags(
+                make_entry("rg-b", "vm-3", 5.0, "2026-04-01"),
+                &[("Env", "dev")],
+            ),
+        ]);
+        let preds = TagPredicate::parse_all(&["env=PROD", "!owner"], false).unwrap();
+        let r = query_cost(
+            &bills,
+            &CostQuery {
+                tag_predicates: preds.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.row_count, 1);
+        assert!((r.cost_usd - 20.0).abs() < 0.001);
+
+        let r = search_resources(
+            &bills,
+            &ResourceSearchQuery {
+                tag_predicates: preds,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.total_resources, 1);
+        assert_eq!(r.resources[0].resource_name, "vm-2");
+    }
+
+    #[test]
+    fn date_range_spans_months() {
+        let march = make_bills(vec![
+            make_entry("rg-a", "vm-1", 10.0, "2026-03-09"),
+            make_entry("rg-a", "vm-1", 20.0, "2026-03-10"),
+        ]);
+        let april = make_bills(vec![
+            make_entry("rg-a", "vm-1", 5.0, "2026-04-20"),
+            make_entry("rg-b", "vm-2", 7.0, "2026-04-21"),
+        ]);
+        let range = DateRange::parse(Some("2026-03-10"), Some("2026-04-20")).unwrap();
+        let filter = CostQuery {
+            date_range: range,
+            ..Default::default()
+        }
+        .to_filter()
+        .unwrap();
+        let r = query_cost_months(&[&march, &april], filter.clone());
+        assert_eq!(r.row_count, 2);
+        assert!((r.cost_usd - 25.0).abs() < 0.001);
+
+        let r = search_resources_months(&[&march, &april], filter, None);
+        assert_eq!(r.total_resources, 1);
+        assert_eq!(r.resources[0].charge_rows, 2);
+    }
+
+    #[test]
+    fn excludes_filter_query_cost_and_search() {
+        use crate::bills::bill_filter::FilterDimension;
+        let bills = make_bills(vec![
+            make_entry("rg-app", "vm-1", 10.0, "2026-04-01"),
+            make_entry("MC_aks_nodes", "vmss-1", 20.0, "2026-04-01"),
+            make_entry("rg-app", "vm-sandbox", 5.0, "2026-04-01"),
+        ]);
+        let excludes = || {
+            vec![
+                Exclude::new(FilterDimension::ResourceGroup, "^mc_", false).unwrap(),
+                Exclude::new(FilterDimension::Name, "sandbox", false).unwrap(),
+            ]
+        };
+        let r = query_cost(
+            &bills,
+            &CostQuery {
+                excludes: excludes(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.row_count, 1);
+        assert!((r.cost_usd - 10.0).abs() < 0.001);
+
+        let r = search_resources(
+            &bills,
+            &ResourceSearchQuery {
+                excludes: excludes(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.total_resources, 1);
+        assert_eq!(r.resources[0].resource_name, "vm-1");
+    }
+
+    #[test]
+    fn where_expr_filters_query_cost_and_search() {
+        let bills = make_bills(vec![
+            make_entry("rg-a", "vm-1", 10.0, "2026-04-01"),
+            make_entry("rg-b", "vm-2", 20.0, "2026-04-01"),
+            make_entry("rg-c", "vm-3", 5.0, "2026-04-01"),
+        ]);
+        let expr = || Some(FilterExpr::parse("rg = rg-a or name ~ \"3$\"", false).unwrap());
+        let r = query_cost(
+            &bills,
+            &CostQuery {
+                where_expr: expr(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.row_count, 2);
+        assert!((r.cost_usd - 15.0).abs() < 0.001);
+
+        let r = search_resources(
+            &bills,
+            &ResourceSearchQuery {
+                rg_filter: "rg-[ab]".into(),
+                where_expr: expr(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(r.total_resources, 1);
+        assert_eq!(r.resources[0].resource_name, "vm-1");
+    }
 }
@@ -1,3 +1,15 @@
+use crate::bills::bill_entry::BillEntry;
+use crate::bills::cost_query::extract_resource_type;
+
+/// `none` for an empty key, as for regions.
+fn non_empty(key: String) -> String {
+    if key.is_empty() {
+        "none".to_string()
+    } else {
+        key
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum CostType {
     ResourceName,
@@ -9,9 +21,82 @@ pub enum CostType {
     Reservation,
     Region, //Location
     InvoiceSection,
+    /// ARM type from `resource_id`, e.g. `microsoft.compute/disks`.
+    ResourceType,
+    MeterName,
+    ChargeType,
+    PricingModel,
+    Publisher,
 }
 impl CostType {
-    pub fn as_str(&self) -> &str {
+    pub const ALL: [CostType; 14] = [
+        CostType::ResourceName,
+        CostType::ResourceGroup,
+        CostType::Subscription,
+        CostType::MeterCategory,
+        CostType::MeterSubCategory,
+        CostType::Tag,
+        CostType::Reservation,
+        CostType::Region,
+        CostType::InvoiceSection,
+        CostType::ResourceType,
+        CostType::MeterName,
+        CostType::ChargeType,
+        CostType::PricingModel,
+        CostType::Publisher,
+    ];
+
+    /// Parse a dimension name as used by `query --group-by` / MCP `group_by`,
+    /// e.g. `rg`, `resource_group`, `ResourceGroup`.
+    pub fn from_name(name: &str) -> Option<CostType> {
+        let lower = name.trim().to_lowercase().replace(['-', '_'], "");
+        Self::ALL.into_iter().find(|t| {
+            t.as_str().to_lowercase() == lower
+                || t.as_short().to_lowercase() == lower
+                || match t {
+                    CostType::ResourceName => lower == "name" || lower == "resource",
+                    CostType::Subscription => lower == "subscriptionname",
+                    CostType::MeterSubCategory => lower == "metersub",
+                    CostType::Region => lower == "location",
+                    CostType::InvoiceSection => lower == "invoice",
+                    CostType::ResourceType => lower == "armtype",
+                    CostType::Publisher => lower == "publishername",
+                    _ => false,
+                }
+        })
+    }
+
+    /// The row's key in this dimension, as shown in the report; `tag_key`
+    /// (lowercase unless case-sensitive) is only used for `Tag`.
+    pub fn key_of(&self, bill: &BillEntry, tag_key: &str) -> String {
+        match self {
+            CostType::ResourceName => bill.resource_name.clone(),
+            CostType::ResourceGroup => bill.resource_group.clone(),
+            CostType::Subscription => bill.subscription_name.clone(),
+            CostType::MeterCategory => bill.meter_category.clone(),
+            CostType::MeterSubCategory => {
+                format!("{}__{}", bill.meter_category, bill.meter_sub_category)
+            }
+            CostType::Tag => bill.tags.summary_key(tag_key),
+            CostType::Reservation => bill.benefit_name.clone(),
+            CostType::Region if bill.resource_location.is_empty() => "none".to_string(),
+            CostType::Region => bill.resource_location.clone(),
+            CostType::InvoiceSection if !bill.invoice_section.is_empty() => {
+                bill.invoice_section.clone()
+            }
+            CostType::InvoiceSection if !bill.meter_sub_category.is_empty() => {
+                format!("({})", bill.meter_sub_category)
+            }
+            CostType::InvoiceSection => "none".to_string(),
+            CostType::ResourceType => non_empty(extract_resource_type(&bill.resource_id)),
+            CostType::MeterName => bill.meter_name.clone(),
+            CostType::ChargeType => bill.charge_type.clone(),
+            CostType::PricingModel => non_empty(bill.pricing_model.clone()),
+            CostType::Publisher => non_empty(bill.publisher_name.clone()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
         match self {
             CostType::ResourceName => "ResourceName",
             CostType::ResourceGroup => "ResourceGroup",
@@ -22,6 +107,11 @@ impl CostType {
             CostType::Reservation => "Reservation",
             CostType::Region => "Region",
             CostType::InvoiceSection => "InvoiceSection",
+            CostType::ResourceType => "ResourceType",
+            CostType::MeterName => "MeterName",
+            CostType::ChargeType => "ChargeType",
+            CostType::PricingModel => "PricingModel",
+            CostType::Publisher => "Publisher",
         }
     }
     // short name 3 char
@@ -36,6 +126,11 @@ impl CostType {
             CostType::Reservation => "Resrv",
             CostType::Region => "Loc",
             CostType::InvoiceSection => "Sec",
+            CostType::ResourceType => "Type",
+            CostType::MeterName => "MName",
+            CostType::ChargeType => "Charge",
+            CostType::PricingModel => "Price",
+            CostType::Publisher => "Pub",
         }
     }
 }
@@ -0,0 +1,185 @@
+//! Reservation coverage — how much reservation-eligible usage ran on-demand.
+//!
+//! Utilisation (`--reservation`) answers "are the reservations we bought used?";
+//! coverage answers "how much eligible usage is not under a reservation or
+//! savings plan?". Rows are built by `Bills::reservation_coverage`.
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::f64_to_currency;
+
+/// Coverage of one reservation flex group in one region.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoverageRow {
+    /// `"MC:{meter_category}__MSubC:{meter_sub_category}"`, see `bills_sum_data::reservation_flex_group`.
+    pub flex_group: String,
+    pub region: String,
+    /// Hours billed under a reservation or savings plan.
+    pub covered_hours: f64,
+    /// Hours billed on-demand.
+    pub uncovered_hours: f64,
+    /// Cost of the on-demand hours in the billing currency (NZD) — what could have been reserved.
+    pub on_demand_cost: f64,
+    pub on_demand_cost_usd: f64,
+}
+
+impl CoverageRow {
+    /// Covered hours / all eligible hours; `None` when there were no hours.
+    pub fn coverage_pct(&self) -> Option<f64> {
+        let total = self.covered_hours + self.uncovered_hours;
+        if total > 0.0 {
+            Some(self.covered_hours / total * 100.0)
+        } else {
+            None
+        }
+    }
+}
+
+/// Render rows as CSV with a header line, including a `coverage_pct` column.
+pub fn coverage_to_csv(rows: &[CoverageRow]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "flex_group",
+        "region",
+        "covered_hours",
+        "uncovered_hours",
+        "coverage_pct",
+        "on_demand_cost",
+        "on_demand_cost_usd",
+    ])?;
+    for row in rows {
+        wtr.write_record([
+            row.flex_group.clone(),
+            row.region.clone(),
+            format!("{:.2}", row.covered_hours),
+            format!("{:.2}", row.uncovered_hours),
+            row.coverage_pct()
+                .map(|p| format!("{p:.1}"))
+                .unwrap_or_default(),
+            format!("{:.2}", row.on_demand_cost),
+            format!("{:.2}", row.on_demand_cost_usd),
+        ])?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// Print rows as a coloured terminal table with a total line.
+pub fn print_coverage(rows: &[CoverageRow], cur: &str) {
+    println!(
+        "{:>10} {:>10} {:>9} {:>14}  {:<16} flex group",
+        "covered_h", "on_dem_h", "coverage", "on_demand", "region"
+    );
+    let (mut covered, mut uncovered, mut cost) = (0.0, 0.0, 0.0);
+    for row in rows {
+        covered += row.covered_hours;
+        uncovered += row.uncovered_hours;
+        cost += row.on_demand_cost;
+        let pct = row
+            .coverage_pct()
+            .map(|p| format!("{p:.0}%"))
+            .unwrap_or_else(|| "n/a".to_string());
+        println!(
+            "{:>10.0} {:>10.0} {:>9} {:>14}  {:<16} '{}'",
+            row.covered_hours,
+            row.uncovered_hours,
+            if row.uncovered_hours > 0.0 {
+                pct.yellow()
+            } else {
+                pct.green()
+            },
+            format!("{cur} {}", f64_to_currency(row.on_demand_cost, 2)).red(),
+            row.region,
+            row.flex_group,
+        );
+    }
+    let total_pct = if covered + uncovered > 0.0 {
+        covered / (covered + uncovered) * 100.0
+    } else {
+        0.0
+    };
+    println!(
+        "Total covered {covered:.0}h, on-demand {uncovered:.0}h, coverage {pct}, on-demand cost {cur} {cost}",
+        pct = format!("{total_pct:.0}%").bold(),
+        cost = f64_to_currency(cost, 2).red().bold(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::{BillFilter, Bills};
+    use crate::cmd_parse::FilterOpts;
+    use std::path::PathBuf;
+
+    fn load(path: &str) -> Bills {
+        let mut bills = Bills::default();
+        bills
+            .parse_csv(&PathBuf::from(path), &FilterOpts::default())
+            .expect("parse failed");
+        bills
+    }
+
+    fn no_filter() -> BillFilter {
+        BillFilter::match_all(&FilterOpts::default())
+    }
+
+    #[test]
+    fn coverage_counts_on_demand_hours_and_cost() {
+        let bills = load("tests/azure_test_nzd_usd_latest.csv");
+        let rows = bills.reservation_coverage(&no_filter());
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row.flex_group, "MC:Compute__MSubC:Virtual Machines");
+        assert_eq!(row.region, "australiaeast");
+        assert_eq!(row.covered_hours, 0.0);
+        assert_eq!(row.uncovered_hours, 1488.0);
+        assert_eq!(row.on_demand_cost, 150.0);
+        assert_eq!(row.on_demand_cost_usd, 90.0);
+        assert_eq!(row.coverage_pct(), Some(0.0));
+    }
+
+    #[test]
+    fn coverage_splits_reserved_spot_and_unused() {
+        let mut bills = load("tests/azure_test_nzd_usd_latest.csv");
+        bills.bills[0].pricing_model = "Reservation".to_string();
+        bills.bills[1].pricing_model = "Spot".to_string();
+        bills.push(crate::bills::bill_entry::BillEntry {
+            meter_category: "Compute".to_string(),
+            meter_sub_category: "Virtual Machines".to_string(),
+            resource_location: "australiaeast".to_string(),
+            pricing_model: "Reservation".to_string(),
+            charge_type: "UnusedReservation".to_string(),
+            unit_price: 0.134,
+            quantity: 100.0,
+            ..Default::default()
+        });
+        let rows = bills.reservation_coverage(&no_filter());
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].covered_hours, 744.0);
+        assert_eq!(rows[0].uncovered_hours, 0.0, "spot is not reservable");
+        assert_eq!(rows[0].coverage_pct(), Some(100.0));
+    }
+
+    #[test]
+    fn coverage_csv_has_header_and_pct() {
+        let rows = vec![CoverageRow {
+            flex_group: "MC:Virtual Machines__MSubC:Dv5".to_string(),
+            region: "australiaeast".to_string(),
+            covered_hours: 300.0,
+            uncovered_hours: 100.0,
+            on_demand_cost: 12.5,
+            on_demand_cost_usd: 7.5,
+        }];
+        let csv = coverage_to_csv(&rows).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "flex_group,region,covered_hours,uncovered_hours,coverage_pct,on_demand_cost,on_demand_cost_usd"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "MC:Virtual Machines__MSubC:Dv5,australiaeast,300.00,100.00,75.0,12.50,7.50"
+        );
+    }
+}
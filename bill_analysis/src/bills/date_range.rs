@@ -0,0 +1,208 @@
+//! Inclusive usage-date range (CLI `--from` / `--to`, MCP `from` / `to`).
+//!
+//! Bill rows carry an ISO `date` (`BillEntry.date`, normalised from the CSV's
+//! `MM/DD/YYYY`), so a range is two ISO strings compared lexicographically.
+//! Bound specs:
+//!
+//! | spec          | `from`                 | `to`                  |
+//! |---------------|------------------------|-----------------------|
+//! | `2025-10-10`  | that day               | that day              |
+//! | `2025-10`     | first day of the month | last day of the month |
+//! | `today`       | today                  | today                 |
+//! | `yesterday`   | yesterday              | yesterday             |
+//! | `90d`         | 90 days before today   | 90 days before today  |
+//!
+//! A range spanning several months is answered by loading each month through
+//! `BillRepository::get_range`; a range with only a `to` starts at the earliest
+//! month available.
+
+use chrono::{Datelike, Days, Local, NaiveDate};
+use std::fmt;
+
+/// Upper bound on months loaded for one range query.
+pub const MAX_RANGE_MONTHS: usize = 36;
+
+/// Inclusive date range; an unset bound is open.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateRange {
+    /// ISO `YYYY-MM-DD`.
+    pub from: Option<String>,
+    /// ISO `YYYY-MM-DD`.
+    pub to: Option<String>,
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<NaiveDate> {
+    let (y, m) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(y, m, 1)?.pred_opt()
+}
+
+/// Parse one bound spec (see the module docs) relative to `today`.
+fn parse_bound(spec: &str, is_end: bool, today: NaiveDate) -> Result<NaiveDate, String> {
+    let spec = spec.trim();
+    let invalid =
+        || format!("Invalid date '{spec}', expected YYYY-MM-DD, YYYY-MM, today, yesterday or <N>d");
+    match spec.to_lowercase().as_str() {
+        "today" => return Ok(today),
+        "yesterday" => return today.pred_opt().ok_or_else(invalid),
+        _ => {}
+    }
+    if let Some(days) = spec.strip_suffix(['d', 'D']) {
+        let days: u64 = days
+            .trim_start_matches('-')
+            .parse()
+            .map_err(|_| invalid())?;
+        return today.checked_sub_days(Days::new(days)).ok_or_else(invalid);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+        return Ok(date);
+    }
+    let first =
+        NaiveDate::parse_from_str(&format!("{spec}-01"), "%Y-%m-%d").map_err(|_| invalid())?;
+    if is_end {
+        last_day_of_month(first.year(), first.month()).ok_or_else(invalid)
+    } else {
+        Ok(first)
+    }
+}
+
+impl DateRange {
+    /// Parse `--from` / `--to` specs relative to the local date.
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Result<Self, String> {
+        Self::parse_relative(from, to, Local::now().date_naive())
+    }
+
+    /// As [`DateRange::parse`] with an explicit `today`.
+    pub fn parse_relative(
+        from: Option<&str>,
+        to: Option<&str>,
+        today: NaiveDate,
+    ) -> Result<Self, String> {
+        let from = from
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| parse_bound(s, false, today))
+            .transpose()?;
+        let to = to
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| parse_bound(s, true, today))
+            .transpose()?;
+        if let (Some(f), Some(t)) = (from, to)
+            && f > t
+        {
+            return Err(format!("Date range start {f} is after its end {t}"));
+        }
+        let iso = |d: NaiveDate| d.format("%Y-%m-%d").to_string();
+        Ok(DateRange {
+            from: from.map(iso),
+            to: to.map(iso),
+        })
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    /// `true` when `date` (ISO) lies in the range; rows without a date only match an unset range.
+    pub fn contains(&self, date: &str) -> bool {
+        if !self.is_set() {
+            return true;
+        }
+        !date.is_empty()
+            && self.from.as_deref().is_none_or(|f| date >= f)
+            && self.to.as_deref().is_none_or(|t| date <= t)
+    }
+
+    /// `(year, month)` of every bill month the range touches, oldest first.
+    /// An open end defaults to today's month; an open start is an error here,
+    /// `BillRepository::get_range` starts it at the earliest available month.
+    pub fn months(&self) -> Result<Vec<(u32, u32)>, String> {
+        let ym = |iso: &str| -> (u32, u32) {
+            (
+                iso[..4].parse().unwrap_or_default(),
+                iso[5..7].parse().unwrap_or_default(),
+            )
+        };
+        let from = self
+            .from
+            .as_deref()
+            .ok_or_else(|| "Date range needs a start ('from') to select months".to_string())?;
+        let today = Local::now().date_naive();
+        let (mut y, mut m) = ym(from);
+        let (end_y, end_m) = self
+            .to
+            .as_deref()
+            .map(ym)
+            .unwrap_or((today.year() as u32, today.month()));
+        let mut months = Vec::new();
+        while (y, m) <= (end_y, end_m) {
+            months.push((y, m));
+            if months.len() > MAX_RANGE_MONTHS {
+                return Err(format!(
+                    "Date range {self} spans more than {MAX_RANGE_MONTHS} months"
+                ));
+            }
+            (y, m) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+        }
+        Ok(months)
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}..{}",
+            self.from.as_deref().unwrap_or(""),
+            self.to.as_deref().unwrap_or("")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 15).unwrap()
+    }
+
+    #[test]
+    fn bound_specs() {
+        let r = DateRange::parse_relative(Some("2025-10-10"), Some("2025-10-20"), today()).unwrap();
+        assert_eq!(r.to_string(), "2025-10-10..2025-10-20");
+        let r = DateRange::parse_relative(Some("2024-02"), Some("2024-02"), today()).unwrap();
+        assert_eq!(r.to_string(), "2024-02-01..2024-02-29");
+        let r = DateRange::parse_relative(Some("90d"), Some("yesterday"), today()).unwrap();
+        assert_eq!(r.to_string(), "2025-12-15..2026-03-14");
+        let r = DateRange::parse_relative(None, Some("today"), today()).unwrap();
+        assert_eq!(r.to_string(), "..2026-03-15");
+        assert!(DateRange::parse_relative(Some("10/01/2025"), None, today()).is_err());
+        let err = DateRange::parse_relative(Some("2025-11"), Some("2025-10"), today()).unwrap_err();
+        assert!(err.contains("after its end"), "{err}");
+    }
+
+    #[test]
+    fn contains_is_inclusive_and_open_ended() {
+        let r = DateRange::parse_relative(Some("2025-10-10"), Some("2025-10-20"), today()).unwrap();
+        assert!(r.contains("2025-10-10") && r.contains("2025-10-20"));
+        assert!(!r.contains("2025-10-09") && !r.contains("2025-10-21") && !r.contains(""));
+        let open = DateRange::parse_relative(Some("2025-10-10"), None, today()).unwrap();
+        assert!(open.contains("2030-01-01"));
+        assert!(DateRange::default().contains(""));
+    }
+
+    #[test]
+    fn months_spanned() {
+        let r = DateRange::parse_relative(Some("2025-11-20"), Some("2026-02-03"), today()).unwrap();
+        assert_eq!(
+            r.months().unwrap(),
+            vec![(2025, 11), (2025, 12), (2026, 1), (2026, 2)]
+        );
+        assert!(DateRange::default().months().is_err());
+        let long = DateRange::parse_relative(Some("2020-01"), Some("2026-01"), today()).unwrap();
+        assert!(long.months().unwrap_err().contains("36 months"));
+    }
+}
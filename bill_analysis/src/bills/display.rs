@@ -1,12 +1,13 @@
-use std::collections::HashSet;
-
 use colored::Colorize;
+use std::cmp::Ordering;
 
 use crate::bills::Bills;
+use crate::bills::bill_diff::{BillDiff, ChangeKind};
 use crate::bills::bill_filter::BillFilter;
 // use super::bills_sum_data;
 use crate::bills::bills_sum_data::{CostSource, SummaryData};
 use crate::bills::cost_type_enum::CostType;
+use crate::bills::variance::{VarianceBreakdown, VarianceComponents};
 use crate::cmd_parse::DisplayOpts;
 use crate::f64_to_currency;
 
@@ -28,6 +29,12 @@ pub struct PreparedRow {
     pub colour: RowColour,
     /// true when cost exceeds the display threshold
     pub visible: bool,
+    /// Share of the section total, in percent.
+    pub pct: f64,
+    /// Running share from the largest row down to this one (Pareto), in percent.
+    pub cum_pct: f64,
+    /// Rows folded into this "other (N items)" row by `DisplayOpts::top`; 0 for a normal row.
+    pub other_count: usize,
 }
 
 #[derive(Debug)]
@@ -65,34 +72,54 @@ pub fn build_display_plan(
 
 // ── Pure helper functions (no I/O) ────────────────────────────────────────────
 
+fn row_colour(cost: f64, source: CostSource) -> RowColour {
+    match source {
+        CostSource::Original => {
+            if cost < 0.0 {
+                RowColour::Cyan
+            } else {
+                RowColour::Red
+            }
+        }
+        CostSource::Secondary => RowColour::Green,
+        CostSource::Combined => {
+            if cost < 0.0 {
+                RowColour::Green
+            } else {
+                RowColour::Blue
+            }
+        }
+    }
+}
+
 /// Assigns a display colour and visibility flag to each row; returns totals.
+///
+/// With `display_opts.top`, only the N rows with the largest absolute cost are
+/// kept and the rest are folded into one leading "other (N items)" row. `pct`
+/// and `cum_pct` are shares of the section total, cumulated from the largest
+/// row down, with the "other" row last (100%).
 pub(crate) fn prepare_rows(
     bill_summary: &SummaryData,
     cost_type: CostType,
     display_opts: &DisplayOpts,
 ) -> PreparedSummary {
-    let (total, total_usd, _cnt, sorted) = sort_calc_total(bill_summary, &cost_type);
+    let (total, total_usd, _cnt, mut sorted) = sort_calc_total(bill_summary, &cost_type);
+    let mut other = None;
+    if let Some(top) = display_opts.top.filter(|&n| sorted.len() > n) {
+        sorted.sort_by(|a, b| b.0.abs().partial_cmp(&a.0.abs()).unwrap_or(Ordering::Equal));
+        let tail = sorted.split_off(top);
+        let source = if tail.iter().all(|r| matches!(r.2, CostSource::Original)) {
+            CostSource::Original
+        } else {
+            CostSource::Combined
+        };
+        other = Some((tail.iter().map(|r| r.0).sum::<f64>(), tail.len(), source));
+        sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    }
     let mut skipped_count = 0usize;
-    let rows: Vec<PreparedRow> = sorted
+    let mut rows: Vec<PreparedRow> = sorted
         .into_iter()
         .map(|(cost, name, source)| {
-            let colour = match source {
-                CostSource::Original => {
-                    if cost < 0.0 {
-                        RowColour::Cyan
-                    } else {
-                        RowColour::Red
-                    }
-                }
-                CostSource::Secondary => RowColour::Green,
-                CostSource::Combined => {
-                    if cost < 0.0 {
-                        RowColour::Green
-                    } else {
-                        RowColour::Blue
-                    }
-                }
-            };
             let visible =
                 cost > display_opts.cost_min_display || cost < -display_opts.cost_min_display;
             if !visible {
@@ -102,11 +129,42 @@ pub(crate) fn prepare_rows(
                 cost,
                 name: name.to_string(),
                 source,
-                colour,
+                colour: row_colour(cost, source),
                 visible,
+                pct: 0.0,
+                cum_pct: 0.0,
+                other_count: 0,
             }
         })
         .collect();
+    if let Some((cost, count, source)) = other {
+        rows.insert(
+            0,
+            PreparedRow {
+                cost,
+                name: format!("other ({count} item{})", if count == 1 { "" } else { "s" }),
+                source,
+                colour: row_colour(cost, source),
+                visible: true,
+                pct: 0.0,
+                cum_pct: 0.0,
+                other_count: count,
+            },
+        );
+    }
+    let share = |cost: f64| {
+        if total != 0.0 {
+            cost / total * 100.0
+        } else {
+            0.0
+        }
+    };
+    let mut running = 0.0;
+    for row in rows.iter_mut().rev() {
+        running += row.cost;
+        row.pct = share(row.cost);
+        row.cum_pct = share(running);
+    }
     PreparedSummary {
         rows,
         total,
@@ -144,24 +202,62 @@ pub fn display_cost_by_filter(
 ) {
     println!();
     println!(
-        "Filter Azure name:{}, rg:{}, sub:{}, cat:{}, tag_filter:{}, tag_summarise:{}, location:{}, reservation:{}, invoice_section:{}.\n",
+        "Filter Azure name:{}, rg:{}, sub:{}, cat:{}, tag_filter:{}, tags:[{}], tag_summarise:{}, location:{}, reservation:{}, invoice_section:{}.\n",
         filter.name,
         filter.resource_group,
         filter.subscription,
         filter.meter_category,
         filter.tag_filter,
+        filter
+            .tag_predicates
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
         filter.tag_summarise,
         filter.location,
         filter.reservation,
         filter.invoice_section,
     );
+    let more_filters: Vec<String> = [
+        ("resource_type", &filter.resource_type),
+        ("meter_name", &filter.meter_name),
+        ("charge_type", &filter.charge_type),
+        ("pricing_model", &filter.pricing_model),
+        ("publisher", &filter.publisher),
+    ]
+    .iter()
+    .filter(|(_, pattern)| !pattern.is_empty())
+    .map(|(name, pattern)| format!("{name}:{pattern}"))
+    .collect();
+    if !more_filters.is_empty() {
+        println!("Filter {}.\n", more_filters.join(", "));
+    }
+    if filter.date_range.is_set() {
+        println!("Dates: {}\n", filter.date_range);
+    }
+    if !filter.excludes.is_empty() {
+        println!(
+            "Exclude: {}\n",
+            filter
+                .excludes
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    if let Some(where_expr) = &filter.where_expr {
+        println!("Where: {where_expr}\n");
+    }
     // now that we have latest_bill and disks, lookup disk cost in latest_bill
     // and print the cost
     let cur = latest_bill.get_billing_currency();
     let mut display_date = latest_bill.file_short_name.clone();
 
     let is_comparison = previous_bill.is_some();
-    let mut bill_summary = latest_bill.cost_by_any_summary(filter);
+    let cost_types = crate::bills::report::section_cost_types(filter);
+    let mut bill_summary = latest_bill.cost_by_any_summary(filter, &cost_types);
     let mut total_cost = bill_summary.filtered_cost_total;
     let mut total_cost_usd = bill_summary.filtered_cost_total_usd;
     // If we got a previous bill calculate summary and subtract.
@@ -171,7 +267,7 @@ pub fn display_cost_by_filter(
             display_date = display_date,
             prev_date = prev_bill.file_short_name
         );
-        let prev_bill_summary = prev_bill.cost_by_any_summary(filter);
+        let prev_bill_summary = prev_bill.cost_by_any_summary(filter, &cost_types);
         bill_summary.merge_summaries(&prev_bill_summary);
         total_cost = bill_summary.filtered_cost_total;
         total_cost_usd = bill_summary.filtered_cost_total_usd;
@@ -187,118 +283,16 @@ pub fn display_cost_by_filter(
         println!();
     }
 
-    // print Region bill details
-    println!(
-        "## Location bill details {} '{}'",
-        filter.location, display_date
-    );
-    print_summary(
-        &bill_summary,
-        &cur,
-        CostType::Region,
-        display_opts,
-        is_comparison,
-    );
-    println!();
-
-    // print Invoice Section bill details (only when filter specified)
-    if !filter.invoice_section.is_empty() {
-        println!(
-            "## Invoice Section bill details '{}' '{}'",
-            filter.invoice_section, display_date
-        );
-        print_summary(
-            &bill_summary,
-            &cur,
-            CostType::InvoiceSection,
-            display_opts,
-            is_comparison,
-        );
-        println!();
-    }
-
-    // print Subscription bill details
-    println!(
-        "## Subscription bill details {} '{}'",
-        filter.subscription, display_date
-    );
-    print_summary(
-        &bill_summary,
-        &cur,
-        CostType::Subscription,
-        display_opts,
-        is_comparison,
-    );
-    println!();
-    // print ResourceGroup bill details
-    println!(
-        "## ResourceGroup bill details {} '{}'",
-        filter.resource_group, display_date
-    );
-    print_summary(
-        &bill_summary,
-        &cur,
-        CostType::ResourceGroup,
-        display_opts,
-        is_comparison,
-    );
-    println!();
-    // print Resource bill details
-    if !filter.name.is_empty() {
-        println!(
-            "## ResourceName bill details {} '{}'",
-            filter.resource_group, display_date
-        );
-        print_summary(
-            &bill_summary,
-            &cur,
-            CostType::ResourceName,
-            display_opts,
-            is_comparison,
-        );
-    }
-
-    // print MeterSubCategory bill details
-    if !filter.meter_category.is_empty() {
-        println!(
-            "## MeterSubCategory bill details {} '{}'",
-            filter.resource_group, display_date
-        );
-        print_summary(
-            &bill_summary,
-            &cur,
-            CostType::MeterSubCategory,
-            display_opts,
-            is_comparison,
-        );
-        println!()
-    }
-    // print MeterCategory bill details
-    if !filter.meter_category.is_empty() {
-        println!(
-            "## MeterCategory bill details {} '{}'",
-            filter.resource_group, display_date
-        );
-        print_summary(
-            &bill_summary,
-            &cur,
-            CostType::MeterCategory,
-            display_opts,
-            is_comparison,
-        );
-        println!()
-    }
-
-    // print Tag bill details
-    if !filter.tag_summarise.is_empty() {
-        println!("## Tag details {} '{}'", filter.tag_summarise, display_date);
-        print_summary(
-            &bill_summary,
-            &cur,
-            CostType::Tag,
-            display_opts,
-            is_comparison,
-        );
+    // one section per report::section_types, the same order as --output json|csv|markdown
+    for (cost_type, pattern) in crate::bills::report::section_types(filter) {
+        let heading = match cost_type {
+            CostType::Region => "Location bill details",
+            CostType::InvoiceSection => "Invoice Section bill details",
+            CostType::Tag => "Tag details",
+            _ => &format!("{} bill details", cost_type.as_str()),
+        };
+        println!("## {heading} {pattern} '{display_date}'");
+        print_summary(&bill_summary, &cur, cost_type, display_opts, is_comparison);
         println!();
     }
 
@@ -317,33 +311,50 @@ pub fn display_cost_by_filter(
             latest_bill.tag_names.len(),
             latest_bill.tag_names
         );
+        if !latest_bill.tag_merges.is_empty() {
+            println!(
+                "Tag variants merged by alias map: {}",
+                latest_bill.tag_merges.len()
+            );
+            for m in &latest_bill.tag_merges {
+                println!(
+                    "  {} '{}' → '{}' rows:{} {cur} {}",
+                    if m.is_key {
+                        "key  ".to_string()
+                    } else {
+                        format!("{}=", m.key)
+                    },
+                    m.raw,
+                    m.canonical,
+                    m.rows,
+                    f64_to_currency(m.cost, 2),
+                );
+            }
+        }
     }
 
-    // print Reservation bill details
+    // print Reservation usage per flex group (the Reservation section is printed above)
     if !filter.reservation.is_empty() {
-        print_summary(
-            &bill_summary,
-            &cur,
-            CostType::Reservation,
-            display_opts,
-            is_comparison,
-        );
-        println!();
-
         println!();
         println!("Reservations:");
-        let mut unique_key = HashSet::new();
-        for (key, _day) in bill_summary.reservations.keys() {
-            unique_key.insert(key.clone());
+        if bill_summary.reservations.is_empty() {
+            println!("    no reservation eligible usage matched the filter");
         }
-        for key in unique_key {
+        for (key, month) in bill_summary.reservation_month_totals() {
             println!("{} '{}'", "Reservation key:".blue(), key.blue());
-            let mut res_cost_savings = 0.0;
-            let mut res_cost_unused = 0.0;
-            let mut res_cost_full = 0.0;
             let mut res_compare_days = "".to_string();
-            for day in 1..=31 {
-                if let Some(reservation) = bill_summary.reservations.get_mut(&(key.clone(), day)) {
+            let mut dates: Vec<String> = bill_summary
+                .reservations
+                .keys()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, date)| date.clone())
+                .collect();
+            dates.sort();
+            for date in dates {
+                if let Some(reservation) = bill_summary
+                    .reservations
+                    .get_mut(&(key.clone(), date.clone()))
+                {
                     // get reservation names convert to vec and sort them
                     let mut rn = reservation
                         .reservation_names
@@ -364,29 +375,29 @@ pub fn display_cost_by_filter(
                         res_compare_days = res_compare_days_new;
                         println!(
                             "Res: Day:{d} Save:{rcs:.2} Unused:{rcu:.2} FullCost:{cf:.2} , key:'{key}'\n     ResName:[{rn}]\n     VMsRes:[{rvmr}]\n     VMsNotRes:[{rvmnr}]",
-                            d = day,
+                            d = date,
                             rcs = reservation.cost_savings,
                             rcu = reservation.cost_unused,
                             cf = reservation.cost_full,
                             key = key.red(),
                         );
                     };
-                    res_cost_savings += reservation.cost_savings;
-                    res_cost_unused += reservation.cost_unused;
-                    res_cost_full += reservation.cost_full;
                 }
             }
+            let saving_pct = month.saving_pct();
             println!(
-                "    Month Total: Save:{rcs:.2} Unused:{rcu:.2} FullCost:{cf:.2} Saving:{saving_pct} key:'{key}' ",
-                rcs = res_cost_savings,
-                rcu = res_cost_unused,
-                cf = res_cost_full,
+                "    Month Total: Save:{rcs:.2} Unused:{rcu:.2} FullCost:{cf:.2} Saving:{saving_pct} Hours:{hr:.0} Reserved:{hr_res:.0} Utilisation:{util} key:'{key}' ",
+                rcs = month.cost_savings,
+                rcu = month.cost_unused,
+                cf = month.cost_full,
                 key = key.red(),
-                saving_pct = format!(
-                    "{:.0}%",
-                    res_cost_savings / (res_cost_full + res_cost_unused) * 100.0
-                )
-                .green(),
+                saving_pct = format!("{saving_pct:.0}%").green(),
+                hr = month.hr_total,
+                hr_res = month.hr_reserved,
+                util = match month.utilisation_pct() {
+                    Some(pct) => format!("{pct:.0}%").yellow(),
+                    None => "n/a".normal(),
+                },
             );
         }
         // if *day == 1 || true {
@@ -418,6 +429,117 @@ pub fn display_cost_by_filter(
     }
 }
 
+/// Print the resource-level diff produced by `bill_diff::diff_bills`.
+/// Records with `|delta| < cost_min_display` are counted but not printed.
+pub fn display_resource_diff(diff: &BillDiff, cur: &str, display_opts: &DisplayOpts) {
+    println!("## Resource changes (latest - previous)");
+    let mut skipped = 0usize;
+    for r in &diff.records {
+        if r.delta.abs() < display_opts.cost_min_display {
+            skipped += 1;
+            continue;
+        }
+        let part1 = format!("{cur} {:>11}", f64_to_currency(r.delta, 2));
+        let color_cost = match r.kind {
+            ChangeKind::Added => part1.red(),
+            ChangeKind::Removed | ChangeKind::Decreased => part1.green(),
+            ChangeKind::Increased => part1.blue(),
+            ChangeKind::Unchanged => part1.normal(),
+        };
+        let pct = r
+            .delta_pct
+            .map(|p| format!("{p:+.0}%"))
+            .unwrap_or_else(|| "new".to_string());
+        let meter = if r.meter.is_empty() {
+            String::new()
+        } else {
+            format!(" meter:'{}'", r.meter)
+        };
+        println!(
+            " diff: '{color_cost}' {pct:>6} {kind:<9} :: Rg:'{rg}' Res:'{name}'{meter} ({prev} -> {latest})",
+            kind = format!("{:?}", r.kind),
+            rg = r.resource_group,
+            name = r.resource_name,
+            prev = f64_to_currency(r.prev_cost, 2),
+            latest = f64_to_currency(r.latest_cost, 2),
+        );
+    }
+    if skipped > 0 {
+        println!(
+            " diff: skipped {skipped} with change below < '{cur} {:.2}'",
+            display_opts.cost_min_display
+        );
+    }
+    let counts = diff.count_by_kind();
+    let count = |k| counts.get(&k).copied().unwrap_or(0);
+    println!(
+        "     Total change {cur} {delta}  (US$ {delta_usd})  added:{a} removed:{r} increased:{i} decreased:{d} unchanged:{u}",
+        delta = f64_to_currency(diff.delta(), 2).bold(),
+        delta_usd = f64_to_currency(diff.delta_usd(), 2).bold(),
+        a = count(ChangeKind::Added),
+        r = count(ChangeKind::Removed),
+        i = count(ChangeKind::Increased),
+        d = count(ChangeKind::Decreased),
+        u = count(ChangeKind::Unchanged),
+    );
+    println!("     {}", legend_text(true));
+}
+
+/// Print the variance decomposition per meter category and resource group.
+/// Groups with `|total| < cost_min_display` are counted but not printed.
+pub fn display_variance(variance: &VarianceBreakdown, cur: &str, display_opts: &DisplayOpts) {
+    let header = format!(
+        "   {:>12} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "volume", "price", "fx", "conversion", "new", "removed", "total"
+    );
+    let line = |c: &VarianceComponents| {
+        format!(
+            "   {:>12} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            f64_to_currency(c.volume, 2),
+            f64_to_currency(c.price, 2),
+            f64_to_currency(c.fx, 2),
+            f64_to_currency(c.conversion, 2),
+            f64_to_currency(c.new_resources, 2).red(),
+            f64_to_currency(c.removed_resources, 2).green(),
+            f64_to_currency(c.total(), 2).bold(),
+        )
+    };
+    for (title, groups) in [
+        ("MeterCategory", &variance.by_meter_category),
+        ("ResourceGroup", &variance.by_resource_group),
+    ] {
+        println!("## Variance by {title} (latest - previous) {cur}");
+        println!("{header}");
+        let mut skipped = 0usize;
+        for (name, c) in groups {
+            if c.total().abs() < display_opts.cost_min_display {
+                skipped += 1;
+                continue;
+            }
+            println!("{} :: '{name}'", line(c));
+        }
+        if skipped > 0 {
+            println!(
+                " variance: skipped {skipped} with change below < '{cur} {:.2}'",
+                display_opts.cost_min_display
+            );
+        }
+        println!();
+    }
+    println!(
+        "## Variance total {cur}  (rate US$1 = {cur} {:.4} -> {:.4})",
+        variance.prev_rate, variance.latest_rate
+    );
+    println!("{header}");
+    println!("{}", line(&variance.total));
+    println!(
+        "     Total change {cur} {delta}  ({prev} -> {latest})",
+        delta = f64_to_currency(variance.delta(), 2).bold(),
+        prev = f64_to_currency(variance.prev_total, 2),
+        latest = f64_to_currency(variance.latest_total, 2),
+    );
+}
+
 fn sort_calc_total<'a>(
     bill_details: &'a SummaryData,
     cost_type: &CostType,
@@ -462,8 +584,14 @@ fn print_summary(
             RowColour::Blue => part1.blue().to_string(),
             RowColour::Cyan => part1.cyan().to_string(),
         };
+        // shares of a net difference are not meaningful, only shown for a single bill
+        let shares = if is_comparison {
+            String::new()
+        } else {
+            format!(" {:>5.1}% {:>5.1}%", row.pct, row.cum_pct)
+        };
         println!(
-            " bill_details: '{color_cost}' :: {t_short}:'{name}'",
+            " bill_details: '{color_cost}'{shares} :: {t_short}:'{name}'",
             t_short = cost_type.as_short(),
             name = row.name,
         );
@@ -478,7 +606,7 @@ fn print_summary(
         );
     }
 
-    let total_count = prepared.rows.len();
+    let total_count: usize = prepared.rows.iter().map(|r| r.other_count.max(1)).sum();
     if total_count > 0 {
         let total_colored = if prepared.total < 0.0 {
             f64_to_currency(prepared.total, 2)
@@ -511,7 +639,7 @@ mod tests {
         let mut s = SummaryData::default();
         for (name, ct, cost, source) in entries {
             s.per_type.insert(
-                (ct.clone(), name.to_string()),
+                (*ct, name.to_string()),
                 CostTotal {
                     cost: Nzd(*cost),
                     cost_usd: Usd(*cost),
@@ -577,6 +705,7 @@ mod tests {
             cost_min_display: 0.0,
             tag_list: false,
             debug: false,
+            top: None,
         };
         let prepared = prepare_rows(&summary, CostType::ResourceGroup, &opts);
 
@@ -598,6 +727,7 @@ mod tests {
             cost_min_display: 10.0,
             tag_list: false,
             debug: false,
+            top: None,
         };
         let prepared = prepare_rows(&summary, CostType::ResourceGroup, &opts);
 
@@ -621,6 +751,7 @@ mod tests {
             cost_min_display: 0.0,
             tag_list: false,
             debug: false,
+            top: None,
         };
         let plan = build_display_plan(&summary, CostType::ResourceGroup, &opts);
         assert_eq!(plan.rows.len(), 3, "expected 3 rows in plan");
@@ -639,6 +770,7 @@ mod tests {
             cost_min_display: 0.0,
             tag_list: false,
             debug: false,
+            top: None,
         };
         let plan = build_display_plan(&summary, CostType::ResourceGroup, &opts);
         let row = &plan.rows[0];
@@ -648,4 +780,43 @@ mod tests {
             "Secondary source should be Green"
         );
     }
+
+    #[test]
+    fn prepare_rows_top_n_other_and_pareto() {
+        let summary = make_summary(&[
+            ("rg-a", CostType::ResourceGroup, 60.0, CostSource::Original),
+            ("rg-b", CostType::ResourceGroup, 25.0, CostSource::Original),
+            ("rg-c", CostType::ResourceGroup, 10.0, CostSource::Original),
+            ("rg-d", CostType::ResourceGroup, 5.0, CostSource::Original),
+        ]);
+        let opts = DisplayOpts {
+            cost_min_display: 0.0,
+            tag_list: false,
+            debug: false,
+            top: Some(2),
+        };
+        let prepared = prepare_rows(&summary, CostType::ResourceGroup, &opts);
+        let rows: Vec<(&str, f64, f64, f64, usize)> = prepared
+            .rows
+            .iter()
+            .map(|r| (r.name.as_str(), r.cost, r.pct, r.cum_pct, r.other_count))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("other (2 items)", 15.0, 15.0, 100.0, 2),
+                ("rg-b", 25.0, 25.0, 85.0, 0),
+                ("rg-a", 60.0, 60.0, 60.0, 0),
+            ]
+        );
+        assert_eq!(prepared.total, 100.0);
+
+        let all = DisplayOpts {
+            top: Some(4),
+            ..opts
+        };
+        let prepared = prepare_rows(&summary, CostType::ResourceGroup, &all);
+        assert_eq!(prepared.rows.len(), 4);
+        assert_eq!(prepared.rows[0].cum_pct, 100.0);
+    }
 }
@@ -0,0 +1,634 @@
+//! Boolean filter expressions over bill rows (CLI `--where`, MCP `where`).
+//!
+//! The regex dimensions of `BillFilter` are always ANDed. A `FilterExpr` can
+//! combine any row field with `and`, `or`, `not` and parentheses:
+//!
+//! ```text
+//! rg ~ "prod" and (tag.env = "prod" or sub ~ "core") and not meter ~ "Bandwidth" and cost > 10
+//! ```
+//!
+//! | operator         | fields  | meaning                                      |
+//! |------------------|---------|----------------------------------------------|
+//! | `=`  `!=`        | all     | equal / not equal                            |
+//! | `~`  `!~`        | text    | regex matches / does not match               |
+//! | `<` `<=` `>` `>=`| numeric | compare                                      |
+//! | bare `tag.KEY`   | tag     | the tag exists (`not tag.KEY`: it is missing)|
+//!
+//! Text fields: `name`, `rg`, `sub`, `meter` (category), `meter_sub`,
+//! `meter_name`, `location`, `reservation` (benefit name), `invoice`,
+//! `pricing`, `charge`, `publisher`, `resource_id`, `tags` (whole lowercase
+//! tag string) and `tag.KEY` (`tag."key with spaces"`). Numeric fields:
+//! `cost` (billing currency), `cost_usd`, `quantity`, `unit_price`,
+//! `effective_price`. Long names (`resource_group`, `subscription`, ...) work too.
+//!
+//! Values are quoted (`"..."` or `'...'`), numbers, or bare words. Text
+//! comparisons follow `FilterOpts.case_sensitive`; a missing tag never
+//! equals or matches anything. Keywords are case-insensitive, `&&`, `||`
+//! and `!` are accepted as well. `location = ""` selects rows without a location.
+
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+use crate::bills::bill_entry::BillEntry;
+
+/// A row field an expression can test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Name,
+    ResourceGroup,
+    Subscription,
+    MeterCategory,
+    MeterSubCategory,
+    MeterName,
+    Location,
+    Reservation,
+    InvoiceSection,
+    PricingModel,
+    ChargeType,
+    Publisher,
+    ResourceId,
+    Tags,
+    /// Lowercase tag key.
+    Tag(String),
+    Cost,
+    CostUsd,
+    Quantity,
+    UnitPrice,
+    EffectivePrice,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        Some(match name.to_lowercase().as_str() {
+            "name" | "resource" | "resource_name" => Field::Name,
+            "rg" | "resource_group" => Field::ResourceGroup,
+            "sub" | "subscription" => Field::Subscription,
+            "meter" | "meter_category" | "category" => Field::MeterCategory,
+            "meter_sub" | "meter_sub_category" => Field::MeterSubCategory,
+            "meter_name" => Field::MeterName,
+            "location" | "region" => Field::Location,
+            "reservation" | "benefit" => Field::Reservation,
+            "invoice" | "invoice_section" => Field::InvoiceSection,
+            "pricing" | "pricing_model" => Field::PricingModel,
+            "charge" | "charge_type" => Field::ChargeType,
+            "publisher" => Field::Publisher,
+            "resource_id" | "id" => Field::ResourceId,
+            "tags" => Field::Tags,
+            "cost" => Field::Cost,
+            "cost_usd" => Field::CostUsd,
+            "quantity" | "qty" => Field::Quantity,
+            "unit_price" => Field::UnitPrice,
+            "effective_price" => Field::EffectivePrice,
+            _ => return None,
+        })
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Field::Cost
+                | Field::CostUsd
+                | Field::Quantity
+                | Field::UnitPrice
+                | Field::EffectivePrice
+        )
+    }
+
+    fn text<'a>(&self, bill: &'a BillEntry) -> Option<&'a str> {
+        Some(match self {
+            Field::Name => &bill.resource_name,
+            Field::ResourceGroup => &bill.resource_group,
+            Field::Subscription => &bill.subscription_name,
+            Field::MeterCategory => &bill.meter_category,
+            Field::MeterSubCategory => &bill.meter_sub_category,
+            Field::MeterName => &bill.meter_name,
+            Field::Location => &bill.resource_location,
+            Field::Reservation => &bill.benefit_name,
+            Field::InvoiceSection => &bill.invoice_section,
+            Field::PricingModel => &bill.pricing_model,
+            Field::ChargeType => &bill.charge_type,
+            Field::Publisher => &bill.publisher_name,
+            Field::ResourceId => &bill.resource_id,
+            Field::Tags => &bill.tags.value,
+            Field::Tag(key) => return bill.tags.kv.get(key).map(|(v, _)| v.as_str()),
+            _ => return None,
+        })
+    }
+
+    fn number(&self, bill: &BillEntry) -> f64 {
+        match self {
+            Field::Cost => bill.cost.amount(),
+            Field::CostUsd => bill.cost_usd.amount(),
+            Field::Quantity => bill.quantity,
+            Field::UnitPrice => bill.unit_price,
+            Field::EffectivePrice => bill.effective_price,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Parsed expression tree.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// Bare `tag.KEY`.
+    TagExists(String),
+    TextEq {
+        field: Field,
+        value: String,
+        negate: bool,
+    },
+    TextMatch {
+        field: Field,
+        re: Regex,
+        negate: bool,
+    },
+    Number {
+        field: Field,
+        op: NumOp,
+        value: f64,
+    },
+}
+
+impl Expr {
+    fn eval(&self, bill: &BillEntry, case_sensitive: bool) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(bill, case_sensitive) && b.eval(bill, case_sensitive),
+            Expr::Or(a, b) => a.eval(bill, case_sensitive) || b.eval(bill, case_sensitive),
+            Expr::Not(e) => !e.eval(bill, case_sensitive),
+            Expr::TagExists(key) => bill.tags.kv.contains_key(key),
+            Expr::TextEq {
+                field,
+                value,
+                negate,
+            } => {
+                let eq = field.text(bill).is_some_and(|v| {
+                    if case_sensitive {
+                        v == value
+                    } else {
+                        v.to_lowercase() == value.to_lowercase()
+                    }
+                });
+                eq != *negate
+            }
+            Expr::TextMatch { field, re, negate } => {
+                field.text(bill).is_some_and(|v| re.is_match(v)) != *negate
+            }
+            Expr::Number { field, op, value } => {
+                let v = field.number(bill);
+                match op {
+                    NumOp::Eq => v == *value,
+                    NumOp::Ne => v != *value,
+                    NumOp::Lt => v < *value,
+                    NumOp::Le => v <= *value,
+                    NumOp::Gt => v > *value,
+                    NumOp::Ge => v >= *value,
+                }
+            }
+        }
+    }
+}
+
+/// A parsed `--where` expression; `Display` gives back the source text.
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    source: String,
+    root: Expr,
+    case_sensitive: bool,
+}
+
+impl FilterExpr {
+    /// Parse `source`; errors name the column and point at it.
+    pub fn parse(source: &str, case_sensitive: bool) -> Result<Self, String> {
+        let tokens = tokenize(source).map_err(|(pos, msg)| parse_error(source, pos, &msg))?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            case_sensitive,
+        };
+        let root = parser
+            .or_expr()
+            .and_then(|root| match parser.peek() {
+                (Tok::End, _) => Ok(root),
+                (tok, pos) => Err((
+                    pos,
+                    format!("Unexpected {tok}, expected 'and', 'or' or end"),
+                )),
+            })
+            .map_err(|(pos, msg)| parse_error(source, pos, &msg))?;
+        Ok(FilterExpr {
+            source: source.trim().to_string(),
+            root,
+            case_sensitive,
+        })
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.root
+    }
+
+    pub fn matches(&self, bill: &BillEntry) -> bool {
+        self.root.eval(bill, self.case_sensitive)
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// "message at column N" plus the expression with a caret under the column.
+fn parse_error(source: &str, pos: usize, msg: &str) -> String {
+    let col = source[..pos.min(source.len())].chars().count();
+    format!(
+        "Invalid where expression: {msg} at column {}\n  {source}\n  {}^",
+        col + 1,
+        " ".repeat(col)
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    /// Value and the text as written.
+    Num(f64, String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Dot,
+    End,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Ident(s) => write!(f, "'{s}'"),
+            Tok::Str(s) => write!(f, "string \"{s}\""),
+            Tok::Num(_, text) => write!(f, "number {text}"),
+            Tok::Op(op) => write!(f, "'{op}'"),
+            Tok::LParen => write!(f, "'('"),
+            Tok::RParen => write!(f, "')'"),
+            Tok::Dot => write!(f, "'.'"),
+            Tok::End => write!(f, "end of expression"),
+        }
+    }
+}
+
+type Spanned = (Tok, usize);
+type PResult<T> = Result<T, (usize, String)>;
+
+/// Longest first so `<=` is not read as `<`.
+const OPERATORS: [&str; 12] = [
+    "&&", "||", "==", "!=", "!~", "<=", ">=", "=", "~", "<", ">", "!",
+];
+
+fn tokenize(src: &str) -> PResult<Vec<Spanned>> {
+    let mut tokens = Vec::new();
+    let chars: Vec<(usize, char)> = src.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' || c == '.' {
+            tokens.push((
+                match c {
+                    '(' => Tok::LParen,
+                    ')' => Tok::RParen,
+                    _ => Tok::Dot,
+                },
+                pos,
+            ));
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err((pos, "Unterminated string".to_string())),
+                    Some((_, '\\')) if i + 1 < chars.len() => {
+                        s.push(chars[i + 1].1);
+                        i += 2;
+                    }
+                    Some((_, q)) if *q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some((_, ch)) => {
+                        s.push(*ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Tok::Str(s), pos));
+        } else if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].1.is_ascii_digit() || chars[i].1 == '.') {
+                i += 1;
+            }
+            let end = chars.get(i).map_or(src.len(), |(p, _)| *p);
+            let text = &src[pos..end];
+            let n = text
+                .parse::<f64>()
+                .map_err(|_| (chars[start].0, format!("Invalid number '{text}'")))?;
+            tokens.push((Tok::Num(n, text.to_string()), pos));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut s = String::new();
+            while i < chars.len() {
+                let ch = chars[i].1;
+                if ch.is_alphanumeric() || ch == '_' || ch == '-' {
+                    s.push(ch);
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push((Tok::Ident(s), pos));
+        } else if let Some(op) = OPERATORS.iter().find(|op| src[pos..].starts_with(**op)) {
+            tokens.push((Tok::Op(op), pos));
+            i += op.chars().count();
+        } else {
+            return Err((pos, format!("Unexpected character '{c}'")));
+        }
+    }
+    tokens.push((Tok::End, src.len()));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    case_sensitive: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> (Tok, usize) {
+        self.tokens[self.pos].clone()
+    }
+
+    fn next(&mut self) -> (Tok, usize) {
+        let t = self.peek();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        t
+    }
+
+    /// Consume a keyword (`and`, `or`, `not`) or its symbol form.
+    fn keyword(&mut self, word: &str, symbol: &str) -> bool {
+        let hit = match &self.peek().0 {
+            Tok::Ident(s) => s.eq_ignore_ascii_case(word),
+            Tok::Op(op) => *op == symbol,
+            _ => false,
+        };
+        if hit {
+            self.next();
+        }
+        hit
+    }
+
+    fn or_expr(&mut self) -> PResult<Expr> {
+        let mut lhs = self.and_expr()?;
+        while self.keyword("or", "||") {
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and_expr()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and_expr(&mut self) -> PResult<Expr> {
+        let mut lhs = self.unary()?;
+        while self.keyword("and", "&&") {
+            lhs = Expr::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> PResult<Expr> {
+        if self.keyword("not", "!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek().0 == Tok::LParen {
+            let (_, open) = self.next();
+            let e = self.or_expr()?;
+            return match self.next() {
+                (Tok::RParen, _) => Ok(e),
+                (tok, pos) => Err((
+                    pos,
+                    format!(
+                        "Expected ')' to close '(' at column {}, found {tok}",
+                        open + 1
+                    ),
+                )),
+            };
+        }
+        self.comparison()
+    }
+
+    fn field(&mut self) -> PResult<(Field, usize)> {
+        match self.next() {
+            (Tok::Ident(name), pos) if name.eq_ignore_ascii_case("tag") => {
+                match self.next() {
+                    (Tok::Dot, _) => {}
+                    (tok, pos) => {
+                        return Err((pos, format!("Expected '.' after 'tag', found {tok}")));
+                    }
+                }
+                match self.next() {
+                    (Tok::Ident(key) | Tok::Str(key), _) if !key.trim().is_empty() => {
+                        Ok((Field::Tag(key.trim().to_lowercase()), pos))
+                    }
+                    (tok, pos) => Err((pos, format!("Expected tag key after 'tag.', found {tok}"))),
+                }
+            }
+            (Tok::Ident(name), pos) => Field::from_name(&name)
+                .map(|f| (f, pos))
+                .ok_or_else(|| (pos, format!("Unknown field '{name}'"))),
+            (tok, pos) => Err((pos, format!("Expected a field name, found {tok}"))),
+        }
+    }
+
+    fn comparison(&mut self) -> PResult<Expr> {
+        let (field, field_pos) = self.field()?;
+        let op = match self.peek() {
+            (Tok::Op(op), _) if !matches!(op, "&&" | "||" | "!") => {
+                self.next();
+                op
+            }
+            (tok, pos) => {
+                return match field {
+                    Field::Tag(key) => Ok(Expr::TagExists(key)),
+                    _ => Err((
+                        pos,
+                        format!("Expected an operator (= != ~ !~ < <= > >=), found {tok}"),
+                    )),
+                };
+            }
+        };
+        let (value, value_pos) = self.next();
+        if field.is_numeric() {
+            let op = match op {
+                "=" | "==" => NumOp::Eq,
+                "!=" => NumOp::Ne,
+                "<" => NumOp::Lt,
+                "<=" => NumOp::Le,
+                ">" => NumOp::Gt,
+                ">=" => NumOp::Ge,
+                _ => {
+                    return Err((
+                        field_pos,
+                        format!("Operator '{op}' needs a text field, {field:?} is numeric"),
+                    ));
+                }
+            };
+            return match value {
+                Tok::Num(value, _) => Ok(Expr::Number { field, op, value }),
+                tok => Err((value_pos, format!("Expected a number, found {tok}"))),
+            };
+        }
+        let value = match value {
+            // numbers keep the text as written, e.g. invoice = 0042
+            Tok::Str(s) | Tok::Ident(s) | Tok::Num(_, s) => s,
+            tok => return Err((value_pos, format!("Expected a value, found {tok}"))),
+        };
+        Ok(match op {
+            "=" | "==" | "!=" => Expr::TextEq {
+                field,
+                value,
+                negate: op == "!=",
+            },
+            "~" | "!~" => Expr::TextMatch {
+                field,
+                re: RegexBuilder::new(&value)
+                    .case_insensitive(!self.case_sensitive)
+                    .build()
+                    .map_err(|e| (value_pos, format!("Invalid regex \"{value}\": {e}")))?,
+                negate: op == "!~",
+            },
+            _ => {
+                return Err((
+                    field_pos,
+                    format!(
+                        "Operator '{op}' needs a numeric field (cost, cost_usd, quantity, ...)"
+                    ),
+                ));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::row;
+
+    #[test]
+    fn precedence_grouping_and_negation() {
+        let e = FilterExpr::parse(
+            r#"rg ~ "prod" and (tag.env = "prod" or sub ~ "core") and not meter ~ "Bandwidth" and cost > 10"#,
+            false,
+        )
+        .unwrap();
+        assert!(
+            e.matches(
+                &row()
+                    .sub("x")
+                    .rg("RG-Prod")
+                    .meter("Compute")
+                    .tags(r#""Env": "PROD""#)
+                    .cost(20.0)
+            )
+        );
+        assert!(
+            e.matches(
+                &row()
+                    .sub("core-sub")
+                    .rg("rg-prod")
+                    .meter("Storage")
+                    .cost(20.0)
+            )
+        );
+        assert!(
+            !e.matches(
+                &row()
+                    .sub("x")
+                    .rg("rg-prod")
+                    .meter("Compute")
+                    .tags(r#""env": "dev""#)
+                    .cost(20.0)
+            )
+        );
+        assert!(
+            !e.matches(
+                &row()
+                    .sub("core")
+                    .rg("rg-prod")
+                    .meter("Bandwidth")
+                    .cost(20.0)
+            )
+        );
+        assert!(!e.matches(&row().sub("core").rg("rg-prod").meter("Compute").cost(10.0)));
+
+        // and binds tighter than or
+        let e = FilterExpr::parse("sub = a or sub = b and cost >= 5", false).unwrap();
+        assert!(e.matches(&row().sub("a").cost(0.0)));
+        assert!(!e.matches(&row().sub("b").cost(1.0)));
+        assert_eq!(e.to_string(), "sub = a or sub = b and cost >= 5");
+    }
+
+    #[test]
+    fn tags_and_empty_values() {
+        let tagged = row().tags(r#""owner": "al""#).cost(1.0);
+        let untagged = row().cost(1.0);
+        let exists = FilterExpr::parse("tag.owner", false).unwrap();
+        assert!(exists.matches(&tagged) && !exists.matches(&untagged));
+        let missing = FilterExpr::parse("!tag.\"owner\"", false).unwrap();
+        assert!(missing.matches(&untagged));
+        let ne = FilterExpr::parse("tag.owner != al", false).unwrap();
+        assert!(ne.matches(&untagged), "missing tag is not equal");
+        assert!(!ne.matches(&tagged));
+        let no_location = FilterExpr::parse(r#"location = """#, false).unwrap();
+        assert!(no_location.matches(&untagged));
+        let cs = FilterExpr::parse("tag.owner = AL", true).unwrap();
+        assert!(!cs.matches(&tagged));
+        let num = FilterExpr::parse("rg = 0042 || cost < -1", false).unwrap();
+        assert!(num.matches(&row().rg("0042").cost(0.0)));
+        assert!(!num.matches(&row().rg("42").cost(0.0)));
+        assert!(num.matches(&row().rg("42").cost(-2.0)));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let err = |s: &str| FilterExpr::parse(s, false).unwrap_err();
+        let e = err("rg ~ prod and colour = red");
+        assert!(e.contains("Unknown field 'colour' at column 15"), "{e}");
+        assert!(
+            e.ends_with("\n  rg ~ prod and colour = red\n                ^"),
+            "{e}"
+        );
+        assert!(err("(rg ~ a").contains("Expected ')'"));
+        assert!(err("cost ~ 5").contains("needs a text field"));
+        assert!(err("rg > 5").contains("needs a numeric field"));
+        assert!(err("cost > ten").contains("Expected a number"));
+        assert!(err("rg ~ \"(\"").contains("Invalid regex"));
+        assert!(err("rg = 'x").contains("Unterminated string at column 6"));
+        assert!(err("rg = a b").contains("Unexpected 'b'"));
+        assert!(err("rg").contains("Expected an operator"));
+    }
+}
@@ -0,0 +1,427 @@
+//! Self-contained HTML report (`html-report` subcommand) for finance.
+//!
+//! `build_html_report` collects the filtered month: totals, every section of the
+//! default report (via `report::build_report`, so the tables match the text and
+//! `--output` reports) with the previous month's cost of each row alongside, the
+//! daily cost and the reservation / savings plan savings. `render_html` writes it
+//! as one static page: inline CSS, an inline SVG bar chart and a few lines of JS
+//! to sort tables. Nothing is loaded over the network.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::report::{self, ReportSection};
+use crate::cmd_parse::DisplayOpts;
+use crate::f64_to_currency;
+
+/// A report section with the previous month's cost per row (`None` without one,
+/// or for keys absent last month and for "other (N items)" rows).
+#[derive(Debug, Clone)]
+pub struct ComparedSection {
+    pub section: ReportSection,
+    pub previous: Vec<Option<f64>>,
+    pub previous_total: Option<f64>,
+}
+
+/// Reservation / savings plan savings of one meter category, in USD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavingsRow {
+    pub meter_category: String,
+    pub used: f64,
+    pub unused: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HtmlReport {
+    pub month: String,
+    pub prev_month: Option<String>,
+    pub currency: String,
+    pub filter: BTreeMap<String, String>,
+    pub total_cost: f64,
+    pub total_cost_usd: f64,
+    pub prev_total_cost: Option<f64>,
+    pub sections: Vec<ComparedSection>,
+    /// (ISO date, cost) in date order.
+    pub daily: Vec<(String, f64)>,
+    pub savings_used: f64,
+    pub savings_unused: f64,
+    /// Largest savings first.
+    pub savings: Vec<SavingsRow>,
+}
+
+/// Build the report for `latest`, compared with `prev` (bill and month label) when given.
+pub fn build_html_report(
+    latest: &Bills,
+    month: &str,
+    prev: Option<(&Bills, &str)>,
+    filter: &BillFilter,
+    display_opts: &DisplayOpts,
+) -> HtmlReport {
+    let rep = report::build_report(filter, latest, None, display_opts);
+    let cost_types = report::section_cost_types(filter);
+    let prev_summary = prev.map(|(p, _)| p.cost_by_any_summary(filter, &cost_types));
+    let sections = report::section_types(filter)
+        .into_iter()
+        .zip(rep.sections)
+        .map(|((cost_type, _), section)| {
+            let Some(ps) = &prev_summary else {
+                return ComparedSection {
+                    previous: vec![None; section.rows.len()],
+                    previous_total: None,
+                    section,
+                };
+            };
+            let previous = section
+                .rows
+                .iter()
+                .map(|r| {
+                    ps.per_type
+                        .get(&(cost_type, r.name.clone()))
+                        .map(|c| c.cost.amount())
+                })
+                .collect();
+            let previous_total = ps
+                .per_type
+                .iter()
+                .filter(|((t, _), _)| *t == cost_type)
+                .map(|(_, c)| c.cost.amount())
+                .sum();
+            ComparedSection {
+                section,
+                previous,
+                previous_total: Some(previous_total),
+            }
+        })
+        .collect();
+
+    let mut daily: BTreeMap<String, f64> = BTreeMap::new();
+    for bill in latest.bills.iter().filter(|b| filter.matches(b)) {
+        if !bill.date.is_empty() {
+            *daily.entry(bill.date.clone()).or_default() += bill.cost.amount();
+        }
+    }
+
+    let totals = latest.calc_filtered_totals(filter);
+    let mut savings: Vec<SavingsRow> = totals
+        .total_savings_meter_category_map
+        .iter()
+        .map(|(category, (used, unused))| SavingsRow {
+            meter_category: category.clone(),
+            used: used.amount(),
+            unused: unused.amount(),
+        })
+        .collect();
+    savings.sort_by(|a, b| {
+        b.used
+            .total_cmp(&a.used)
+            .then_with(|| a.meter_category.cmp(&b.meter_category))
+    });
+
+    HtmlReport {
+        month: month.to_string(),
+        prev_month: prev.map(|(_, m)| m.to_string()),
+        currency: rep.currency,
+        filter: rep.filter,
+        total_cost: rep.total_cost,
+        total_cost_usd: rep.total_cost_usd,
+        prev_total_cost: prev_summary
+            .as_ref()
+            .map(|s| s.filtered_cost_total.amount()),
+        sections,
+        daily: daily.into_iter().collect(),
+        savings_used: totals.total_savings_used.amount(),
+        savings_unused: totals.total_savings_un_used.amount(),
+        savings,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn money(value: f64) -> String {
+    f64_to_currency(value, 2)
+}
+
+/// Change cell: signed amount, red when the cost went up.
+fn delta_cell(current: f64, previous: Option<f64>) -> String {
+    match previous {
+        Some(p) => {
+            let delta = current - p;
+            let class = if delta > 0.0 { "up" } else { "down" };
+            format!("<td class=\"num {class}\">{}</td>", money(delta))
+        }
+        None => "<td class=\"num\">new</td>".to_string(),
+    }
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2em;color:#222}\
+h1{margin-bottom:.2em}table{border-collapse:collapse;margin:.5em 0 2em}\
+th,td{padding:.25em .8em;border-bottom:1px solid #ddd;text-align:left}\
+th{cursor:pointer;background:#f4f4f4}td.num{text-align:right;font-variant-numeric:tabular-nums}\
+td.up{color:#b00}td.down{color:#070}.cards{display:flex;gap:1em;flex-wrap:wrap}\
+.card{border:1px solid #ddd;border-radius:6px;padding:.6em 1em}.card b{display:block;font-size:1.4em}\
+svg rect{fill:#3a6ea5}svg rect:hover{fill:#f0a030}.muted{color:#777}";
+
+const SCRIPT: &str = "document.querySelectorAll('table.sort thead th').forEach(function(th,i){\
+th.addEventListener('click',function(){var t=th.closest('table'),b=t.tBodies[0],\
+r=Array.from(b.rows),asc=th.dataset.asc!=='1';th.dataset.asc=asc?'1':'0';\
+r.sort(function(x,y){var a=x.cells[i].dataset.v||x.cells[i].textContent,\
+c=y.cells[i].dataset.v||y.cells[i].textContent,n=parseFloat(a)-parseFloat(c);\
+if(isNaN(n))n=a.localeCompare(c);return asc?n:-n});r.forEach(function(e){b.appendChild(e)})})});";
+
+fn daily_chart(out: &mut String, daily: &[(String, f64)], cur: &str) {
+    if daily.is_empty() {
+        out.push_str("<p class=\"muted\">No dated rows.</p>\n");
+        return;
+    }
+    let (bar, gap, height) = (18.0, 4.0, 160.0);
+    let max = daily.iter().map(|(_, c)| *c).fold(0.0_f64, f64::max);
+    let width = daily.len() as f64 * (bar + gap);
+    let _ = writeln!(
+        out,
+        "<svg width=\"{width}\" height=\"{}\" role=\"img\" aria-label=\"Daily cost\">",
+        height + 40.0
+    );
+    for (i, (date, cost)) in daily.iter().enumerate() {
+        let h = if max > 0.0 {
+            (cost.max(0.0) / max * height).max(1.0)
+        } else {
+            1.0
+        };
+        let x = i as f64 * (bar + gap);
+        let _ = writeln!(
+            out,
+            "<rect x=\"{x}\" y=\"{:.1}\" width=\"{bar}\" height=\"{h:.1}\"><title>{} {cur} {}</title></rect>",
+            height - h,
+            escape(date),
+            money(*cost)
+        );
+        let day = date.rsplit('-').next().unwrap_or(date);
+        let _ = writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>",
+            x + bar / 2.0,
+            height + 14.0,
+            escape(day)
+        );
+    }
+    out.push_str("</svg>\n");
+}
+
+/// Render the report as one self-contained HTML page.
+pub fn render_html(rep: &HtmlReport) -> String {
+    let cur = escape(&rep.currency);
+    let mut out = String::new();
+    let title = match &rep.prev_month {
+        Some(prev) => format!("Azure cost report {} (vs {prev})", rep.month),
+        None => format!("Azure cost report {}", rep.month),
+    };
+    let title = escape(&title);
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>"
+    );
+
+    out.push_str("<div class=\"cards\">\n");
+    let _ = writeln!(
+        out,
+        "<div class=\"card\">Total excl. GST<b>{cur} {}</b>US$ {}</div>",
+        money(rep.total_cost),
+        money(rep.total_cost_usd)
+    );
+    if let (Some(prev), Some(prev_month)) = (rep.prev_total_cost, &rep.prev_month) {
+        let delta = rep.total_cost - prev;
+        let pct = if prev != 0.0 {
+            format!(" ({:+.1}%)", delta / prev * 100.0)
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            out,
+            "<div class=\"card\">{}<b>{cur} {}</b>change {cur} {}{}{pct}</div>",
+            escape(prev_month),
+            money(prev),
+            if delta > 0.0 { "+" } else { "" },
+            money(delta),
+        );
+    }
+    let _ = writeln!(
+        out,
+        "<div class=\"card\">Reservation savings<b>US$ {}</b>unused US$ {}</div>",
+        money(rep.savings_used),
+        money(rep.savings_unused)
+    );
+    out.push_str("</div>\n");
+
+    if !rep.filter.is_empty() {
+        out.push_str("<p class=\"muted\">Filter: ");
+        let parts: Vec<String> = rep
+            .filter
+            .iter()
+            .map(|(k, v)| format!("{k} <code>{}</code>", escape(v)))
+            .collect();
+        out.push_str(&parts.join(", "));
+        out.push_str("</p>\n");
+    }
+
+    out.push_str("<h2>Daily cost</h2>\n");
+    daily_chart(&mut out, &rep.daily, &cur);
+
+    let compare = rep.prev_month.is_some();
+    for s in &rep.sections {
+        let _ = writeln!(
+            out,
+            "<h2>{} {}</h2>\n<table class=\"sort\">\n<thead><tr><th>Name</th><th>Cost ({cur})</th><th>%</th><th>Cum %</th>{}</tr></thead>\n<tbody>",
+            escape(s.section.dimension),
+            escape(&s.section.pattern),
+            if compare {
+                "<th>Previous</th><th>Change</th>"
+            } else {
+                ""
+            }
+        );
+        for (r, prev) in s.section.rows.iter().zip(&s.previous) {
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td class=\"num\" data-v=\"{}\">{}</td><td class=\"num\">{:.1}</td><td class=\"num\">{:.1}</td>",
+                escape(&r.name),
+                r.cost,
+                money(r.cost),
+                r.pct.unwrap_or_default(),
+                r.cum_pct.unwrap_or_default()
+            );
+            if compare {
+                let prev_cell = prev.map(money).unwrap_or_default();
+                let _ = write!(
+                    out,
+                    "<td class=\"num\" data-v=\"{}\">{prev_cell}</td>{}",
+                    prev.unwrap_or(0.0),
+                    delta_cell(r.cost, *prev)
+                );
+            }
+            out.push_str("</tr>\n");
+        }
+        let _ = write!(
+            out,
+            "</tbody>\n<tfoot><tr><th>Total</th><th class=\"num\">{}</th><th></th><th></th>",
+            money(s.section.total)
+        );
+        if compare {
+            let prev = s.previous_total.unwrap_or(0.0);
+            let _ = write!(
+                out,
+                "<th class=\"num\">{}</th>{}",
+                money(prev),
+                delta_cell(s.section.total, Some(prev))
+            );
+        }
+        out.push_str("</tr></tfoot>\n</table>\n");
+        if s.section.skipped_count > 0 {
+            let _ = writeln!(
+                out,
+                "<p class=\"muted\">{} rows below the display threshold.</p>",
+                s.section.skipped_count
+            );
+        }
+    }
+
+    out.push_str("<h2>Reservation and savings plan savings (US$)</h2>\n");
+    if rep.savings.is_empty() {
+        out.push_str("<p class=\"muted\">No reservation usage.</p>\n");
+    } else {
+        out.push_str("<table class=\"sort\">\n<thead><tr><th>Meter category</th><th>Saved</th><th>Unused</th></tr></thead>\n<tbody>\n");
+        for s in &rep.savings {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"num\" data-v=\"{}\">{}</td><td class=\"num\" data-v=\"{}\">{}</td></tr>",
+                escape(&s.meter_category),
+                s.used,
+                money(s.used),
+                s.unused,
+                money(s.unused)
+            );
+        }
+        out.push_str("</tbody>\n</table>\n");
+    }
+
+    let _ = writeln!(out, "<script>{SCRIPT}</script>\n</body>\n</html>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::cost_type_enum::CostType;
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    fn opts() -> DisplayOpts {
+        DisplayOpts {
+            cost_min_display: 0.0,
+            tag_list: false,
+            debug: false,
+            top: None,
+        }
+    }
+
+    #[test]
+    fn daily_totals_and_previous_month() {
+        let latest = bills(vec![
+            row().rg("rg-a").date("2026-01-01").cost(10.0),
+            row().rg("rg-a").date("2026-01-02").cost(5.0),
+            row().rg("rg-new").date("2026-01-01").cost(2.0),
+        ]);
+        let prev = bills(vec![row().rg("rg-a").date("2025-12-01").cost(12.0)]);
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let rep = build_html_report(
+            &latest,
+            "2026-01",
+            Some((&prev, "2025-12")),
+            &filter,
+            &opts(),
+        );
+        assert_eq!(
+            rep.daily,
+            vec![
+                ("2026-01-01".to_string(), 12.0),
+                ("2026-01-02".to_string(), 5.0)
+            ]
+        );
+        assert_eq!(rep.prev_total_cost, Some(12.0));
+        let rg = &rep.sections[2];
+        assert_eq!(rg.section.dimension, CostType::ResourceGroup.as_str());
+        let names: Vec<&str> = rg.section.rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["rg-a", "rg-new"]);
+        assert_eq!(rg.previous, vec![Some(12.0), None]);
+        assert_eq!(rg.previous_total, Some(12.0));
+    }
+
+    #[test]
+    fn render_is_self_contained_and_escaped() {
+        let latest = bills(vec![row().rg("rg-<script>").date("2026-01-01").cost(10.0)]);
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let rep = build_html_report(&latest, "2026-01", None, &filter, &opts());
+        let html = render_html(&rep);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("rg-&lt;script&gt;"));
+        assert!(!html.contains("rg-<script>"));
+        assert!(!html.contains("http://") && !html.contains("https://"));
+        assert!(html.contains("<svg") && html.contains("<title>2026-01-01 NZD 10.00</title>"));
+        assert!(!html.contains("<th>Previous</th>"));
+    }
+}
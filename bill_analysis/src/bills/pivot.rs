@@ -0,0 +1,391 @@
+//! Pivot table: filtered rows grouped by an ordered list of dimensions
+//! (`pivot --by subscription,meter_category`), with a subtotal at every level.
+//!
+//! `cost_by_any_summary` totals each `CostType` on its own; a pivot nests them,
+//! e.g. subscription × meter category or resource group × `tag:team`. Keys
+//! are the same as the query engine's ([`GroupBy`]), so a pivot by a single
+//! dimension gives the `query` rows. Output is a tree ([`PivotNode`], JSON and
+//! nested terminal layout) or flattened to one line per group with subtotal
+//! lines ([`flatten`], CSV and flat terminal layout).
+
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::query::{self, GroupBy, Metric, QueryRow, SortOrder};
+use crate::f64_to_currency;
+
+/// How the pivot is laid out in terminal and JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PivotLayout {
+    /// Indented tree, subtotal on each group's line.
+    #[default]
+    Nested,
+    /// One line per leaf with every key, subtotal lines after each group.
+    Flat,
+}
+
+/// Dimensions and ranking of a pivot.
+#[derive(Debug)]
+pub struct PivotSpec {
+    /// Outermost first.
+    pub dimensions: Vec<GroupBy>,
+    pub metric: Metric,
+    pub sort: SortOrder,
+    /// Children kept per group after sorting; subtotals still include the rest.
+    pub limit: Option<usize>,
+}
+
+/// One group; the root is the grand total.
+#[derive(Debug, Clone, Serialize)]
+pub struct PivotNode {
+    pub key: String,
+    pub cost: f64,
+    pub cost_usd: f64,
+    pub quantity: f64,
+    pub rows: usize,
+    /// Children dropped by the limit.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub more: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PivotNode>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PivotTable {
+    pub dimensions: Vec<String>,
+    pub metric: Metric,
+    pub total: PivotNode,
+}
+
+/// One line of the flat layout.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlatRow {
+    /// One key per dimension; a subtotal row leaves the inner keys empty.
+    pub keys: Vec<String>,
+    /// Number of keys set; `dimensions.len()` for a leaf, 0 for the grand total.
+    pub level: usize,
+    pub subtotal: bool,
+    pub cost: f64,
+    pub cost_usd: f64,
+    pub quantity: f64,
+    pub rows: usize,
+}
+
+#[derive(Default)]
+struct Acc {
+    total: QueryRow,
+    children: HashMap<String, Acc>,
+}
+
+impl Acc {
+    fn insert(&mut self, dims: &[GroupBy], bill: &crate::bills::bill_entry::BillEntry) {
+        self.total.add(bill);
+        if let Some((dim, rest)) = dims.split_first() {
+            self.children
+                .entry(dim.label_of(bill))
+                .or_default()
+                .insert(rest, bill);
+        }
+    }
+
+    fn finish(self, key: String, spec: &PivotSpec) -> PivotNode {
+        let mut children: Vec<PivotNode> = self
+            .children
+            .into_iter()
+            .map(|(k, acc)| acc.finish(k, spec))
+            .collect();
+        children.sort_by(|a, b| {
+            let (va, vb) = (a.value(spec.metric), b.value(spec.metric));
+            match spec.sort {
+                SortOrder::Desc => vb.partial_cmp(&va).unwrap_or(Ordering::Equal),
+                SortOrder::Asc => va.partial_cmp(&vb).unwrap_or(Ordering::Equal),
+                SortOrder::Key => Ordering::Equal,
+            }
+            .then_with(|| a.key.cmp(&b.key))
+        });
+        let kept = spec.limit.unwrap_or(usize::MAX).min(children.len());
+        let more = children.len() - kept;
+        children.truncate(kept);
+        PivotNode {
+            key,
+            cost: self.total.cost,
+            cost_usd: self.total.cost_usd,
+            quantity: self.total.quantity,
+            rows: self.total.rows,
+            more,
+            children,
+        }
+    }
+}
+
+impl PivotNode {
+    pub fn value(&self, metric: Metric) -> f64 {
+        match metric {
+            Metric::Cost => self.cost,
+            Metric::CostUsd => self.cost_usd,
+            Metric::Quantity => self.quantity,
+            Metric::Rows => self.rows as f64,
+        }
+    }
+}
+
+/// Group the rows of `months` passing `filter` by `spec.dimensions`.
+pub fn pivot(months: &[&Bills], filter: &BillFilter, spec: &PivotSpec) -> PivotTable {
+    let mut root = Acc::default();
+    for bill in query::select(months, filter) {
+        root.insert(&spec.dimensions, bill);
+    }
+    PivotTable {
+        dimensions: spec.dimensions.iter().map(|d| d.to_string()).collect(),
+        metric: spec.metric,
+        total: root.finish("Total".to_string(), spec),
+    }
+}
+
+/// Depth-first rows: leaves with every key, each group's subtotal after its
+/// children, the grand total last.
+pub fn flatten(table: &PivotTable) -> Vec<FlatRow> {
+    fn walk(node: &PivotNode, path: &mut Vec<String>, depth: usize, out: &mut Vec<FlatRow>) {
+        for child in &node.children {
+            path.push(child.key.clone());
+            walk(child, path, depth, out);
+            path.pop();
+        }
+        let mut keys = path.clone();
+        keys.resize(depth, String::new());
+        out.push(FlatRow {
+            keys,
+            level: path.len(),
+            subtotal: path.len() < depth,
+            cost: node.cost,
+            cost_usd: node.cost_usd,
+            quantity: node.quantity,
+            rows: node.rows,
+        });
+    }
+    let mut out = Vec::new();
+    walk(
+        &table.total,
+        &mut Vec::new(),
+        table.dimensions.len(),
+        &mut out,
+    );
+    out
+}
+
+/// Flat layout as CSV, one column per dimension.
+pub fn pivot_to_csv(table: &PivotTable) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    let mut header = table.dimensions.clone();
+    header.extend(["cost", "cost_usd", "quantity", "rows", "pct", "subtotal"].map(String::from));
+    wtr.write_record(&header)?;
+    for r in flatten(table) {
+        let mut record = r.keys.clone();
+        record.extend([
+            format!("{:.2}", r.cost),
+            format!("{:.2}", r.cost_usd),
+            format!("{:.4}", r.quantity),
+            r.rows.to_string(),
+            format!("{:.1}", query::pct(r.cost, table.total.cost)),
+            r.subtotal.to_string(),
+        ]);
+        wtr.write_record(&record)?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+fn print_line(cost: f64, cost_usd: f64, share: f64, rows: usize, label: &str, cur: &str) {
+    println!(
+        "{:>16} {:>14} {:>7} {:>7}  {}",
+        format!("{cur} {}", f64_to_currency(cost, 2)).yellow(),
+        format!("US$ {}", f64_to_currency(cost_usd, 2)),
+        format!("{share:.1}%"),
+        rows,
+        label,
+    );
+}
+
+/// Print the pivot as coloured terminal text in `layout`.
+pub fn print_pivot(table: &PivotTable, layout: PivotLayout, cur: &str) {
+    let total = table.total.cost;
+    println!(
+        "{:>16} {:>14} {:>7} {:>7}  {}",
+        "cost",
+        "USD",
+        "pct",
+        "rows",
+        table.dimensions.join(" / ")
+    );
+    match layout {
+        PivotLayout::Nested => {
+            fn walk(node: &PivotNode, depth: usize, total: f64, cur: &str) {
+                for child in &node.children {
+                    let label = format!("{:indent$}'{}'", "", child.key, indent = depth * 2);
+                    let label = if child.children.is_empty() {
+                        label
+                    } else {
+                        label.bold().to_string()
+                    };
+                    print_line(
+                        child.cost,
+                        child.cost_usd,
+                        query::pct(child.cost, total),
+                        child.rows,
+                        &label,
+                        cur,
+                    );
+                    walk(child, depth + 1, total, cur);
+                }
+                if node.more > 0 {
+                    println!(
+                        "{:>48}  {:indent$}... {} more",
+                        "",
+                        "",
+                        node.more,
+                        indent = depth * 2
+                    );
+                }
+            }
+            walk(&table.total, 0, total, cur);
+        }
+        PivotLayout::Flat => {
+            for r in flatten(table).iter().filter(|r| r.level > 0) {
+                let keys = r.keys[..r.level]
+                    .iter()
+                    .map(|k| format!("'{k}'"))
+                    .collect::<Vec<_>>()
+                    .join(" / ");
+                let label = if r.subtotal {
+                    format!("{keys} subtotal").bold().to_string()
+                } else {
+                    keys
+                };
+                print_line(
+                    r.cost,
+                    r.cost_usd,
+                    query::pct(r.cost, total),
+                    r.rows,
+                    &label,
+                    cur,
+                );
+            }
+        }
+    }
+    println!(
+        "Total {cur} {} (US$ {}) over {} rows",
+        f64_to_currency(total, 2).bold(),
+        f64_to_currency(table.total.cost_usd, 2),
+        table.total.rows,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    fn sample() -> Bills {
+        bills(vec![
+            row()
+                .sub("prod")
+                .meter("Compute")
+                .tags(r#""team": "a""#)
+                .cost(10.0),
+            row()
+                .sub("prod")
+                .meter("Storage")
+                .tags(r#""team": "a""#)
+                .cost(4.0),
+            row()
+                .sub("prod")
+                .meter("Compute")
+                .tags(r#""team": "b""#)
+                .cost(6.0),
+            row().sub("dev").meter("Compute").cost(3.0),
+        ])
+    }
+
+    fn spec(by: &[&str]) -> PivotSpec {
+        PivotSpec {
+            dimensions: by.iter().map(|d| d.parse().unwrap()).collect(),
+            metric: Metric::Cost,
+            sort: SortOrder::Desc,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn nested_subtotals_sum_to_parent() {
+        let b = sample();
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let t = pivot(&[&b], &filter, &spec(&["subscription", "meter_category"]));
+        assert_eq!(t.total.cost, 23.0);
+        let prod = &t.total.children[0];
+        assert_eq!((prod.key.as_str(), prod.cost), ("prod", 20.0));
+        let keys: Vec<(&str, f64)> = prod
+            .children
+            .iter()
+            .map(|c| (c.key.as_str(), c.cost))
+            .collect();
+        assert_eq!(keys, vec![("Compute", 16.0), ("Storage", 4.0)]);
+        assert_eq!(t.total.children[1].children[0].cost, 3.0);
+
+        let mut limited = spec(&["subscription", "tag:team"]);
+        limited.limit = Some(1);
+        let t = pivot(&[&b], &filter, &limited);
+        assert_eq!(t.total.more, 1);
+        assert_eq!(t.total.children[0].more, 1);
+        assert_eq!(
+            t.total.children[0].cost, 20.0,
+            "subtotal includes dropped children"
+        );
+    }
+
+    #[test]
+    fn flat_rows_and_csv() {
+        let b = sample();
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let t = pivot(&[&b], &filter, &spec(&["subscription", "tag:team"]));
+        let rows = flatten(&t);
+        let lines: Vec<(Vec<&str>, bool, f64)> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.keys.iter().map(|k| k.as_str()).collect(),
+                    r.subtotal,
+                    r.cost,
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (vec!["prod", "tag:team=a"], false, 14.0),
+                (vec!["prod", "tag:team=b"], false, 6.0),
+                (vec!["prod", ""], true, 20.0),
+                (vec!["dev", "tag:none"], false, 3.0),
+                (vec!["dev", ""], true, 3.0),
+                (vec!["", ""], true, 23.0),
+            ]
+        );
+        let csv = pivot_to_csv(&t).unwrap();
+        assert!(
+            csv.starts_with("Subscription,tag:team,cost,cost_usd,quantity,rows,pct,subtotal\n")
+        );
+        assert!(
+            csv.contains("\nprod,,20.00,10.00,0.0000,3,87.0,true\n"),
+            "{csv}"
+        );
+    }
+}
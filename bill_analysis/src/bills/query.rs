@@ -0,0 +1,461 @@
+//! Query engine shared by the CLI (`query` subcommand) and the MCP server.
+//!
+//! A [`Query`] is a `BillFilter` (every filter dimension, `--where`, excludes,
+//! date range) plus group-by, metric, sort order and limit. [`run`] evaluates
+//! it over one or more monthly bills. The MCP `get_monthly_cost`,
+//! `search_resources` and `query_costs` tools and the CLI `query` subcommand
+//! all go through it, so the same arguments give the same rows everywhere.
+//!
+//! Group-by keys come from `CostType::key_of`, the same keys as the sections
+//! of the default CLI report; `resource` groups by resource name and resource
+//! group, `tag:KEY` by a tag's value, `none` returns only the totals.
+
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bills::Bills;
+use crate::bills::bill_entry::BillEntry;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::cost_query::extract_resource_type;
+use crate::bills::cost_type_enum::CostType;
+use crate::f64_to_currency;
+
+/// What rows are grouped by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupBy {
+    /// Totals only.
+    None,
+    /// Resource name + resource group.
+    Resource,
+    Dimension(CostType),
+    /// Tag value of a lowercase key, `tag:none` when missing.
+    Tag(String),
+}
+
+impl GroupBy {
+    pub(crate) fn key_of(&self, bill: &BillEntry) -> (String, String) {
+        match self {
+            GroupBy::None => (String::new(), String::new()),
+            GroupBy::Resource => (bill.resource_name.clone(), bill.resource_group.clone()),
+            GroupBy::Dimension(t) => (t.key_of(bill, ""), String::new()),
+            GroupBy::Tag(key) => (CostType::Tag.key_of(bill, key), String::new()),
+        }
+    }
+
+    /// [`GroupBy::key_of`] as one string, `name (rg)` for `resource`.
+    pub(crate) fn label_of(&self, bill: &BillEntry) -> String {
+        match self.key_of(bill) {
+            (key, rg) if rg.is_empty() => key,
+            (key, rg) => format!("{key} ({rg})"),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    /// `none`, `resource`, `tag:KEY` or a dimension name (`rg`, `subscription`, `meter_category`, ...).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("none") || s.is_empty() {
+            return Ok(GroupBy::None);
+        }
+        if s.eq_ignore_ascii_case("resource") {
+            return Ok(GroupBy::Resource);
+        }
+        if let Some((prefix, key)) = s.split_once(':')
+            && prefix.eq_ignore_ascii_case("tag")
+            && !key.trim().is_empty()
+        {
+            return Ok(GroupBy::Tag(key.trim().to_lowercase()));
+        }
+        match CostType::from_name(s) {
+            Some(CostType::Tag) => {
+                Err("Group by tag needs a key, e.g. 'tag:environment'".to_string())
+            }
+            Some(t) => Ok(GroupBy::Dimension(t)),
+            None => Err(format!(
+                "Unknown group-by '{s}', expected none, resource, tag:KEY or one of: {}",
+                CostType::ALL
+                    .iter()
+                    .filter(|t| **t != CostType::Tag)
+                    .map(|t| t.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupBy::None => write!(f, "none"),
+            GroupBy::Resource => write!(f, "resource"),
+            GroupBy::Dimension(t) => write!(f, "{}", t.as_str()),
+            GroupBy::Tag(key) => write!(f, "tag:{key}"),
+        }
+    }
+}
+
+/// The value rows are ranked by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Billing currency cost.
+    #[default]
+    Cost,
+    CostUsd,
+    Quantity,
+    Rows,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Largest metric first.
+    #[default]
+    Desc,
+    Asc,
+    /// By group key.
+    Key,
+}
+
+/// Filters, grouping and ranking of one query.
+#[derive(Debug)]
+pub struct Query {
+    pub filter: BillFilter,
+    pub group_by: GroupBy,
+    pub metric: Metric,
+    pub sort: SortOrder,
+    /// Rows returned after sorting; `None` returns all groups.
+    pub limit: Option<usize>,
+}
+
+/// One group of matching rows.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueryRow {
+    pub key: String,
+    /// Set when grouping by `resource`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub resource_group: String,
+    pub cost: f64,
+    pub cost_usd: f64,
+    pub quantity: f64,
+    pub rows: usize,
+    /// Context of the first matching row of the group.
+    pub subscription_name: String,
+    pub meter_category: String,
+    pub resource_type: String,
+}
+
+impl QueryRow {
+    pub fn value(&self, metric: Metric) -> f64 {
+        match metric {
+            Metric::Cost => self.cost,
+            Metric::CostUsd => self.cost_usd,
+            Metric::Quantity => self.quantity,
+            Metric::Rows => self.rows as f64,
+        }
+    }
+
+    pub(crate) fn add(&mut self, bill: &BillEntry) {
+        if self.rows == 0 {
+            self.subscription_name = bill.subscription_name.clone();
+            self.meter_category = bill.meter_category.clone();
+            self.resource_type = extract_resource_type(&bill.resource_id);
+        }
+        self.cost += bill.cost.amount();
+        self.cost_usd += bill.cost_usd.amount();
+        self.quantity += bill.quantity;
+        self.rows += 1;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryResult {
+    pub group_by: String,
+    pub metric: Metric,
+    pub total_cost: f64,
+    pub total_cost_usd: f64,
+    pub total_quantity: f64,
+    pub row_count: usize,
+    /// Groups before the limit.
+    pub total_groups: usize,
+    pub rows: Vec<QueryRow>,
+}
+
+/// Rows of `months` passing `filter`.
+pub fn select<'a>(
+    months: &'a [&'a Bills],
+    filter: &'a BillFilter,
+) -> impl Iterator<Item = &'a BillEntry> + 'a {
+    months
+        .iter()
+        .flat_map(|b| b.bills.iter())
+        .filter(move |b| filter.matches(b))
+}
+
+/// Evaluate `query` over `months` (one bill per month, oldest first).
+pub fn run(months: &[&Bills], query: &Query) -> QueryResult {
+    let mut total = QueryRow::default();
+    let mut groups: HashMap<(String, String), QueryRow> = HashMap::new();
+    for bill in select(months, &query.filter) {
+        total.add(bill);
+        if query.group_by != GroupBy::None {
+            groups
+                .entry(query.group_by.key_of(bill))
+                .or_default()
+                .add(bill);
+        }
+    }
+    let total_groups = groups.len();
+    let mut rows: Vec<QueryRow> = groups
+        .into_iter()
+        .map(|((key, resource_group), row)| QueryRow {
+            key,
+            resource_group,
+            ..row
+        })
+        .collect();
+    let by_key =
+        |a: &QueryRow, b: &QueryRow| (&a.key, &a.resource_group).cmp(&(&b.key, &b.resource_group));
+    let metric = query.metric;
+    rows.sort_by(|a, b| {
+        let (va, vb) = (a.value(metric), b.value(metric));
+        match query.sort {
+            SortOrder::Desc => vb.partial_cmp(&va).unwrap_or(std::cmp::Ordering::Equal),
+            SortOrder::Asc => va.partial_cmp(&vb).unwrap_or(std::cmp::Ordering::Equal),
+            SortOrder::Key => std::cmp::Ordering::Equal,
+        }
+        .then_with(|| by_key(a, b))
+    });
+    if let Some(limit) = query.limit {
+        rows.truncate(limit);
+    }
+    QueryResult {
+        group_by: query.group_by.to_string(),
+        metric,
+        total_cost: total.cost,
+        total_cost_usd: total.cost_usd,
+        total_quantity: total.quantity,
+        row_count: total.rows,
+        total_groups,
+        rows,
+    }
+}
+
+pub fn query_to_csv(result: &QueryResult) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "key",
+        "resource_group",
+        "cost",
+        "cost_usd",
+        "quantity",
+        "rows",
+        "pct",
+    ])?;
+    for r in &result.rows {
+        wtr.write_record([
+            r.key.as_str(),
+            r.resource_group.as_str(),
+            &format!("{:.2}", r.cost),
+            &format!("{:.2}", r.cost_usd),
+            &format!("{:.4}", r.quantity),
+            &r.rows.to_string(),
+            &format!("{:.1}", pct(r.cost, result.total_cost)),
+        ])?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+pub(crate) fn pct(part: f64, total: f64) -> f64 {
+    if total != 0.0 {
+        part / total * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Print the groups and totals as coloured terminal text.
+pub fn print_query(result: &QueryResult, cur: &str) {
+    println!(
+        "{:>16} {:>14} {:>7} {:>7}  {}",
+        "cost", "USD", "pct", "rows", result.group_by
+    );
+    for r in &result.rows {
+        let key = if r.resource_group.is_empty() {
+            format!("'{}'", r.key)
+        } else {
+            format!("'{}' rg:'{}'", r.key, r.resource_group)
+        };
+        println!(
+            "{:>16} {:>14} {:>7} {:>7}  {}",
+            format!("{cur} {}", f64_to_currency(r.cost, 2)).yellow(),
+            format!("US$ {}", f64_to_currency(r.cost_usd, 2)),
+            format!("{:.1}%", pct(r.cost, result.total_cost)),
+            r.rows,
+            key,
+        );
+    }
+    if result.total_groups > result.rows.len() {
+        println!(
+            "  ... {} more groups",
+            result.total_groups - result.rows.len()
+        );
+    }
+    println!(
+        "Total {cur} {} (US$ {}) over {} rows, ranked by {:?}",
+        f64_to_currency(result.total_cost, 2).bold(),
+        f64_to_currency(result.total_cost_usd, 2),
+        result.row_count,
+        result.metric,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    fn sample() -> Bills {
+        bills(vec![
+            row()
+                .name("vm-1")
+                .rg("rg-a")
+                .location("eastus")
+                .tags(r#""env": "prod""#)
+                .quantity(1.0)
+                .cost(10.0),
+            row()
+                .name("vm-1")
+                .rg("rg-a")
+                .location("eastus")
+                .tags(r#""env": "prod""#)
+                .quantity(1.0)
+                .cost(5.0),
+            row().name("vm-1").rg("rg-b").quantity(1.0).cost(20.0),
+            row()
+                .name("disk")
+                .rg("rg-b")
+                .location("westus")
+                .tags(r#""env": "dev""#)
+                .quantity(1.0)
+                .cost(1.0),
+        ])
+    }
+
+    fn query(group_by: &str) -> Query {
+        Query {
+            filter: BillFilter::match_all(&FilterOpts::default()),
+            group_by: group_by.parse().unwrap(),
+            metric: Metric::Cost,
+            sort: SortOrder::Desc,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn group_by_parsing() {
+        assert_eq!(
+            "rg".parse(),
+            Ok(GroupBy::Dimension(CostType::ResourceGroup))
+        );
+        assert_eq!(
+            "meter-category".parse(),
+            Ok(GroupBy::Dimension(CostType::MeterCategory))
+        );
+        assert_eq!("location".parse(), Ok(GroupBy::Dimension(CostType::Region)));
+        assert_eq!("tag:Env".parse(), Ok(GroupBy::Tag("env".to_string())));
+        assert_eq!(
+            "resource_type".parse(),
+            Ok(GroupBy::Dimension(CostType::ResourceType))
+        );
+        assert_eq!(
+            "pricing-model".parse(),
+            Ok(GroupBy::Dimension(CostType::PricingModel))
+        );
+        assert_eq!(
+            "publisher".parse(),
+            Ok(GroupBy::Dimension(CostType::Publisher))
+        );
+        assert_eq!(
+            "resource".parse::<GroupBy>().unwrap().to_string(),
+            "resource"
+        );
+        assert!(
+            "tag"
+                .parse::<GroupBy>()
+                .unwrap_err()
+                .contains("needs a key")
+        );
+        assert!(
+            "colour"
+                .parse::<GroupBy>()
+                .unwrap_err()
+                .contains("Unknown group-by")
+        );
+    }
+
+    #[test]
+    fn groups_use_report_keys_and_rank() {
+        let b = sample();
+        let r = run(&[&b], &query("location"));
+        let keys: Vec<&str> = r.rows.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, vec!["none", "eastus", "westus"]);
+        assert_eq!(r.total_cost, 36.0);
+        assert_eq!(r.row_count, 4);
+
+        let r = run(&[&b], &query("resource"));
+        assert_eq!(r.total_groups, 3);
+        assert_eq!(
+            (r.rows[0].key.as_str(), r.rows[0].resource_group.as_str()),
+            ("vm-1", "rg-b")
+        );
+
+        let mut q = query("tag:env");
+        q.metric = Metric::Rows;
+        q.limit = Some(2);
+        let r = run(&[&b], &q);
+        assert_eq!(r.rows[0].key, "tag:env=prod");
+        assert_eq!(r.rows[0].rows, 2);
+        assert_eq!(r.rows.len(), 2);
+        assert_eq!(r.total_groups, 3);
+
+        q.sort = SortOrder::Key;
+        q.limit = None;
+        let r = run(&[&b], &q);
+        let keys: Vec<&str> = r.rows.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, vec!["tag:env=dev", "tag:env=prod", "tag:none"]);
+    }
+
+    #[test]
+    fn matches_cli_report_totals() {
+        let b = sample();
+        let mut q = query("rg");
+        q.filter = BillFilter::match_all(&FilterOpts::default()).with_excludes(vec![
+            crate::bills::bill_filter::Exclude::new(
+                crate::bills::bill_filter::FilterDimension::Name,
+                "disk",
+                false,
+            )
+            .unwrap(),
+        ]);
+        let r = run(&[&b], &q);
+        let summary = b.cost_by_any_summary(&q.filter, &[CostType::ResourceGroup]);
+        for row in &r.rows {
+            let total = &summary.per_type[&(CostType::ResourceGroup, row.key.clone())];
+            assert_eq!(total.cost.amount(), row.cost, "{}", row.key);
+        }
+        assert_eq!(r.total_cost, 35.0);
+        let csv = query_to_csv(&r).unwrap();
+        assert!(csv.contains("\nrg-b,,20.00,10.00,1.0000,1,57.1\n"), "{csv}");
+    }
+}
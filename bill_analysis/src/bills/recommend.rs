@@ -0,0 +1,397 @@
+//! Offline reservation purchase recommender — works from local bill CSVs only.
+//!
+//! `reservation_plan/src/reservation_recommend.rs` asks the Consumption API;
+//! this module derives the same kind of advice from bill history:
+//!
+//! 1. On-demand hours of reservation-eligible meters are summed per SKU
+//!    (meter name) and region per day, and divided by 24 to give the average
+//!    number of instances running that day. Days without usage count as 0.
+//! 2. Buying one more reservation pays off when it is used more than the
+//!    break-even utilisation `1 - discount` of the time. The recommended
+//!    quantity per term is therefore the largest `q` for which more than
+//!    `1 - discount` of the days ran at least `q` instances.
+//! 3. Projected savings are per 730 h month at the SKU's on-demand hourly cost.
+
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::bills::BillFilter;
+use crate::bills::Bills;
+use crate::bills::bills_sum_data::reservation_flex_group;
+use crate::f64_to_currency;
+
+/// Hours in an average month, as used by Azure pricing.
+const HOURS_PER_MONTH: f64 = 730.0;
+
+/// Assumed reservation discount off the on-demand price per term, `0.0..1.0`.
+#[derive(Debug, Clone, Copy)]
+pub struct TermDiscounts {
+    pub one_year: f64,
+    pub three_year: f64,
+}
+
+impl Default for TermDiscounts {
+    /// Typical Azure VM discounts; override per workload from the CLI.
+    fn default() -> Self {
+        Self {
+            one_year: 0.35,
+            three_year: 0.55,
+        }
+    }
+}
+
+/// Recommendation for one term, see [`Recommendation`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TermAdvice {
+    /// Reserved instances to buy; `0` means reserving does not pay off.
+    pub quantity: u32,
+    /// Expected share of reserved hours used, `0..=100`.
+    pub utilisation_pct: f64,
+    /// Utilisation below which the reservation costs more than on-demand, `0..=100`.
+    pub break_even_pct: f64,
+    /// Projected saving per month in the billing currency (NZD).
+    pub monthly_savings: f64,
+}
+
+/// Purchase advice for one SKU in one region.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recommendation {
+    pub flex_group: String,
+    /// Meter name, e.g. `"D4s v5"`.
+    pub sku: String,
+    pub region: String,
+    /// Days in the look-back period.
+    pub days: usize,
+    /// Mean on-demand instances per day.
+    pub avg_instances: f64,
+    /// Fewest on-demand instances on any day.
+    pub min_instances: f64,
+    /// On-demand cost per instance hour in the billing currency (NZD).
+    pub hourly_cost: f64,
+    pub hourly_cost_usd: f64,
+    /// Discount seen on existing reserved usage of this SKU (`1 - effective/unit`), if any.
+    pub observed_discount_pct: Option<f64>,
+    pub one_year: TermAdvice,
+    pub three_year: TermAdvice,
+}
+
+#[derive(Default)]
+struct SkuUsage {
+    flex_group: String,
+    hours_per_day: HashMap<String, f64>,
+    on_demand_hours: f64,
+    on_demand_cost: f64,
+    on_demand_cost_usd: f64,
+    reserved_unit: f64,
+    reserved_effective: f64,
+}
+
+fn advise(counts: &[f64], hourly_cost: f64, discount: f64) -> TermAdvice {
+    let break_even = 1.0 - discount;
+    let days = counts.len() as f64;
+    let max = counts.iter().copied().fold(0.0, f64::max).floor() as u32;
+    // Largest q where the q-th instance runs on more than break-even of the days.
+    let quantity = (1..=max)
+        .rev()
+        .find(|q| counts.iter().filter(|c| **c >= f64::from(*q)).count() as f64 / days > break_even)
+        .unwrap_or(0);
+    let (utilisation_pct, monthly_savings) = if quantity == 0 {
+        (0.0, 0.0)
+    } else {
+        let q = f64::from(quantity);
+        let used = counts.iter().map(|c| c.min(q)).sum::<f64>() / days;
+        (
+            used / q * 100.0,
+            (used - q * break_even) * HOURS_PER_MONTH * hourly_cost,
+        )
+    };
+    TermAdvice {
+        quantity,
+        utilisation_pct,
+        break_even_pct: break_even * 100.0,
+        monthly_savings,
+    }
+}
+
+/// Recommend reservation purchases from the on-demand usage in `months`
+/// (typically the last N monthly bills), restricted to rows matching `filter`.
+/// Sorted by projected 3-year monthly savings, largest first.
+pub fn recommend(
+    months: &[&Bills],
+    filter: &BillFilter,
+    discounts: TermDiscounts,
+) -> Vec<Recommendation> {
+    let mut all_days: BTreeSet<&str> = BTreeSet::new();
+    let mut usage: HashMap<(String, String), SkuUsage> = HashMap::new();
+    for bills in months {
+        for bill in &bills.bills {
+            all_days.insert(&bill.date);
+            if !filter.matches(bill) || bill.charge_type != "Usage" {
+                continue;
+            }
+            let Some(flex_group) = reservation_flex_group(bill) else {
+                continue;
+            };
+            let region = if bill.resource_location.is_empty() {
+                "none".to_string()
+            } else {
+                bill.resource_location.to_lowercase()
+            };
+            let u = usage
+                .entry((bill.meter_name.clone(), region))
+                .or_insert_with(|| SkuUsage {
+                    flex_group,
+                    ..SkuUsage::default()
+                });
+            match bill.pricing_model.as_str() {
+                "OnDemand" | "" => {
+                    *u.hours_per_day.entry(bill.date.clone()).or_default() += bill.quantity;
+                    u.on_demand_hours += bill.quantity;
+                    u.on_demand_cost += bill.cost.amount();
+                    u.on_demand_cost_usd += bill.cost_usd.amount();
+                }
+                "Reservation" => {
+                    u.reserved_unit += bill.unit_price * bill.quantity;
+                    u.reserved_effective += bill.effective_price * bill.quantity;
+                }
+                _ => {} // savings plan / spot usage is not a reservation candidate
+            }
+        }
+    }
+
+    let mut recs: Vec<Recommendation> = usage
+        .into_iter()
+        .filter(|(_, u)| u.on_demand_hours > 0.0)
+        .map(|((sku, region), u)| {
+            let counts: Vec<f64> = all_days
+                .iter()
+                .map(|d| u.hours_per_day.get(*d).copied().unwrap_or(0.0) / 24.0)
+                .collect();
+            let hourly_cost = u.on_demand_cost / u.on_demand_hours;
+            Recommendation {
+                flex_group: u.flex_group,
+                sku,
+                region,
+                days: counts.len(),
+                avg_instances: counts.iter().sum::<f64>() / counts.len() as f64,
+                min_instances: counts.iter().copied().fold(f64::INFINITY, f64::min),
+                hourly_cost,
+                hourly_cost_usd: u.on_demand_cost_usd / u.on_demand_hours,
+                observed_discount_pct: (u.reserved_unit > 0.0)
+                    .then(|| (1.0 - u.reserved_effective / u.reserved_unit) * 100.0),
+                one_year: advise(&counts, hourly_cost, discounts.one_year),
+                three_year: advise(&counts, hourly_cost, discounts.three_year),
+            }
+        })
+        .collect();
+    recs.sort_by(|a, b| {
+        b.three_year
+            .monthly_savings
+            .partial_cmp(&a.three_year.monthly_savings)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.sku.cmp(&b.sku))
+            .then_with(|| a.region.cmp(&b.region))
+    });
+    recs
+}
+
+/// Render recommendations as flat CSV, one line per SKU and region.
+pub fn recommend_to_csv(recs: &[Recommendation]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "flex_group",
+        "sku",
+        "region",
+        "days",
+        "avg_instances",
+        "min_instances",
+        "hourly_cost",
+        "hourly_cost_usd",
+        "observed_discount_pct",
+        "qty_1y",
+        "utilisation_1y_pct",
+        "break_even_1y_pct",
+        "monthly_savings_1y",
+        "qty_3y",
+        "utilisation_3y_pct",
+        "break_even_3y_pct",
+        "monthly_savings_3y",
+    ])?;
+    for r in recs {
+        wtr.write_record([
+            r.flex_group.clone(),
+            r.sku.clone(),
+            r.region.clone(),
+            r.days.to_string(),
+            format!("{:.2}", r.avg_instances),
+            format!("{:.2}", r.min_instances),
+            format!("{:.4}", r.hourly_cost),
+            format!("{:.4}", r.hourly_cost_usd),
+            r.observed_discount_pct
+                .map(|p| format!("{p:.1}"))
+                .unwrap_or_default(),
+            r.one_year.quantity.to_string(),
+            format!("{:.1}", r.one_year.utilisation_pct),
+            format!("{:.1}", r.one_year.break_even_pct),
+            format!("{:.2}", r.one_year.monthly_savings),
+            r.three_year.quantity.to_string(),
+            format!("{:.1}", r.three_year.utilisation_pct),
+            format!("{:.1}", r.three_year.break_even_pct),
+            format!("{:.2}", r.three_year.monthly_savings),
+        ])?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// Print recommendations as a coloured terminal table.
+pub fn print_recommendations(recs: &[Recommendation], cur: &str) {
+    println!(
+        "{:>7} {:>7} {:>10} | {:>4} {:>6} {:>12} | {:>4} {:>6} {:>12} | {:<16} sku",
+        "avg", "min", "hourly", "1y", "util", "save/month", "3y", "util", "save/month", "region"
+    );
+    for r in recs {
+        println!(
+            "{:>7.2} {:>7.2} {:>10} | {:>4} {:>6} {:>12} | {:>4} {:>6} {:>12} | {:<16} '{}'",
+            r.avg_instances,
+            r.min_instances,
+            f64_to_currency(r.hourly_cost, 4),
+            r.one_year.quantity.to_string().bold(),
+            format!("{:.0}%", r.one_year.utilisation_pct),
+            f64_to_currency(r.one_year.monthly_savings, 2).green(),
+            r.three_year.quantity.to_string().bold(),
+            format!("{:.0}%", r.three_year.utilisation_pct),
+            f64_to_currency(r.three_year.monthly_savings, 2).green(),
+            r.region,
+            r.sku,
+        );
+    }
+    let (save_1y, save_3y) = recs.iter().fold((0.0, 0.0), |acc, r| {
+        (
+            acc.0 + r.one_year.monthly_savings,
+            acc.1 + r.three_year.monthly_savings,
+        )
+    });
+    if let Some(r) = recs.first() {
+        println!(
+            "Break-even utilisation 1y {:.0}% 3y {:.0}%",
+            r.one_year.break_even_pct, r.three_year.break_even_pct
+        );
+    }
+    println!(
+        "Projected savings per month {cur}: 1y {} 3y {}",
+        f64_to_currency(save_1y, 2).green().bold(),
+        f64_to_currency(save_3y, 2).green().bold(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::bill_entry::BillEntry;
+    use crate::cmd_parse::FilterOpts;
+    use crate::money::{Nzd, Usd};
+
+    fn vm_day(day: u32, instances: f64) -> BillEntry {
+        BillEntry {
+            date: format!("2026-01-{day:02}"),
+            meter_category: "Virtual Machines".to_string(),
+            meter_sub_category: "Dv5 Series".to_string(),
+            meter_name: "D4s v5".to_string(),
+            resource_location: "AustraliaEast".to_string(),
+            charge_type: "Usage".to_string(),
+            pricing_model: "OnDemand".to_string(),
+            unit_price: 0.2,
+            effective_price: 0.2,
+            quantity: instances * 24.0,
+            cost: Nzd(instances * 24.0 * 0.3),
+            cost_usd: Usd(instances * 24.0 * 0.2),
+            ..BillEntry::default()
+        }
+    }
+
+    fn no_filter() -> BillFilter {
+        BillFilter::match_all(&FilterOpts::default())
+    }
+
+    #[test]
+    fn quantity_follows_break_even_per_term() {
+        // 10 days: 2 instances always, a 3rd on 6 days, a 4th on 3 days.
+        let mut bills = Bills::default();
+        for day in 1..=10 {
+            let n = 2.0 + f64::from(u8::from(day <= 6)) + f64::from(u8::from(day <= 3));
+            bills.push(vm_day(day, n));
+        }
+        let recs = recommend(&[&bills], &no_filter(), TermDiscounts::default());
+        assert_eq!(recs.len(), 1);
+        let r = &recs[0];
+        assert_eq!(r.region, "australiaeast");
+        assert_eq!(r.days, 10);
+        assert_eq!(r.min_instances, 2.0);
+        assert!((r.hourly_cost - 0.3).abs() < 1e-9);
+        // 1y break-even 65%: 3rd instance runs 60% of days → not worth it.
+        assert_eq!(r.one_year.quantity, 2);
+        assert_eq!(r.one_year.utilisation_pct, 100.0);
+        assert!((r.one_year.monthly_savings - 2.0 * 0.35 * 730.0 * 0.3).abs() < 1e-9);
+        // 3y break-even 45%: 3rd instance (60%) pays off, 4th (30%) does not.
+        assert_eq!(r.three_year.quantity, 3);
+        assert!((r.three_year.utilisation_pct - 2.6 / 3.0 * 100.0).abs() < 1e-9);
+        assert!((r.three_year.break_even_pct - 45.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn days_without_usage_count_as_zero() {
+        let mut bills = Bills::default();
+        bills.push(vm_day(1, 1.0));
+        // another meter keeps day 2 in the look-back period
+        bills.push(BillEntry {
+            date: "2026-01-02".to_string(),
+            meter_category: "Storage".to_string(),
+            ..BillEntry::default()
+        });
+        let recs = recommend(&[&bills], &no_filter(), TermDiscounts::default());
+        assert_eq!(recs[0].days, 2);
+        assert_eq!(recs[0].min_instances, 0.0);
+        // used 50% of the time < 65% break-even
+        assert_eq!(recs[0].one_year.quantity, 0);
+        assert_eq!(recs[0].three_year.quantity, 1);
+    }
+
+    #[test]
+    fn reserved_usage_gives_observed_discount_not_candidates() {
+        let mut bills = Bills::default();
+        bills.push(vm_day(1, 1.0));
+        let mut reserved = vm_day(1, 2.0);
+        reserved.pricing_model = "Reservation".to_string();
+        reserved.effective_price = 0.12;
+        bills.push(reserved);
+        let recs = recommend(&[&bills], &no_filter(), TermDiscounts::default());
+        assert_eq!(recs[0].avg_instances, 1.0);
+        assert!((recs[0].observed_discount_pct.unwrap() - 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recommend_over_test_months() {
+        let mut months = Vec::new();
+        for path in [
+            "tests/azure_test_nzd_usd_prev.csv",
+            "tests/azure_test_nzd_usd_latest.csv",
+        ] {
+            let mut bills = Bills::default();
+            bills
+                .parse_csv(&std::path::PathBuf::from(path), &FilterOpts::default())
+                .expect("parse failed");
+            months.push(bills);
+        }
+        let refs: Vec<&Bills> = months.iter().collect();
+        let recs = recommend(&refs, &no_filter(), TermDiscounts::default());
+        assert_eq!(recs.len(), 1);
+        // two VMs × 744 h billed on the 1st of each month
+        assert_eq!(recs[0].days, 2);
+        assert_eq!(recs[0].avg_instances, 62.0);
+        assert_eq!(recs[0].one_year.quantity, 62);
+        let csv = recommend_to_csv(&recs).unwrap();
+        assert!(csv.starts_with("flex_group,sku,region,days,"));
+        assert_eq!(csv.lines().count(), 2);
+    }
+}
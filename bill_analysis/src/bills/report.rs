@@ -0,0 +1,524 @@
+//! Machine-readable form of the default CLI report (`--output json|csv|markdown`).
+//!
+//! [`build_report`] and `display::display_cost_by_filter` both walk [`section_types`]
+//! (location, subscription, resource group, tag, ...) and build each section from a
+//! `DisplayPlan`, so the rows, shares and `--top`/`--cost-min-display` handling match
+//! the text report.
+//! Only the rows the text report would show are included; `skipped_count` says how
+//! many fell below the display threshold.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::bills_sum_data::CostSource;
+use crate::bills::cost_type_enum::CostType;
+use crate::bills::display::build_display_plan;
+use crate::cmd_parse::DisplayOpts;
+
+/// One row of a report section.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportRow {
+    pub name: String,
+    pub cost: f64,
+    /// Share of the section total, in percent; `None` in a comparison report,
+    /// where the costs are changes and a share is meaningless.
+    pub pct: Option<f64>,
+    /// Running share from the largest row down to this one, in percent; `None`
+    /// in a comparison report.
+    pub cum_pct: Option<f64>,
+    /// `original`, `secondary` (previous bill only) or `combined` (both bills).
+    pub source: &'static str,
+    /// Rows folded into an "other (N items)" row by `--top`; 0 for a normal row.
+    pub other_count: usize,
+}
+
+/// One section of the report, e.g. the resource group breakdown.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportSection {
+    /// `CostType::as_str` of the section, e.g. "ResourceGroup".
+    pub dimension: &'static str,
+    /// Filter pattern for this dimension, empty when unset.
+    pub pattern: String,
+    pub total: f64,
+    pub total_usd: f64,
+    /// Rows below `--cost-min-display`, not listed in `rows`.
+    pub skipped_count: usize,
+    /// Largest cost first.
+    pub rows: Vec<ReportRow>,
+}
+
+/// Month total of one reservation flex group, as in the text report's
+/// "Month Total" line; costs in the pricing currency (USD).
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportReservation {
+    /// Flex group key, e.g. "MC:Virtual Machines__MSubC:Dav4/Dasv4 Series".
+    pub key: String,
+    pub cost_savings: f64,
+    pub cost_unused: f64,
+    pub cost_full: f64,
+    pub saving_pct: f64,
+    pub hr_total: f64,
+    pub hr_reserved: f64,
+    pub hr_unused: f64,
+    /// Reserved hours used / purchased; `None` when nothing was reserved.
+    pub utilisation_pct: Option<f64>,
+}
+
+/// The default report: bill(s), filter metadata, totals and every section.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub bill: String,
+    /// Set when a previous bill was subtracted.
+    pub previous_bill: Option<String>,
+    /// Billing currency code from the bill, e.g. "NZD".
+    pub currency: String,
+    /// Non-empty filter settings, keyed by CLI flag name.
+    pub filter: BTreeMap<String, String>,
+    pub total_cost: f64,
+    pub total_cost_usd: f64,
+    pub sections: Vec<ReportSection>,
+    /// Reservation utilisation of the latest bill per flex group, only with `--reservation`.
+    pub reservations: Vec<ReportReservation>,
+}
+
+fn source_str(source: CostSource) -> &'static str {
+    match source {
+        CostSource::Original => "original",
+        CostSource::Secondary => "secondary",
+        CostSource::Combined => "combined",
+    }
+}
+
+/// Sections of the text and serialised reports, in order, with the filter pattern of each.
+/// Location, subscription and resource group are always present; the rest only
+/// when their filter is set.
+pub(crate) fn section_types(filter: &BillFilter) -> Vec<(CostType, String)> {
+    let mut sections = vec![(CostType::Region, filter.location.clone())];
+    if !filter.invoice_section.is_empty() {
+        sections.push((CostType::InvoiceSection, filter.invoice_section.clone()));
+    }
+    sections.push((CostType::Subscription, filter.subscription.clone()));
+    sections.push((CostType::ResourceGroup, filter.resource_group.clone()));
+    if !filter.name.is_empty() {
+        sections.push((CostType::ResourceName, filter.name.clone()));
+    }
+    if !filter.meter_category.is_empty() {
+        sections.push((CostType::MeterSubCategory, filter.meter_category.clone()));
+        sections.push((CostType::MeterCategory, filter.meter_category.clone()));
+    }
+    for (cost_type, pattern) in [
+        (CostType::ResourceType, &filter.resource_type),
+        (CostType::MeterName, &filter.meter_name),
+        (CostType::ChargeType, &filter.charge_type),
+        (CostType::PricingModel, &filter.pricing_model),
+        (CostType::Publisher, &filter.publisher),
+    ] {
+        if !pattern.is_empty() {
+            sections.push((cost_type, pattern.clone()));
+        }
+    }
+    if !filter.tag_summarise.is_empty() {
+        sections.push((CostType::Tag, filter.tag_summarise.clone()));
+    }
+    if !filter.reservation.is_empty() {
+        sections.push((CostType::Reservation, filter.reservation.clone()));
+    }
+    sections
+}
+
+/// The `CostType`s of [`section_types`], for `Bills::cost_by_any_summary`.
+pub(crate) fn section_cost_types(filter: &BillFilter) -> Vec<CostType> {
+    section_types(filter).into_iter().map(|s| s.0).collect()
+}
+
+fn filter_metadata(filter: &BillFilter) -> BTreeMap<String, String> {
+    let tags = filter
+        .tag_predicates
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let excludes = filter
+        .excludes
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let dates = if filter.date_range.is_set() {
+        filter.date_range.to_string()
+    } else {
+        String::new()
+    };
+    let where_expr = filter
+        .where_expr
+        .as_ref()
+        .map(|w| w.to_string())
+        .unwrap_or_default();
+    [
+        ("name-regex", filter.name.clone()),
+        ("resource-group", filter.resource_group.clone()),
+        ("subscription", filter.subscription.clone()),
+        ("meter-category", filter.meter_category.clone()),
+        ("location", filter.location.clone()),
+        ("reservation", filter.reservation.clone()),
+        ("tag-filter", filter.tag_filter.clone()),
+        ("tag", tags),
+        ("tag-summarise", filter.tag_summarise.clone()),
+        ("invoice-section", filter.invoice_section.clone()),
+        ("resource-type", filter.resource_type.clone()),
+        ("meter-name", filter.meter_name.clone()),
+        ("charge-type", filter.charge_type.clone()),
+        ("pricing-model", filter.pricing_model.clone()),
+        ("publisher", filter.publisher.clone()),
+        ("dates", dates),
+        ("exclude", excludes),
+        ("where", where_expr),
+    ]
+    .into_iter()
+    .filter(|(_, v)| !v.is_empty())
+    .map(|(k, v)| (k.to_string(), v))
+    .collect()
+}
+
+/// Build the report for `latest` (minus `previous` when given) under `filter`.
+pub fn build_report(
+    filter: &BillFilter,
+    latest: &Bills,
+    previous: Option<&Bills>,
+    display_opts: &DisplayOpts,
+) -> Report {
+    let cost_types = section_cost_types(filter);
+    let mut summary = latest.cost_by_any_summary(filter, &cost_types);
+    if let Some(prev) = previous {
+        summary.merge_summaries(&prev.cost_by_any_summary(filter, &cost_types));
+    }
+    let sections = section_types(filter)
+        .into_iter()
+        .map(|(cost_type, pattern)| {
+            let plan = build_display_plan(&summary, cost_type, display_opts);
+            ReportSection {
+                dimension: cost_type.as_str(),
+                pattern,
+                total: plan.total,
+                total_usd: plan.total_usd,
+                skipped_count: plan.skipped_count,
+                rows: plan
+                    .rows
+                    .iter()
+                    .rev()
+                    .filter(|r| r.visible)
+                    .map(|r| ReportRow {
+                        name: r.name.clone(),
+                        cost: r.cost,
+                        pct: previous.is_none().then_some(r.pct),
+                        cum_pct: previous.is_none().then_some(r.cum_pct),
+                        source: source_str(r.source),
+                        other_count: r.other_count,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    let reservations = if filter.reservation.is_empty() {
+        Vec::new()
+    } else {
+        summary
+            .reservation_month_totals()
+            .into_iter()
+            .map(|(key, m)| ReportReservation {
+                key,
+                cost_savings: m.cost_savings,
+                cost_unused: m.cost_unused,
+                cost_full: m.cost_full,
+                saving_pct: m.saving_pct(),
+                hr_total: m.hr_total,
+                hr_reserved: m.hr_reserved,
+                hr_unused: m.hr_unused,
+                utilisation_pct: m.utilisation_pct(),
+            })
+            .collect()
+    };
+    Report {
+        bill: latest.file_short_name.clone(),
+        previous_bill: previous.map(|p| p.file_short_name.clone()),
+        currency: latest.billing_currency.clone().unwrap_or_default(),
+        filter: filter_metadata(filter),
+        total_cost: summary.filtered_cost_total.amount(),
+        total_cost_usd: summary.filtered_cost_total_usd.amount(),
+        sections,
+        reservations,
+    }
+}
+
+/// `{:.1}` of a share, empty when it is `None`.
+fn pct_str(pct: Option<f64>) -> String {
+    pct.map(|p| format!("{p:.1}")).unwrap_or_default()
+}
+
+/// Flatten the report to one CSV row per section row; the report total is a
+/// final `Total` row. Each reservation flex group is a `ReservationUtilisation`
+/// row with its savings (USD) as `cost` and its utilisation as `pct`.
+pub fn report_to_csv(report: &Report) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "section",
+        "name",
+        "cost",
+        "pct",
+        "cum_pct",
+        "source",
+        "other_count",
+    ])?;
+    for s in &report.sections {
+        for r in &s.rows {
+            wtr.write_record([
+                s.dimension,
+                r.name.as_str(),
+                &format!("{:.2}", r.cost),
+                &pct_str(r.pct),
+                &pct_str(r.cum_pct),
+                r.source,
+                &r.other_count.to_string(),
+            ])?;
+        }
+    }
+    for r in &report.reservations {
+        wtr.write_record([
+            "ReservationUtilisation",
+            r.key.as_str(),
+            &format!("{:.2}", r.cost_savings),
+            &pct_str(r.utilisation_pct),
+            "",
+            "",
+            "",
+        ])?;
+    }
+    let total_pct = if report.previous_bill.is_none() {
+        "100.0"
+    } else {
+        ""
+    };
+    wtr.write_record([
+        "Total",
+        "",
+        &format!("{:.2}", report.total_cost),
+        total_pct,
+        total_pct,
+        "",
+        "",
+    ])?;
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// Render the report as Markdown: a filter list, then one table per section.
+pub fn report_to_markdown(report: &Report) -> String {
+    let cur = &report.currency;
+    let mut out = String::new();
+    let bills = match &report.previous_bill {
+        Some(prev) => format!("{} - {prev}", report.bill),
+        None => report.bill.clone(),
+    };
+    let _ = writeln!(out, "# Azure cost report '{bills}'\n");
+    let _ = writeln!(
+        out,
+        "Total cost excl. GST: {cur} {:.2} (US$ {:.2})\n",
+        report.total_cost, report.total_cost_usd
+    );
+    if !report.filter.is_empty() {
+        for (k, v) in &report.filter {
+            let _ = writeln!(out, "- {k}: `{v}`");
+        }
+        out.push('\n');
+    }
+    for s in &report.sections {
+        let _ = writeln!(out, "## {} {}\n", s.dimension, s.pattern);
+        let _ = writeln!(out, "| Name | Cost ({cur}) | % | Cum % |");
+        let _ = writeln!(out, "|---|---:|---:|---:|");
+        for r in &s.rows {
+            let _ = writeln!(
+                out,
+                "| {} | {:.2} | {} | {} |",
+                r.name.replace('|', "\\|"),
+                r.cost,
+                pct_str(r.pct),
+                pct_str(r.cum_pct)
+            );
+        }
+        let _ = writeln!(out, "| **Total** | **{:.2}** | | |", s.total);
+        if s.skipped_count > 0 {
+            let _ = writeln!(
+                out,
+                "\n{} rows below the display threshold.",
+                s.skipped_count
+            );
+        }
+        out.push('\n');
+    }
+    if !report.reservations.is_empty() {
+        let _ = writeln!(out, "## Reservation utilisation (US$)\n");
+        let _ = writeln!(
+            out,
+            "| Key | Save | Unused | Full cost | Saving % | Hours | Reserved | Utilisation % |"
+        );
+        let _ = writeln!(out, "|---|---:|---:|---:|---:|---:|---:|---:|");
+        for r in &report.reservations {
+            let _ = writeln!(
+                out,
+                "| {} | {:.2} | {:.2} | {:.2} | {:.0} | {:.0} | {:.0} | {} |",
+                r.key.replace('|', "\\|"),
+                r.cost_savings,
+                r.cost_unused,
+                r.cost_full,
+                r.saving_pct,
+                r.hr_total,
+                r.hr_reserved,
+                r.utilisation_pct
+                    .map(|p| format!("{p:.0}"))
+                    .unwrap_or_else(|| "n/a".to_string()),
+            );
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::bill_entry::BillEntry;
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    fn opts() -> DisplayOpts {
+        DisplayOpts {
+            cost_min_display: 0.0,
+            tag_list: false,
+            debug: false,
+            top: None,
+        }
+    }
+
+    #[test]
+    fn sections_follow_filter() {
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let kinds = section_cost_types(&filter);
+        assert_eq!(
+            kinds,
+            vec![
+                CostType::Region,
+                CostType::Subscription,
+                CostType::ResourceGroup
+            ]
+        );
+        let filter = filter
+            .with_pattern(
+                crate::bills::bill_filter::FilterDimension::Publisher,
+                "Micro",
+            )
+            .unwrap();
+        let kinds = section_cost_types(&filter);
+        assert_eq!(kinds.last(), Some(&CostType::Publisher));
+    }
+
+    #[test]
+    fn report_rows_largest_first_with_totals() {
+        let latest = Bills {
+            file_short_name: "2026-01".to_string(),
+            ..bills(vec![
+                row().rg("rg-a").location("eastus").cost(30.0),
+                row().rg("rg-b").location("eastus").cost(10.0),
+                row().rg("rg-a").location("westus").cost(60.0),
+            ])
+        };
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let report = build_report(&filter, &latest, None, &opts());
+        assert_eq!(report.total_cost, 100.0);
+        assert_eq!(report.currency, "NZD");
+        let rg = &report.sections[2];
+        assert_eq!(rg.dimension, CostType::ResourceGroup.as_str());
+        let rows: Vec<(&str, f64)> = rg.rows.iter().map(|r| (r.name.as_str(), r.cost)).collect();
+        assert_eq!(rows, vec![("rg-a", 90.0), ("rg-b", 10.0)]);
+        assert_eq!(rg.rows[0].pct, Some(90.0));
+        assert_eq!(rg.rows[1].cum_pct, Some(100.0));
+        assert!(report.reservations.is_empty());
+
+        let csv = report_to_csv(&report).unwrap();
+        assert!(csv.contains("ResourceGroup,rg-a,90.00,90.0,90.0,original,0"));
+        assert!(csv.ends_with("Total,,100.00,100.0,100.0,,\n"));
+        let md = report_to_markdown(&report);
+        assert!(md.contains("| rg-a | 90.00 | 90.0 | 90.0 |"));
+    }
+
+    #[test]
+    fn comparison_report_names_previous_bill() {
+        let latest = Bills {
+            file_short_name: "2026-01".to_string(),
+            ..bills(vec![row().rg("rg-a").location("eastus").cost(50.0)])
+        };
+        let previous = Bills {
+            file_short_name: "2025-12".to_string(),
+            ..bills(vec![row().rg("rg-a").location("eastus").cost(20.0)])
+        };
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let report = build_report(&filter, &latest, Some(&previous), &opts());
+        assert_eq!(report.previous_bill.as_deref(), Some("2025-12"));
+        let rg = &report.sections[2].rows[0];
+        assert_eq!((rg.cost, rg.source), (30.0, "combined"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["sections"][0]["dimension"], "Region");
+        // shares of a change are meaningless: null in JSON, empty in CSV
+        assert!(json["sections"][2]["rows"][0]["pct"].is_null());
+        assert!(json["sections"][2]["rows"][0]["cum_pct"].is_null());
+        let csv = report_to_csv(&report).unwrap();
+        assert!(
+            csv.contains("ResourceGroup,rg-a,30.00,,,combined,0"),
+            "{csv}"
+        );
+        assert!(csv.ends_with("Total,,30.00,,,,\n"), "{csv}");
+    }
+
+    #[test]
+    fn reservation_totals_with_reservation_filter() {
+        let vm = |reserved: bool| BillEntry {
+            date: "2026-01-01".to_string(),
+            meter_category: "Compute".to_string(),
+            meter_sub_category: "Virtual Machines".to_string(),
+            pricing_model: if reserved { "Reservation" } else { "OnDemand" }.to_string(),
+            quantity: 100.0,
+            unit_price: 0.2,
+            effective_price: if reserved { 0.1 } else { 0.2 },
+            ..row().rg("rg-a").location("eastus").cost(10.0)
+        };
+        let latest = Bills {
+            file_short_name: "2026-01".to_string(),
+            ..bills(vec![vm(true), vm(false)])
+        };
+        let filter = BillFilter::match_all(&FilterOpts::default())
+            .with_pattern(
+                crate::bills::bill_filter::FilterDimension::Reservation,
+                ".*",
+            )
+            .unwrap();
+        let report = build_report(&filter, &latest, None, &opts());
+        assert_eq!(report.sections.last().unwrap().dimension, "Reservation");
+        assert_eq!(report.reservations.len(), 1);
+        let r = &report.reservations[0];
+        assert_eq!(r.key, "MC:Compute__MSubC:Virtual Machines");
+        assert!((r.cost_savings - 10.0).abs() < 1e-9);
+        assert_eq!((r.hr_total, r.hr_reserved), (200.0, 100.0));
+        assert_eq!(r.utilisation_pct, Some(100.0));
+
+        let csv = report_to_csv(&report).unwrap();
+        assert!(
+            csv.contains(
+                "ReservationUtilisation,MC:Compute__MSubC:Virtual Machines,10.00,100.0,,,"
+            ),
+            "{csv}"
+        );
+        let md = report_to_markdown(&report);
+        assert!(md.contains("## Reservation utilisation (US$)"));
+    }
+}
@@ -3,16 +3,35 @@ use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
 
 use crate::bills::Bills;
+use crate::bills::date_range::DateRange;
+use crate::bills::tag_alias::TagAliases;
+use crate::bills::tag_inherit::TagInheritance;
 use crate::blob_source::BlobSource;
 use crate::cmd_parse::FilterOpts;
 use crate::find_files;
 
+/// Months loaded for a [`DateRange`] by [`BillRepository::get_range`].
+pub struct RangeBills {
+    /// Loaded months, oldest first.
+    pub months: Vec<Arc<Bills>>,
+    /// `"YYYY-MM"` of months in the range that could not be loaded.
+    pub missing: Vec<String>,
+}
+
+impl RangeBills {
+    pub fn bills(&self) -> Vec<&Bills> {
+        self.months.iter().map(|b| b.as_ref()).collect()
+    }
+}
+
 /// A caching repository that loads Azure billing data from local CSVs or blob
 /// storage. Each unique `(year, month)` pair is loaded once and cached as an
 /// `Arc<Bills>` — subsequent calls return the same allocation.
 pub struct BillRepository {
     data_dir: PathBuf,
     blob: Option<Arc<BlobSource>>,
+    tag_aliases: Option<Arc<TagAliases>>,
+    tag_inheritance: Option<Arc<TagInheritance>>,
     #[allow(clippy::type_complexity)]
     cache: Arc<RwLock<HashMap<(u32, u32), Arc<Bills>>>>,
 }
@@ -22,10 +41,33 @@ impl BillRepository {
         Self {
             data_dir,
             blob,
+            tag_aliases: None,
+            tag_inheritance: None,
             cache: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Normalise tags of every loaded month with `aliases` before caching.
+    pub fn with_tag_aliases(mut self, aliases: Option<Arc<TagAliases>>) -> Self {
+        self.tag_aliases = aliases;
+        self
+    }
+
+    /// Fill missing tags of every loaded month from `rules` before caching (after aliases).
+    pub fn with_tag_inheritance(mut self, rules: Option<Arc<TagInheritance>>) -> Self {
+        self.tag_inheritance = rules;
+        self
+    }
+
+    fn enrich_tags(&self, bills: &mut Bills) {
+        if let Some(aliases) = &self.tag_aliases {
+            bills.apply_tag_aliases(aliases);
+        }
+        if let Some(rules) = &self.tag_inheritance {
+            bills.inherit_tags(rules);
+        }
+    }
+
     /// Return bills for `year`/`month`. Loads from local CSV or blob on the
     /// first call; subsequent calls return the cached `Arc<Bills>`.
     pub async fn get(&self, year: u32, month: u32) -> Result<Arc<Bills>, String> {
@@ -51,6 +93,7 @@ impl BillRepository {
             bills
                 .parse_csv(&csv_path, &filter_opts)
                 .map_err(|e| format!("Failed to parse '{:?}': {e}", csv_path))?;
+            self.enrich_tags(&mut bills);
             log::info!(
                 "[repo] loaded {month_str} from local ({} rows)",
                 bills.len()
@@ -68,7 +111,7 @@ impl BillRepository {
             let filter_opts = FilterOpts {
                 case_sensitive: false,
             };
-            let bills = blob
+            let mut bills = blob
                 .load_bills_for_month(year, month, &filter_opts)
                 .await
                 .map_err(|e| {
@@ -76,6 +119,7 @@ impl BillRepository {
                     log::error!("[repo] {msg}");
                     msg
                 })?;
+            self.enrich_tags(&mut bills);
             log::info!("[repo] loaded {month_str} from blob ({} rows)", bills.len());
             let bills = Arc::new(bills);
             self.cache
@@ -91,6 +135,50 @@ impl BillRepository {
         ))
     }
 
+    /// Return bills for every month `range` touches (see `DateRange::months`); an
+    /// open start begins at the earliest month available locally or in blob storage.
+    /// Months that fail to load — e.g. the current month not exported yet — are
+    /// skipped and listed in `missing`; fails when no month loads.
+    pub async fn get_range(&self, range: &DateRange) -> Result<RangeBills, String> {
+        let months = if range.from.is_some() {
+            range.months()?
+        } else {
+            let first = self
+                .list_months_including_blob()
+                .await
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    format!("No billing data found for {range} in {:?}", self.data_dir)
+                })?;
+            DateRange {
+                from: Some(format!("{first}-01")),
+                to: range.to.clone(),
+            }
+            .months()?
+        };
+        let mut loaded = RangeBills {
+            months: Vec::new(),
+            missing: Vec::new(),
+        };
+        for (year, month) in months {
+            match self.get(year, month).await {
+                Ok(bills) => loaded.months.push(bills),
+                Err(e) => {
+                    log::warn!("[repo] range {range}: {e}");
+                    loaded.missing.push(format!("{year}-{month:02}"));
+                }
+            }
+        }
+        if loaded.months.is_empty() {
+            return Err(format!(
+                "No billing data found for {range} (months {})",
+                loaded.missing.join(", ")
+            ));
+        }
+        Ok(loaded)
+    }
+
     /// Return sorted `"YYYY-MM"` strings for all months found locally in `data_dir`.
     pub async fn list_months(&self) -> Vec<String> {
         find_files::list_bill_months(&self.data_dir)
@@ -172,6 +260,32 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn get_range_skips_missing_months() {
+        let tmp = tempfile::tempdir().unwrap();
+        setup_test_csv(&tmp);
+        let repo = BillRepository::new(tmp.path().to_path_buf(), None);
+        let range = DateRange::parse(Some("2024-02-20"), Some("2024-03-31")).unwrap();
+        let loaded = repo.get_range(&range).await.unwrap();
+        assert_eq!(loaded.months.len(), 1);
+        assert_eq!(loaded.missing, vec!["2024-02"]);
+        let empty = DateRange::parse(Some("2099-01"), Some("2099-02")).unwrap();
+        assert!(repo.get_range(&empty).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn get_range_open_start_begins_at_earliest_month() {
+        let tmp = tempfile::tempdir().unwrap();
+        setup_test_csv(&tmp);
+        let repo = BillRepository::new(tmp.path().to_path_buf(), None);
+        let range = DateRange::parse(None, Some("2024-04-15")).unwrap();
+        let loaded = repo.get_range(&range).await.unwrap();
+        assert_eq!(loaded.months.len(), 1, "2024-03 is the earliest month");
+        assert_eq!(loaded.missing, vec!["2024-04"]);
+        let before = DateRange::parse(None, Some("2024-02")).unwrap();
+        assert!(repo.get_range(&before).await.is_err());
+    }
+
     #[tokio::test]
     async fn list_months_returns_sorted_strings() {
         let tmp = tempfile::tempdir().unwrap();
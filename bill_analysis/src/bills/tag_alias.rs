@@ -0,0 +1,386 @@
+//! Tag key / value normalisation from an alias file.
+//!
+//! Teams spell the same tag differently (`Env`, `environment`, `ENVIRONMENT`;
+//! `prod`, `Production`, `PRD`), which splits `--tag-summarise` rows and makes
+//! tag predicates miss. `Bills::apply_tag_aliases` rewrites `Tags.kv` (and the
+//! lowercase `Tags.value` used by `--tag-filter`) to the canonical spelling
+//! after parsing, and records which raw variants were merged.
+//!
+//! Alias file (JSON), values are keyed by the canonical tag key:
+//!
+//! ```json
+//! {
+//!   "keys":   { "environment": ["env", "envname"] },
+//!   "values": { "environment": { "prod": ["production", "prd"] } }
+//! }
+//! ```
+//!
+//! Aliases match case-insensitively; the canonical spelling itself also folds
+//! case variants (`ENVIRONMENT` → `environment`, `Prod` → `prod`).
+//! Set `AZ_BILLING_TAG_ALIASES` to the file path to apply it in the CLI and MCP server.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::Path;
+
+use crate::bills::Bills;
+use crate::bills::tags::Tags;
+
+/// Env var holding the path of the alias file.
+pub const TAG_ALIASES_ENV: &str = "AZ_BILLING_TAG_ALIASES";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagAliasFile {
+    #[serde(default)]
+    keys: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    values: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+/// Compiled alias map, see the module docs for the file format.
+#[derive(Debug, Default)]
+pub struct TagAliases {
+    /// lowercase alias (or canonical) key → canonical key
+    keys: HashMap<String, String>,
+    /// lowercase canonical key → lowercase alias (or canonical) value → canonical value
+    values: HashMap<String, HashMap<String, String>>,
+}
+
+/// One raw spelling folded into a canonical key or value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagMerge {
+    /// Canonical tag key the variant belongs to.
+    pub key: String,
+    /// `true` for a key variant, `false` for a value variant.
+    pub is_key: bool,
+    pub raw: String,
+    pub canonical: String,
+    /// Number of bill rows rewritten.
+    pub rows: usize,
+    /// Cost of those rows, NZD.
+    pub cost: f64,
+}
+
+/// Insert `alias` → `canonical` into `map`, failing when `alias` already maps elsewhere.
+fn insert_alias(
+    map: &mut HashMap<String, String>,
+    alias: &str,
+    canonical: &str,
+    what: &str,
+) -> Result<(), String> {
+    let alias = alias.trim().to_lowercase();
+    match map.get(&alias) {
+        Some(existing) if existing != canonical => Err(format!(
+            "Tag {what} alias '{alias}' maps to both '{existing}' and '{canonical}'"
+        )),
+        _ => {
+            map.insert(alias, canonical.to_string());
+            Ok(())
+        }
+    }
+}
+
+impl TagAliases {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: TagAliasFile =
+            serde_json::from_str(json).map_err(|e| format!("Invalid tag alias file: {e}"))?;
+        let mut aliases = TagAliases::default();
+        for (canonical, list) in &file.keys {
+            let canonical = canonical.trim();
+            insert_alias(&mut aliases.keys, canonical, canonical, "key")?;
+            for alias in list {
+                insert_alias(&mut aliases.keys, alias, canonical, "key")?;
+            }
+        }
+        for (key, map) in &file.values {
+            // values may be listed under an alias of the key
+            let key = aliases
+                .canonical_key(&key.trim().to_lowercase())
+                .unwrap_or(key.trim())
+                .to_lowercase();
+            let values = aliases.values.entry(key).or_default();
+            for (canonical, list) in map {
+                let canonical = canonical.trim();
+                insert_alias(values, canonical, canonical, "value")?;
+                for alias in list {
+                    insert_alias(values, alias, canonical, "value")?;
+                }
+            }
+        }
+        Ok(aliases)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tag alias file {path:?}: {e}"))?;
+        Self::from_json(&json).map_err(|e| format!("{e} in {path:?}"))
+    }
+
+    /// Load the file named by `AZ_BILLING_TAG_ALIASES`, `Ok(None)` when unset.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var(TAG_ALIASES_ENV) {
+            Ok(path) if !path.is_empty() => Self::load(Path::new(&path)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn canonical_key(&self, lower_key: &str) -> Option<&str> {
+        self.keys.get(lower_key).map(String::as_str)
+    }
+
+    /// Canonical form of `tags`, or `None` when nothing changes.
+    /// Calls `merged(key, is_key, raw, canonical)` for every rewritten key or value
+    /// that is kept. When a row carries both the canonical key and an alias, the
+    /// alias is dropped; a different value on it is logged as a conflict.
+    pub fn normalise(
+        &self,
+        tags: &Tags,
+        mut merged: impl FnMut(&str, bool, &str, &str),
+    ) -> Option<Tags> {
+        let mut entries: Vec<(&String, &(String, String))> = tags.kv.iter().collect();
+        // an exact canonical key wins over its aliases when a row carries both
+        entries.sort_by_key(|(lk, _)| {
+            (
+                self.canonical_key(lk).is_some_and(|c| c != lk.as_str()),
+                *lk,
+            )
+        });
+        let mut kv: HashMap<String, (String, String)> = HashMap::with_capacity(entries.len());
+        let mut changed = false;
+        for (lk, (value, orig_key)) in entries {
+            let key = self.canonical_key(lk).unwrap_or(orig_key);
+            let lower_key = key.to_lowercase();
+            let canonical_value = self
+                .values
+                .get(&lower_key)
+                .and_then(|m| m.get(&value.to_lowercase()))
+                .unwrap_or(value);
+            match kv.entry(lower_key) {
+                Entry::Occupied(kept) => {
+                    let (kept_value, kept_key) = kept.get();
+                    if kept_value != canonical_value {
+                        log::warn!(
+                            "Tag conflict: '{orig_key}': '{value}' dropped, the row already has '{kept_key}': '{kept_value}'"
+                        );
+                    }
+                    changed = true;
+                }
+                Entry::Vacant(slot) => {
+                    if key != orig_key {
+                        merged(key, true, orig_key, key);
+                    }
+                    if canonical_value != value {
+                        merged(key, false, value, canonical_value);
+                    }
+                    changed |= key != orig_key || canonical_value != value;
+                    slot.insert((canonical_value.clone(), key.to_string()));
+                }
+            }
+        }
+        if !changed {
+            return None;
+        }
+        let mut pairs: Vec<String> = kv
+            .values()
+            .map(|(v, k)| {
+                format!(
+                    "{}: {}",
+                    serde_json::Value::from(k.as_str()),
+                    serde_json::Value::from(v.as_str())
+                )
+            })
+            .collect();
+        pairs.sort();
+        Some(Tags {
+            kv,
+            value: pairs.join(",").to_lowercase(),
+            parse_error: tags.parse_error.clone(),
+            inherited: tags.inherited.clone(),
+        })
+    }
+}
+
+impl Bills {
+    /// Rewrite every row's tags to the canonical keys / values, rebuild
+    /// `tag_names` and record the merged variants in `tag_merges`.
+    pub fn apply_tag_aliases(&mut self, aliases: &TagAliases) {
+        let mut merges: HashMap<(String, bool, String, String), (usize, f64)> = HashMap::new();
+        for bill in self.bills.iter_mut() {
+            let cost = bill.cost.amount();
+            if let Some(tags) = aliases.normalise(&bill.tags, |key, is_key, raw, canonical| {
+                let e = merges
+                    .entry((
+                        key.to_string(),
+                        is_key,
+                        raw.to_string(),
+                        canonical.to_string(),
+                    ))
+                    .or_default();
+                e.0 += 1;
+                e.1 += cost;
+            }) {
+                bill.tags = tags;
+            }
+        }
+        self.tag_names = self
+            .bills
+            .iter()
+            .flat_map(|b| b.tags.kv.keys().cloned())
+            .collect();
+        self.tag_merges = sum_tag_merges(merges.into_iter().map(
+            |((key, is_key, raw, canonical), (rows, cost))| TagMerge {
+                key,
+                is_key,
+                raw,
+                canonical,
+                rows,
+                cost,
+            },
+        ));
+    }
+}
+
+/// One `TagMerge` per (key, is_key, raw, canonical) with rows and cost summed,
+/// sorted by key, key variants first; e.g. the merges of several months.
+pub fn sum_tag_merges(merges: impl IntoIterator<Item = TagMerge>) -> Vec<TagMerge> {
+    let mut summed: Vec<TagMerge> = Vec::new();
+    for m in merges {
+        match summed.iter_mut().find(|s| {
+            (&s.key, s.is_key, &s.raw, &s.canonical) == (&m.key, m.is_key, &m.raw, &m.canonical)
+        }) {
+            Some(s) => {
+                s.rows += m.rows;
+                s.cost += m.cost;
+            }
+            None => summed.push(m),
+        }
+    }
+    summed.sort_by(|a, b| {
+        (&a.key, !a.is_key, &a.canonical, &a.raw).cmp(&(&b.key, !b.is_key, &b.canonical, &b.raw))
+    });
+    summed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::bills_sum_data::SummaryData;
+    use crate::bills::cost_type_enum::CostType;
+    use crate::bills::test_support::row;
+    use crate::money::Nzd;
+
+    const ALIASES: &str = r#"{
+        "keys":   { "environment": ["env", "EnvName"] },
+        "values": { "env": { "prod": ["production", "PRD"] } }
+    }"#;
+
+    #[test]
+    fn keys_and_values_fold_to_canonical() {
+        let aliases = TagAliases::from_json(ALIASES).unwrap();
+        let mut bills = Bills::default();
+        for (tags, cost) in [
+            (r#""Env": "Production""#, 10.0),
+            (r#""ENVIRONMENT": "prd""#, 20.0),
+            (r#""environment": "prod","owner": "al""#, 30.0),
+            (r#""envname": "dev""#, 5.0),
+        ] {
+            bills.push(row().tags(tags).cost(cost));
+        }
+        bills.apply_tag_aliases(&aliases);
+
+        let mut summary = SummaryData::default();
+        for b in &bills.bills {
+            summary.accumulate(
+                CostType::Tag,
+                b.tags.summary_key("environment"),
+                b.cost,
+                b.cost_usd,
+                0.0,
+            );
+        }
+        let keys: Vec<&String> = {
+            let mut k: Vec<&String> = summary.per_type.keys().map(|(_, n)| n).collect();
+            k.sort();
+            k
+        };
+        assert_eq!(keys, vec!["tag:environment=dev", "tag:environment=prod"]);
+        assert_eq!(
+            summary.per_type[&(CostType::Tag, "tag:environment=prod".to_string())].cost,
+            Nzd(60.0)
+        );
+        assert_eq!(
+            bills.tag_names.len(),
+            2,
+            "env, envname folded: {:?}",
+            bills.tag_names
+        );
+        assert_eq!(bills.bills[0].tags.value, r#""environment": "prod""#);
+
+        let m = |is_key: bool, raw: &str| {
+            bills
+                .tag_merges
+                .iter()
+                .find(|m| m.is_key == is_key && m.raw == raw)
+                .unwrap_or_else(|| panic!("no merge for {raw}"))
+        };
+        assert_eq!(m(true, "Env").canonical, "environment");
+        assert_eq!(m(true, "ENVIRONMENT").rows, 1);
+        assert_eq!(m(false, "Production").canonical, "prod");
+        assert_eq!(m(false, "prd").cost, 20.0);
+        assert!(
+            !bills.tag_merges.iter().any(|m| m.raw == "prod"),
+            "unchanged values are not reported"
+        );
+    }
+
+    #[test]
+    fn canonical_key_wins_when_row_has_both() {
+        let aliases = TagAliases::from_json(ALIASES).unwrap();
+        let tags = Tags::parse(r#""env": "dev","environment": "prod""#);
+        let mut merged = Vec::new();
+        let out = aliases
+            .normalise(&tags, |_, _, raw, _| merged.push(raw.to_string()))
+            .unwrap();
+        assert_eq!(out.kv.len(), 1);
+        assert_eq!(out.get_value("environment"), Some("prod"));
+        assert!(
+            merged.is_empty(),
+            "dropped alias is not a merge: {merged:?}"
+        );
+        assert!(
+            aliases
+                .normalise(&Tags::parse(r#""owner": "al""#), |_, _, _, _| {})
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn combined_months_sum_their_merges() {
+        let aliases = TagAliases::from_json(ALIASES).unwrap();
+        let month = |cost: f64| {
+            let mut bills = Bills::default();
+            bills.push(row().tags(r#""Env": "dev""#).cost(cost));
+            bills.apply_tag_aliases(&aliases);
+            bills
+        };
+        let (jan, feb) = (month(10.0), month(5.0));
+        let both = Bills::combine(&[&jan, &feb], "2026-01..2026-02").unwrap();
+        assert_eq!(both.tag_merges.len(), 1);
+        assert_eq!(
+            (both.tag_merges[0].rows, both.tag_merges[0].cost),
+            (2, 15.0)
+        );
+    }
+
+    #[test]
+    fn conflicting_or_invalid_files_are_errors() {
+        let err =
+            TagAliases::from_json(r#"{"keys": {"env": ["e"], "environment": ["e"]}}"#).unwrap_err();
+        assert!(err.contains("alias 'e'"), "{err}");
+        assert!(TagAliases::from_json(r#"{"key": {}}"#).is_err());
+        assert!(TagAliases::load(Path::new("/nonexistent/aliases.json")).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Tag compliance — which resources miss the required tags and what that spend costs.
+//!
+//! For each required key the rows are aggregated with the same `CostType::Tag`
+//! naming as `--tag-summarise` (`tag:none` for missing or empty values), so the
+//! untagged cost here matches the `tag:none` line of the main report.
+//! A row counts as *untagged* in the subscription / resource group breakdown
+//! when it misses at least one required key.
+
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::bills_sum_data::SummaryData;
+use crate::bills::cost_type_enum::CostType;
+use crate::bills::query::pct;
+use crate::f64_to_currency;
+
+/// Used when no keys are given on the command line or in a file.
+pub const DEFAULT_REQUIRED_TAGS: &[&str] = &["owner", "environment", "costcenter"];
+
+/// A resource with at least one row missing a required key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissingResource {
+    pub resource_id: String,
+    pub resource_name: String,
+    pub resource_group: String,
+    pub subscription: String,
+    /// Cost of the rows missing the key, NZD.
+    pub cost: f64,
+    pub cost_usd: f64,
+}
+
+/// Compliance of one required tag key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyCompliance {
+    pub key: String,
+    /// `false` when no row in the bill carries the key at all (`Bills.tag_names`), often a typo.
+    pub seen_in_bill: bool,
+    pub missing_cost: f64,
+    pub missing_cost_usd: f64,
+    pub missing_pct: f64,
+    /// Cost of rows that have the key only through `tag_inherit`, not set on the resource.
+    pub inherited_cost: f64,
+    /// Sorted by cost, largest first.
+    pub resources: Vec<MissingResource>,
+}
+
+/// Untagged share of one subscription or resource group.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScopeCompliance {
+    pub name: String,
+    pub total_cost: f64,
+    pub untagged_cost: f64,
+    pub untagged_pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComplianceReport {
+    pub required: Vec<String>,
+    pub total_cost: f64,
+    pub total_cost_usd: f64,
+    /// Cost of rows missing at least one required key.
+    pub untagged_cost: f64,
+    pub untagged_cost_usd: f64,
+    pub untagged_pct: f64,
+    pub keys: Vec<KeyCompliance>,
+    /// Sorted by untagged cost, largest first.
+    pub by_subscription: Vec<ScopeCompliance>,
+    pub by_resource_group: Vec<ScopeCompliance>,
+}
+
+/// Read required tag keys from a file: one or more comma separated keys per line,
+/// `#` starts a comment. Keys are lowercased as `Tags.kv` keys are.
+pub fn load_required_tags(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read required tags file {path:?}: {e}"))?;
+    Ok(parse_required_tags(
+        text.lines()
+            .map(|line| line.split('#').next().unwrap_or_default()),
+    ))
+}
+
+/// Split, trim, lowercase and dedup keys, keeping the first-seen order.
+pub fn parse_required_tags<'s>(specs: impl IntoIterator<Item = &'s str>) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for key in specs.into_iter().flat_map(|s| s.split(',')) {
+        let key = key.trim().to_lowercase();
+        if !key.is_empty() && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+fn sorted_scopes(map: HashMap<String, (f64, f64)>) -> Vec<ScopeCompliance> {
+    let mut scopes: Vec<ScopeCompliance> = map
+        .into_iter()
+        .map(|(name, (total_cost, untagged_cost))| ScopeCompliance {
+            name,
+            total_cost,
+            untagged_cost,
+            untagged_pct: pct(untagged_cost, total_cost),
+        })
+        .collect();
+    scopes.sort_by(|a, b| {
+        b.untagged_cost
+            .partial_cmp(&a.untagged_cost)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    scopes
+}
+
+/// Check the rows matching `filter` for the lowercase `required` tag keys.
+pub fn tag_compliance(bills: &Bills, filter: &BillFilter, required: &[String]) -> ComplianceReport {
+    let mut inherited_cost = vec![0.0; required.len()];
+    let mut per_key: Vec<SummaryData> = required.iter().map(|_| SummaryData::default()).collect();
+    let mut resources: Vec<HashMap<&str, MissingResource>> =
+        required.iter().map(|_| HashMap::new()).collect();
+    let mut by_subscription: HashMap<String, (f64, f64)> = HashMap::new();
+    let mut by_resource_group: HashMap<String, (f64, f64)> = HashMap::new();
+    let (mut total_cost, mut total_cost_usd) = (0.0, 0.0);
+    let (mut untagged_cost, mut untagged_cost_usd) = (0.0, 0.0);
+
+    for bill in bills.bills.iter().filter(|b| filter.matches(b)) {
+        let cost = bill.cost.amount();
+        let cost_usd = bill.cost_usd.amount();
+        total_cost += cost;
+        total_cost_usd += cost_usd;
+        let mut untagged = false;
+        for (i, key) in required.iter().enumerate() {
+            let tag_key = bill.tags.summary_key(key);
+            let missing = bill.tags.get_value(key).is_none();
+            if !missing && !bill.tags.is_explicit(key) {
+                inherited_cost[i] += cost;
+            }
+            per_key[i].accumulate(
+                CostType::Tag,
+                tag_key,
+                bill.cost,
+                bill.cost_usd,
+                bill.unit_price * bill.quantity,
+            );
+            if missing {
+                untagged = true;
+                let id = if bill.resource_id.is_empty() {
+                    bill.resource_name.as_str()
+                } else {
+                    bill.resource_id.as_str()
+                };
+                let r = resources[i].entry(id).or_insert_with(|| MissingResource {
+                    resource_id: id.to_string(),
+                    resource_name: bill.resource_name.clone(),
+                    resource_group: bill.resource_group.clone(),
+                    subscription: bill.subscription_name.clone(),
+                    cost: 0.0,
+                    cost_usd: 0.0,
+                });
+                r.cost += cost;
+                r.cost_usd += cost_usd;
+            }
+        }
+        let untagged_part = if untagged { cost } else { 0.0 };
+        if untagged {
+            untagged_cost += cost;
+            untagged_cost_usd += cost_usd;
+        }
+        for (map, name) in [
+            (&mut by_subscription, &bill.subscription_name),
+            (&mut by_resource_group, &bill.resource_group),
+        ] {
+            let e = map.entry(name.clone()).or_default();
+            e.0 += cost;
+            e.1 += untagged_part;
+        }
+    }
+
+    let keys = required
+        .iter()
+        .zip(per_key)
+        .zip(resources)
+        .zip(inherited_cost)
+        .map(|(((key, summary), resources), inherited_cost)| {
+            let none = summary
+                .per_type
+                .get(&(CostType::Tag, "tag:none".to_string()));
+            let missing_cost = none.map(|t| t.cost.amount()).unwrap_or_default();
+            let mut resources: Vec<MissingResource> = resources.into_values().collect();
+            resources.sort_by(|a, b| {
+                b.cost
+                    .partial_cmp(&a.cost)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| a.resource_id.cmp(&b.resource_id))
+            });
+            KeyCompliance {
+                key: key.clone(),
+                seen_in_bill: bills.tag_names.contains(key),
+                missing_cost,
+                missing_cost_usd: none.map(|t| t.cost_usd.amount()).unwrap_or_default(),
+                missing_pct: pct(missing_cost, total_cost),
+                inherited_cost,
+                resources,
+            }
+        })
+        .collect();
+
+    ComplianceReport {
+        required: required.to_vec(),
+        total_cost,
+        total_cost_usd,
+        untagged_cost,
+        untagged_cost_usd,
+        untagged_pct: pct(untagged_cost, total_cost),
+        keys,
+        by_subscription: sorted_scopes(by_subscription),
+        by_resource_group: sorted_scopes(by_resource_group),
+    }
+}
+
+/// Render the resources missing each key as CSV, one line per (key, resource).
+pub fn compliance_to_csv(report: &ComplianceReport) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "missing_key",
+        "resource_id",
+        "resource_name",
+        "resource_group",
+        "subscription",
+        "cost",
+        "cost_usd",
+    ])?;
+    for key in &report.keys {
+        for r in &key.resources {
+            wtr.write_record([
+                key.key.clone(),
+                r.resource_id.clone(),
+                r.resource_name.clone(),
+                r.resource_group.clone(),
+                r.subscription.clone(),
+                format!("{:.2}", r.cost),
+                format!("{:.2}", r.cost_usd),
+            ])?;
+        }
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+fn print_scopes(title: &str, scopes: &[ScopeCompliance], top: usize, cur: &str) {
+    println!("{}", format!("Untagged spend per {title}").bold());
+    for s in scopes.iter().filter(|s| s.untagged_cost != 0.0).take(top) {
+        println!(
+            "{:>16} of {:>16} {:>7}  '{}'",
+            format!("{cur} {}", f64_to_currency(s.untagged_cost, 2)).red(),
+            format!("{cur} {}", f64_to_currency(s.total_cost, 2)),
+            format!("{:.1}%", s.untagged_pct).yellow(),
+            s.name,
+        );
+    }
+}
+
+/// Print the report as coloured terminal text, listing at most `top` resources / scopes per section.
+pub fn print_compliance(report: &ComplianceReport, top: usize, cur: &str) {
+    println!(
+        "Required tags [{}]: untagged spend {cur} {} of {cur} {} ({})",
+        report.required.join(", "),
+        f64_to_currency(report.untagged_cost, 2).red().bold(),
+        f64_to_currency(report.total_cost, 2),
+        format!("{:.1}%", report.untagged_pct).bold(),
+    );
+    for key in &report.keys {
+        println!(
+            "{} missing on {} resources, {cur} {} ({})",
+            format!("'{}'", key.key).bold(),
+            key.resources.len(),
+            f64_to_currency(key.missing_cost, 2).red(),
+            format!("{:.1}%", key.missing_pct).yellow(),
+        );
+        if key.inherited_cost != 0.0 {
+            println!(
+                "  tagged by inheritance only: {cur} {}",
+                f64_to_currency(key.inherited_cost, 2).cyan()
+            );
+        }
+        if !key.seen_in_bill {
+            println!(
+                "  {}",
+                format!("tag '{}' not found on any row in this bill", key.key).yellow()
+            );
+        }
+        for r in key.resources.iter().take(top) {
+            println!(
+                "  {:>16}  Sub:'{}' RG:'{}' '{}'",
+                format!("{cur} {}", f64_to_currency(r.cost, 2)).red(),
+                r.subscription,
+                r.resource_group,
+                r.resource_name,
+            );
+        }
+        if key.resources.len() > top {
+            println!("  ... {} more", key.resources.len() - top);
+        }
+    }
+    print_scopes("subscription", &report.by_subscription, top, cur);
+    print_scopes("resource group", &report.by_resource_group, top, cur);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    #[test]
+    fn missing_cost_per_key_and_scope() {
+        let b = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg-a")
+                .tags(r#""owner": "al","environment": "prod""#)
+                .cost(60.0),
+            row()
+                .id("vm2")
+                .rg("rg-a")
+                .tags(r#""environment": "dev""#)
+                .cost(30.0),
+            row()
+                .id("vm2")
+                .rg("rg-a")
+                .tags(r#""owner": "","environment": "dev""#)
+                .cost(5.0),
+            row().id("vm3").rg("rg-b").cost(5.0),
+        ]);
+        let required = parse_required_tags(["Owner, environment", "owner"]);
+        assert_eq!(required, vec!["owner", "environment"]);
+        let r = tag_compliance(
+            &b,
+            &BillFilter::match_all(&FilterOpts::default()),
+            &required,
+        );
+        assert_eq!(r.total_cost, 100.0);
+        assert_eq!(r.untagged_cost, 40.0);
+        assert_eq!(r.untagged_pct, 40.0);
+
+        let owner = &r.keys[0];
+        assert_eq!(owner.missing_cost, 40.0, "empty value counts as missing");
+        assert_eq!(owner.missing_cost_usd, 20.0);
+        let ids: Vec<&str> = owner
+            .resources
+            .iter()
+            .map(|r| r.resource_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["vm2", "vm3"]);
+        assert_eq!(owner.resources[0].cost, 35.0);
+        let env = &r.keys[1];
+        assert_eq!(env.missing_cost, 5.0);
+        assert!(env.seen_in_bill);
+
+        assert_eq!(r.by_resource_group[0].name, "rg-a");
+        assert_eq!(r.by_resource_group[0].untagged_cost, 35.0);
+        assert_eq!(r.by_resource_group[1].untagged_pct, 100.0);
+        assert_eq!(r.by_subscription.len(), 1);
+        assert_eq!(r.by_subscription[0].untagged_pct, 40.0);
+    }
+
+    #[test]
+    fn unknown_key_is_flagged_and_csv_lists_resources() {
+        let b = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg-a")
+                .sub("sub")
+                .tags(r#""owner": "al""#)
+                .cost(10.0),
+        ]);
+        let r = tag_compliance(
+            &b,
+            &BillFilter::match_all(&FilterOpts::default()),
+            &parse_required_tags(["costcentre"]),
+        );
+        assert!(!r.keys[0].seen_in_bill);
+        assert_eq!(r.keys[0].missing_pct, 100.0);
+        let csv = compliance_to_csv(&r).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "missing_key,resource_id,resource_name,resource_group,subscription,cost,cost_usd"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "costcentre,vm1,vm1,rg-a,sub,10.00,5.00"
+        );
+    }
+
+    #[test]
+    fn required_tags_file_skips_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("required.txt");
+        std::fs::write(
+            &path,
+            "# governance\nOwner\nenvironment, costcenter # finance\n\n",
+        )
+        .unwrap();
+        assert_eq!(
+            load_required_tags(&path).unwrap(),
+            vec!["owner", "environment", "costcenter"]
+        );
+        assert!(load_required_tags(&dir.path().join("nope")).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! Tag inheritance — fill missing tags on a row from its resource group or subscription.
+//!
+//! Disks, NICs and snapshots are often untagged while their resource group is.
+//! `Bills::inherit_tags` fills tag keys a row does not have (or has empty) from,
+//! in order of precedence:
+//!
+//! 1. the row's resource group in the inheritance file,
+//! 2. the row's subscription in the inheritance file,
+//! 3. with `dominant_min_share` set, the most common value (by cost) of that key
+//!    on the other rows of the same subscription + resource group, when it
+//!    covers at least that share of their tagged cost.
+//!
+//! Inherited keys are recorded in `Tags.inherited`, so `--tag-summarise` shows
+//! them as separate `(inherited:…)` rows; filters and predicates see the
+//! effective tags. Tags set on the row always win.
+//!
+//! Inheritance file (JSON), names match case-insensitively. Resource groups are
+//! keyed `subscription/resource_group`; a bare resource group name applies in
+//! every subscription (a warning is logged when the bill has that name in more
+//! than one) and the qualified entry wins over it:
+//!
+//! ```json
+//! {
+//!   "resource_groups":    { "Prod-Sub/rg-prod-app": { "environment": "prod", "owner": "team-a" },
+//!                           "rg-shared": { "owner": "platform" } },
+//!   "subscriptions":      { "Prod-Sub": { "costcenter": "4711" } },
+//!   "dominant_min_share": 0.6
+//! }
+//! ```
+//!
+//! Set `AZ_BILLING_TAG_INHERITANCE` to the file path to apply it in the CLI and MCP server.
+//! Runs after the alias map (`tag_alias`), so inherited keys use canonical spellings.
+
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::bills::Bills;
+use crate::bills::tags::{TagOrigin, Tags};
+
+/// Env var holding the path of the inheritance file.
+pub const TAG_INHERITANCE_ENV: &str = "AZ_BILLING_TAG_INHERITANCE";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagInheritanceFile {
+    #[serde(default)]
+    resource_groups: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    subscriptions: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    dominant_min_share: Option<f64>,
+}
+
+/// (original key, value) pairs to inherit, per lowercase scope name
+/// (`subscription/resource_group` or a bare name for resource groups).
+type ScopeTags = HashMap<String, Vec<(String, String)>>;
+/// lowercase key → value → (cost, original key) of the explicit tags in one resource group.
+type ValueCosts = HashMap<String, HashMap<String, (f64, String)>>;
+
+/// Compiled inheritance rules, see the module docs for the file format.
+#[derive(Debug, Default)]
+pub struct TagInheritance {
+    resource_groups: ScopeTags,
+    subscriptions: ScopeTags,
+    /// Inherit dominant resource group values covering at least this share (0..=1).
+    pub dominant_min_share: Option<f64>,
+}
+
+fn scope_tags(map: HashMap<String, HashMap<String, String>>) -> ScopeTags {
+    map.into_iter()
+        .map(|(scope, tags)| {
+            let mut tags: Vec<(String, String)> = tags
+                .into_iter()
+                .filter(|(k, v)| !k.trim().is_empty() && !v.is_empty())
+                .map(|(k, v)| (k.trim().to_string(), v))
+                .collect();
+            tags.sort();
+            (scope.trim().to_lowercase(), tags)
+        })
+        .collect()
+}
+
+impl TagInheritance {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: TagInheritanceFile =
+            serde_json::from_str(json).map_err(|e| format!("Invalid tag inheritance file: {e}"))?;
+        if let Some(share) = file.dominant_min_share
+            && !(0.0..=1.0).contains(&share)
+        {
+            return Err(format!(
+                "Invalid dominant_min_share {share}, expected 0.0..1.0"
+            ));
+        }
+        Ok(TagInheritance {
+            resource_groups: scope_tags(file.resource_groups),
+            subscriptions: scope_tags(file.subscriptions),
+            dominant_min_share: file.dominant_min_share,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tag inheritance file {path:?}: {e}"))?;
+        Self::from_json(&json).map_err(|e| format!("{e} in {path:?}"))
+    }
+
+    /// Load the file named by `AZ_BILLING_TAG_INHERITANCE`, `Ok(None)` when unset.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var(TAG_INHERITANCE_ENV) {
+            Ok(path) if !path.is_empty() => Self::load(Path::new(&path)).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Set `key` on `tags` as inherited from `origin` unless the row already has a value.
+/// Returns `true` when the tag was added.
+fn inherit(tags: &mut Tags, orig_key: &str, value: &str, origin: TagOrigin) -> bool {
+    let key = orig_key.to_lowercase();
+    if tags.get_value(&key).is_some() {
+        return false;
+    }
+    tags.value = format!(
+        "{}{}{}: {}",
+        tags.value,
+        if tags.value.is_empty() { "" } else { "," },
+        serde_json::Value::from(orig_key),
+        serde_json::Value::from(value)
+    )
+    .to_lowercase();
+    tags.kv
+        .insert(key.clone(), (value.to_string(), orig_key.to_string()));
+    tags.inherited.insert(key, origin);
+    true
+}
+
+impl TagInheritance {
+    /// Tags for the resource group `rg` in subscription `sub` (both lowercase),
+    /// the qualified `sub/rg` entry before the bare `rg` one.
+    fn resource_group_tags(&self, sub: &str, rg: &str) -> Option<&Vec<(String, String)>> {
+        self.resource_groups
+            .get(&format!("{sub}/{rg}"))
+            .or_else(|| self.resource_groups.get(rg))
+    }
+}
+
+impl Bills {
+    /// Warn about bare resource group names in `rules` that occur in more than
+    /// one subscription of the bill, they apply to all of them.
+    fn warn_ambiguous_resource_groups(&self, rules: &TagInheritance) {
+        let mut subs: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for bill in &self.bills {
+            let rg = bill.resource_group.to_lowercase();
+            if rules.resource_groups.contains_key(&rg) {
+                subs.entry(rg)
+                    .or_default()
+                    .insert(bill.subscription_name.to_lowercase());
+            }
+        }
+        let mut ambiguous: Vec<(String, BTreeSet<String>)> =
+            subs.into_iter().filter(|(_, s)| s.len() > 1).collect();
+        ambiguous.sort();
+        for (rg, subs) in ambiguous {
+            log::warn!(
+                "Tag inheritance: resource group '{rg}' is in subscriptions {}, key it as 'subscription/{rg}' to pick one",
+                subs.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+
+    /// Fill missing tags from `rules`, see the module docs for the precedence.
+    /// Returns the number of rows that received at least one tag.
+    pub fn inherit_tags(&mut self, rules: &TagInheritance) -> usize {
+        self.warn_ambiguous_resource_groups(rules);
+        // (sub, rg) → lowercase key → (original key, dominant value)
+        let mut dominant: HashMap<(String, String), HashMap<String, (String, String)>> =
+            HashMap::new();
+        if let Some(min_share) = rules.dominant_min_share {
+            let mut seen: HashMap<(String, String), ValueCosts> = HashMap::new();
+            for bill in &self.bills {
+                for (key, (value, orig_key)) in &bill.tags.kv {
+                    if !bill.tags.is_explicit(key) {
+                        continue;
+                    }
+                    let e = seen
+                        .entry((
+                            bill.subscription_name.to_lowercase(),
+                            bill.resource_group.to_lowercase(),
+                        ))
+                        .or_default()
+                        .entry(key.clone())
+                        .or_default()
+                        .entry(value.clone())
+                        .or_insert_with(|| (0.0, orig_key.clone()));
+                    e.0 += bill.cost.amount().abs();
+                }
+            }
+            for (scope, keys) in seen {
+                for (key, values) in keys {
+                    let total: f64 = values.values().map(|(c, _)| c).sum();
+                    let best = values.into_iter().max_by(|a, b| {
+                        a.1.0
+                            .partial_cmp(&b.1.0)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                            .then_with(|| b.0.cmp(&a.0))
+                    });
+                    if let Some((value, (cost, orig_key))) = best
+                        && total > 0.0
+                        && cost / total >= min_share
+                    {
+                        dominant
+                            .entry(scope.clone())
+                            .or_default()
+                            .insert(key, (orig_key, value));
+                    }
+                }
+            }
+        }
+
+        let mut rows = 0;
+        for bill in self.bills.iter_mut() {
+            let mut added = false;
+            let rg = bill.resource_group.to_lowercase();
+            let sub = bill.subscription_name.to_lowercase();
+            for (k, v) in rules.resource_group_tags(&sub, &rg).into_iter().flatten() {
+                added |= inherit(&mut bill.tags, k, v, TagOrigin::ResourceGroup);
+            }
+            for (k, v) in rules.subscriptions.get(&sub).into_iter().flatten() {
+                added |= inherit(&mut bill.tags, k, v, TagOrigin::Subscription);
+            }
+            if let Some(keys) = dominant.get(&(sub, rg)) {
+                let mut keys: Vec<&(String, String)> = keys.values().collect();
+                keys.sort();
+                for (k, v) in keys {
+                    added |= inherit(&mut bill.tags, k, v, TagOrigin::Dominant);
+                }
+            }
+            if added {
+                rows += 1;
+            }
+        }
+        self.tag_names.extend(
+            self.bills
+                .iter()
+                .flat_map(|b| b.tags.inherited.keys().cloned()),
+        );
+        log::info!("Inherited tags on {rows} of {} rows", self.bills.len());
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, row};
+
+    #[test]
+    fn mapping_file_precedence_and_marking() {
+        let rules = TagInheritance::from_json(
+            r#"{
+                "resource_groups": { "RG-App": { "environment": "prod", "Owner": "team-a" } },
+                "subscriptions":   { "prod-sub": { "owner": "finance", "costcenter": "4711" } }
+            }"#,
+        )
+        .unwrap();
+        let mut b = bills(vec![
+            row()
+                .sub("Prod-Sub")
+                .rg("rg-app")
+                .tags(r#""owner": "al""#)
+                .cost(10.0),
+            row().sub("Prod-Sub").rg("rg-other").cost(5.0),
+            row().sub("dev-sub").rg("rg-dev").cost(1.0),
+        ]);
+        assert_eq!(b.inherit_tags(&rules), 2);
+
+        let t = &b.bills[0].tags;
+        assert_eq!(t.get_value("owner"), Some("al"), "explicit tag wins");
+        assert!(t.is_explicit("owner"));
+        assert_eq!(t.get_value("environment"), Some("prod"));
+        assert_eq!(t.inherited["environment"], TagOrigin::ResourceGroup);
+        assert_eq!(t.inherited["costcenter"], TagOrigin::Subscription);
+        assert_eq!(t.summary_key("owner"), "tag:owner=al");
+        assert_eq!(
+            t.summary_key("environment"),
+            "tag:environment=prod (inherited:rg)"
+        );
+        assert!(t.value.contains(r#""environment": "prod""#), "{}", t.value);
+
+        let t = &b.bills[1].tags;
+        assert_eq!(t.get_value("owner"), Some("finance"));
+        assert_eq!(t.inherited["owner"], TagOrigin::Subscription);
+        assert!(b.bills[2].tags.kv.is_empty());
+        assert!(b.tag_names.contains("costcenter"));
+    }
+
+    #[test]
+    fn dominant_value_needs_min_share() {
+        let rules = TagInheritance::from_json(r#"{"dominant_min_share": 0.6}"#).unwrap();
+        let mut b = bills(vec![
+            row()
+                .sub("s")
+                .rg("rg-a")
+                .tags(r#""env": "prod","team": "x""#)
+                .cost(70.0),
+            row()
+                .sub("s")
+                .rg("rg-a")
+                .tags(r#""env": "dev","team": "y""#)
+                .cost(30.0),
+            row().sub("s").rg("rg-a").tags(r#""team": "x""#).cost(30.0),
+            row().sub("s").rg("rg-a").cost(2.0),
+            row().sub("s").rg("rg-b").cost(2.0),
+        ]);
+        assert_eq!(b.inherit_tags(&rules), 2);
+        let t = &b.bills[3].tags;
+        assert_eq!(t.get_value("env"), Some("prod"));
+        assert_eq!(t.inherited["env"], TagOrigin::Dominant);
+        // team: x has 100 of 130 = 77%
+        assert_eq!(t.get_value("team"), Some("x"));
+        assert_eq!(b.bills[2].tags.get_value("env"), Some("prod"));
+        assert!(b.bills[4].tags.kv.is_empty(), "no tagged rows in rg-b");
+
+        let mut b = bills(vec![
+            row()
+                .sub("s")
+                .rg("rg-a")
+                .tags(r#""env": "prod""#)
+                .cost(55.0),
+            row().sub("s").rg("rg-a").tags(r#""env": "dev""#).cost(45.0),
+            row().sub("s").rg("rg-a").cost(2.0),
+        ]);
+        assert_eq!(b.inherit_tags(&rules), 0, "55% is below min share");
+    }
+
+    #[test]
+    fn resource_groups_keyed_by_subscription() {
+        let rules = TagInheritance::from_json(
+            r#"{
+                "resource_groups": {
+                    "Prod-Sub/rg-app": { "environment": "prod" },
+                    "rg-app": { "environment": "unknown", "owner": "platform" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut b = bills(vec![
+            row().sub("prod-sub").rg("RG-App").cost(10.0),
+            row().sub("dev-sub").rg("rg-app").cost(5.0),
+        ]);
+        assert_eq!(b.inherit_tags(&rules), 2);
+        let t = &b.bills[0].tags;
+        assert_eq!(
+            t.get_value("environment"),
+            Some("prod"),
+            "qualified entry wins"
+        );
+        assert_eq!(t.get_value("owner"), None, "bare entry is not merged in");
+        let t = &b.bills[1].tags;
+        assert_eq!(t.get_value("environment"), Some("unknown"));
+        assert_eq!(t.get_value("owner"), Some("platform"));
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        assert!(TagInheritance::from_json(r#"{"dominant_min_share": 1.5}"#).is_err());
+        assert!(TagInheritance::from_json(r#"{"resource_group": {}}"#).is_err());
+        assert!(TagInheritance::load(Path::new("/nonexistent/inherit.json")).is_err());
+    }
+}
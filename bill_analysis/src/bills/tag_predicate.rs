@@ -0,0 +1,226 @@
+//! Structured tag predicates evaluated on `Tags.kv`.
+//!
+//! `BillFilter.tag_filter` is a regex over the whole lowercased tag string,
+//! which cannot express "key missing" and easily matches the wrong key.
+//! A `TagPredicate` targets one key. Several predicates are ANDed.
+//!
+//! Spec syntax (CLI `--tag`, MCP `tags`):
+//!
+//! | spec            | matches rows where …                         |
+//! |-----------------|----------------------------------------------|
+//! | `env`           | tag `env` exists with a non-empty value      |
+//! | `!env`          | tag `env` is missing or empty                |
+//! | `env=prod`      | tag `env` equals `prod`                      |
+//! | `env!=prod`     | tag `env` is missing or not equal to `prod`  |
+//! | `env~^prod`     | tag `env` exists and its value matches regex |
+//!
+//! Keys are always matched case-insensitively (as `Tags.kv` keys are lowercase).
+//! Values follow `FilterOpts.case_sensitive`.
+
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+use crate::bills::tags::Tags;
+
+#[derive(Debug, Clone)]
+pub enum TagPredicate {
+    Exists(String),
+    Missing(String),
+    Equals {
+        key: String,
+        value: String,
+        case_sensitive: bool,
+    },
+    NotEquals {
+        key: String,
+        value: String,
+        case_sensitive: bool,
+    },
+    Matches {
+        key: String,
+        re: Regex,
+    },
+}
+
+impl TagPredicate {
+    /// Parse one spec, see the module docs for the syntax.
+    pub fn parse(spec: &str, case_sensitive: bool) -> Result<Self, String> {
+        let spec = spec.trim();
+        // earliest operator wins, so values may contain '=' or '~'
+        let op = ["!=", "=", "~"]
+            .iter()
+            .filter_map(|op| spec.find(op).map(|pos| (pos, *op)))
+            .min_by_key(|(pos, _)| *pos);
+        let (key, pred) = match op {
+            None => match spec.strip_prefix('!') {
+                Some(key) => (key.trim(), TagPredicate::Missing(key.trim().to_lowercase())),
+                None => (spec, TagPredicate::Exists(spec.to_lowercase())),
+            },
+            Some((pos, op)) => {
+                let key = spec[..pos].trim();
+                let value = spec[pos + op.len()..].trim().to_string();
+                let lower_key = key.to_lowercase();
+                let pred = match op {
+                    "!=" => TagPredicate::NotEquals {
+                        key: lower_key,
+                        value,
+                        case_sensitive,
+                    },
+                    "=" => TagPredicate::Equals {
+                        key: lower_key,
+                        value,
+                        case_sensitive,
+                    },
+                    _ => TagPredicate::Matches {
+                        key: lower_key,
+                        re: RegexBuilder::new(&value)
+                            .case_insensitive(!case_sensitive)
+                            .build()
+                            .map_err(|e| format!("Invalid tag regex in '{spec}': {e}"))?,
+                    },
+                };
+                (key, pred)
+            }
+        };
+        if key.is_empty() {
+            return Err(format!(
+                "Invalid tag predicate '{spec}', expected key, !key, key=value, key!=value or key~regex"
+            ));
+        }
+        Ok(pred)
+    }
+
+    /// Parse a list of specs, failing on the first invalid one.
+    pub fn parse_all<S: AsRef<str>>(
+        specs: &[S],
+        case_sensitive: bool,
+    ) -> Result<Vec<Self>, String> {
+        specs
+            .iter()
+            .map(|s| Self::parse(s.as_ref(), case_sensitive))
+            .collect()
+    }
+
+    pub fn matches(&self, tags: &Tags) -> bool {
+        let value_eq = |key: &str, value: &str, case_sensitive: bool| {
+            tags.kv.get(key).is_some_and(|(v, _)| {
+                if case_sensitive {
+                    v == value
+                } else {
+                    v.to_lowercase() == value.to_lowercase()
+                }
+            })
+        };
+        match self {
+            // an empty value counts as missing, as for `tag:none` and tag-compliance
+            TagPredicate::Exists(key) => tags.get_value(key).is_some(),
+            TagPredicate::Missing(key) => tags.get_value(key).is_none(),
+            TagPredicate::Equals {
+                key,
+                value,
+                case_sensitive,
+            } => value_eq(key, value, *case_sensitive),
+            TagPredicate::NotEquals {
+                key,
+                value,
+                case_sensitive,
+            } => !value_eq(key, value, *case_sensitive),
+            TagPredicate::Matches { key, re } => {
+                tags.kv.get(key).is_some_and(|(v, _)| re.is_match(v))
+            }
+        }
+    }
+
+    /// `true` when every predicate matches (an empty list matches everything).
+    pub fn all_match(preds: &[TagPredicate], tags: &Tags) -> bool {
+        preds.iter().all(|p| p.matches(tags))
+    }
+}
+
+impl fmt::Display for TagPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagPredicate::Exists(key) => write!(f, "{key}"),
+            TagPredicate::Missing(key) => write!(f, "!{key}"),
+            TagPredicate::Equals { key, value, .. } => write!(f, "{key}={value}"),
+            TagPredicate::NotEquals { key, value, .. } => write!(f, "{key}!={value}"),
+            TagPredicate::Matches { key, re } => write!(f, "{key}~{re}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn tags(pairs: &[(&str, &str)]) -> Tags {
+        let kv: HashMap<String, (String, String)> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_lowercase(), (v.to_string(), k.to_string())))
+            .collect();
+        Tags {
+            kv,
+            ..Tags::default()
+        }
+    }
+
+    fn check(spec: &str, t: &Tags) -> bool {
+        TagPredicate::parse(spec, false).unwrap().matches(t)
+    }
+
+    #[test]
+    fn exists_and_missing_use_lowercase_keys() {
+        let t = tags(&[("Environment", "Prod")]);
+        assert!(check("environment", &t));
+        assert!(check("ENVIRONMENT", &t));
+        assert!(!check("owner", &t));
+        assert!(check("!owner", &t));
+        assert!(!check("!Environment", &t));
+    }
+
+    #[test]
+    fn empty_value_is_missing() {
+        let t = tags(&[("owner", ""), ("env", "prod")]);
+        assert!(!check("owner", &t));
+        assert!(check("!owner", &t));
+        assert!(check("env", &t));
+    }
+
+    #[test]
+    fn equals_and_not_equals() {
+        let t = tags(&[("owner", "Alice"), ("url", "https://x.io/a=b")]);
+        assert!(check("owner=alice", &t));
+        assert!(
+            !TagPredicate::parse("owner=alice", true)
+                .unwrap()
+                .matches(&t)
+        );
+        assert!(!check("owner=alice2", &t), "equals is exact, not substring");
+        assert!(check("owner!=bob", &t));
+        assert!(check("team!=bob", &t), "missing key is not equal");
+        assert!(!check("owner != Alice", &t));
+        assert!(check("url=https://x.io/a=b", &t), "value may contain '='");
+    }
+
+    #[test]
+    fn regex_on_value_only() {
+        let t = tags(&[("env", "production"), ("note", "env prod")]);
+        assert!(check("env~^prod", &t));
+        assert!(!check("note~^prod", &t));
+        assert!(!check("missing~.*", &t));
+        assert!(TagPredicate::parse("env~(", false).is_err());
+    }
+
+    #[test]
+    fn parse_errors_and_display() {
+        assert!(TagPredicate::parse("=prod", false).is_err());
+        assert!(TagPredicate::parse("!", false).is_err());
+        let p = TagPredicate::parse_all(&["Env=prod", "!owner", "cc~^4"], false).unwrap();
+        let shown: Vec<String> = p.iter().map(|p| p.to_string()).collect();
+        assert_eq!(shown, vec!["env=prod", "!owner", "cc~^4"]);
+        let t = tags(&[("env", "prod"), ("cc", "4711")]);
+        assert!(TagPredicate::all_match(&p, &t));
+        assert!(TagPredicate::all_match(&[], &t));
+    }
+}
@@ -4,18 +4,205 @@ use std::collections::HashMap;
 //use std::path::{Path, PathBuf};
 
 // Tag data deserialized from the CSV file
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Tags {
     // for each lowercase key, we save the value of the tag and the original key(With case)
     pub kv: HashMap<String, (String, String)>,
     pub value: String,
+    /// Set when the raw tag string could not be fully parsed; `kv` then holds
+    /// the pairs recovered before the error. Reported with the CSV line number
+    /// by `Bills::parse_csv_from_reader`.
+    pub parse_error: Option<String>,
+    /// Lowercase keys in `kv` that were filled by `Bills::inherit_tags` rather
+    /// than set on the resource, with where they came from.
+    pub inherited: HashMap<String, TagOrigin>,
 }
+
+/// Source of an inherited tag value, see `tag_inherit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum TagOrigin {
+    /// Resource group entry of the inheritance file.
+    ResourceGroup,
+    /// Subscription entry of the inheritance file.
+    Subscription,
+    /// Most common value of the other resources in the same resource group.
+    Dominant,
+}
+impl TagOrigin {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TagOrigin::ResourceGroup => "rg",
+            TagOrigin::Subscription => "sub",
+            TagOrigin::Dominant => "dominant",
+        }
+    }
+}
+
 impl Tags {
     pub fn to_lowercase(&mut self) -> Tags {
         Tags {
             kv: self.kv.clone(),
             value: self.value.to_lowercase(),
+            parse_error: self.parse_error.clone(),
+            inherited: self.inherited.clone(),
+        }
+    }
+
+    /// `true` when `key` is set on the resource itself, not inherited.
+    pub fn is_explicit(&self, key: &str) -> bool {
+        self.get_value(key).is_some() && !self.inherited.contains_key(key)
+    }
+
+    /// Value of the lowercase tag `key`, `None` when the tag is missing or empty.
+    pub fn get_value(&self, key: &str) -> Option<&str> {
+        self.kv
+            .get(key)
+            .map(|(v, _)| v.as_str())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Name used for `CostType::Tag` aggregation of the lowercase tag `key`:
+    /// `tag:{OrigKey}={value}`, or `tag:none` when the tag is missing or empty.
+    /// Inherited values get an ` (inherited:{origin})` suffix so they show as separate rows.
+    pub fn summary_key(&self, key: &str) -> String {
+        match (self.kv.get(key), self.inherited.get(key)) {
+            (Some((v, orig_key)), None) if !v.is_empty() => format!("tag:{orig_key}={v}"),
+            (Some((v, orig_key)), Some(origin)) if !v.is_empty() => {
+                format!("tag:{orig_key}={v} (inherited:{})", origin.as_str())
+            }
+            _ => "tag:none".to_string(),
+        }
+    }
+
+    /// Parse a raw tag string into `Tags`.
+    ///
+    /// Two formats are accepted:
+    /// - JSON object (newer exports): `{"k": "v", "k2": 3}` — non-string values are kept as JSON text.
+    /// - Legacy: `"k": "v","k2": "v2"` — i.e. a JSON object without the braces, read
+    ///   by a tolerant scanner that also reads trailing commas and unquoted values,
+    ///   and sets `parse_error` when it has to stop early (e.g. an unterminated quote).
+    ///
+    /// Commas and colons inside quoted keys/values are preserved, `\"` and `\\` are unescaped;
+    /// any other backslash in a legacy value is kept as is (`C:\temp`).
+    pub fn parse(raw: &str) -> Tags {
+        let trimmed = raw.trim();
+        let (pairs, parse_error) = if trimmed.is_empty() {
+            (Vec::new(), None)
+        } else if trimmed.starts_with('{') {
+            match parse_json_object(trimmed) {
+                Ok(pairs) => (pairs, None),
+                Err(e) => {
+                    let inner = trimmed.trim_start_matches('{').trim_end_matches('}');
+                    let (pairs, _) = scan_legacy(inner);
+                    (pairs, Some(format!("invalid JSON tags: {e}")))
+                }
+            }
+        } else {
+            // not through serde_json: a literal `C:\temp` would come back with a TAB
+            scan_legacy(trimmed)
+        };
+        let mut kv = HashMap::with_capacity(pairs.len());
+        for (k, v) in pairs {
+            // we save the value and original(case) key as a tuple
+            kv.insert(k.to_lowercase(), (v, k));
+        }
+        Tags {
+            kv,
+            value: raw.to_lowercase(),
+            parse_error,
+            inherited: HashMap::new(),
+        }
+    }
+}
+
+fn parse_json_object(s: &str) -> Result<Vec<(String, String)>, serde_json::Error> {
+    let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(s)?;
+    Ok(map
+        .into_iter()
+        .map(|(k, v)| match v {
+            serde_json::Value::String(s) => (k, s),
+            other => (k, other.to_string()),
+        })
+        .collect())
+}
+
+/// Tolerant scanner for the legacy `"k": "v","k2": "v2"` format.
+/// Returns the pairs it could read and the first problem found, if any.
+fn scan_legacy(s: &str) -> (Vec<(String, String)>, Option<String>) {
+    let chars: Vec<char> = s.chars().collect();
+    let mut pos = 0;
+    let mut pairs = Vec::new();
+    let skip_ws = |pos: &mut usize| {
+        while *pos < chars.len() && chars[*pos].is_whitespace() {
+            *pos += 1;
         }
+    };
+    // quoted string with escapes, or bare text up to `stop`
+    let read_token = |pos: &mut usize, stop: char| -> Result<String, String> {
+        skip_ws(pos);
+        let mut out = String::new();
+        if *pos < chars.len() && chars[*pos] == '"' {
+            let start = *pos;
+            *pos += 1;
+            loop {
+                match chars.get(*pos) {
+                    None => return Err(format!("unterminated quote at char {start}")),
+                    Some('\\') if matches!(chars.get(*pos + 1), Some('"' | '\\')) => {
+                        out.push(chars[*pos + 1]);
+                        *pos += 2;
+                    }
+                    Some('"') => {
+                        *pos += 1;
+                        return Ok(out);
+                    }
+                    Some(c) => {
+                        out.push(*c);
+                        *pos += 1;
+                    }
+                }
+            }
+        }
+        while *pos < chars.len() && chars[*pos] != stop {
+            out.push(chars[*pos]);
+            *pos += 1;
+        }
+        Ok(out.trim().to_string())
+    };
+    loop {
+        skip_ws(&mut pos);
+        while pos < chars.len() && chars[pos] == ',' {
+            pos += 1;
+            skip_ws(&mut pos);
+        }
+        if pos >= chars.len() {
+            return (pairs, None);
+        }
+        let key = match read_token(&mut pos, ':') {
+            Ok(k) => k,
+            Err(e) => return (pairs, Some(e)),
+        };
+        skip_ws(&mut pos);
+        if chars.get(pos) != Some(&':') {
+            return (pairs, Some(format!("expected ':' after key '{key}'")));
+        }
+        pos += 1;
+        let value = match read_token(&mut pos, ',') {
+            Ok(v) => v,
+            Err(e) => return (pairs, Some(e)),
+        };
+        skip_ws(&mut pos);
+        if pos < chars.len() && chars[pos] != ',' {
+            return (
+                pairs,
+                Some(format!(
+                    "unexpected text after value of '{key}' at char {pos}"
+                )),
+            );
+        }
+        if key.is_empty() {
+            return (pairs, Some("empty tag key".to_string()));
+        }
+        pairs.push((key, value));
     }
 }
 
@@ -28,35 +215,79 @@ impl<'de> Deserialize<'de> for Tags {
         // Deserialize the input into a string
         // e.g. '"JenkinsManagedTag": "ManagedByAzureVMAgents","JenkinsTemplateTag": "build-agent-azure"'
         let s = String::deserialize(deserializer)?;
+        // Never fail the whole row on bad tags, parse_error is reported by the caller.
+        Ok(Tags::parse(&s))
+    }
+}
 
-        // Initialize a HashMap to hold the parsed tags
-        let mut kv = HashMap::new();
-
-        // Split the string by commas to separate each key-value pair
-        for part in s.split(',') {
-            // Split each pair by the colon to separate key and value.
-            //let mut iter = part.split(": ");
-            if let Some((key, value)) = part.split_once(": ") {
-                // Trim quotes and whitespace and insert into the HashMap
-                let k = key
-                    .trim_start_matches('"')
-                    .trim_end_matches('"')
-                    .to_string(); // Search also done in lowercase
-                let v = value
-                    .trim_start_matches('"')
-                    .trim_end_matches('"')
-                    .to_string();
-
-                // we save the value and original(case) key as a tuple
-                kv.insert(k.to_lowercase(), (v, k));
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        //println!("kv: {:?}", kv);
-        // Return the Tags struct with the populated HashMap
-        Ok(Tags {
-            kv,
-            value: s.to_lowercase(),
-        })
+    fn get<'a>(t: &'a Tags, key: &str) -> &'a str {
+        &t.kv.get(key).unwrap_or_else(|| panic!("{key} missing")).0
+    }
+
+    #[test]
+    fn legacy_format_keeps_commas_and_colons_in_values() {
+        let t = Tags::parse(
+            r#""Owner": "alice, bob","Url": "https://example.com:8080/a","Env": "prod""#,
+        );
+        assert_eq!(t.parse_error, None);
+        assert_eq!(t.kv.len(), 3);
+        assert_eq!(get(&t, "owner"), "alice, bob");
+        assert_eq!(get(&t, "url"), "https://example.com:8080/a");
+        assert_eq!(t.kv.get("env").unwrap().1, "Env", "original key case kept");
+    }
+
+    #[test]
+    fn json_object_with_escapes_and_non_string_values() {
+        let t =
+            Tags::parse(r#"{"note": "say \"hi\"", "count": 3, "list": ["a","b"], "flag": true}"#);
+        assert_eq!(t.parse_error, None);
+        assert_eq!(get(&t, "note"), r#"say "hi""#);
+        assert_eq!(get(&t, "count"), "3");
+        assert_eq!(get(&t, "list"), r#"["a","b"]"#);
+        assert_eq!(get(&t, "flag"), "true");
+    }
+
+    #[test]
+    fn empty_string_has_no_tags() {
+        let t = Tags::parse("");
+        assert!(t.kv.is_empty());
+        assert_eq!(t.parse_error, None);
+    }
+
+    #[test]
+    fn legacy_backslashes_are_not_json_escapes() {
+        let t = Tags::parse(r#""path": "C:\temp\new","note": "say \"hi\"""#);
+        assert_eq!(t.parse_error, None);
+        assert_eq!(get(&t, "path"), r"C:\temp\new");
+        assert_eq!(get(&t, "note"), r#"say "hi""#);
+    }
+
+    #[test]
+    fn tolerant_scan_recovers_non_json_legacy() {
+        // trailing comma and unquoted value are not JSON but are readable
+        let t = Tags::parse(r#""env": "prod","team": ops,"#);
+        assert_eq!(t.parse_error, None);
+        assert_eq!(get(&t, "env"), "prod");
+        assert_eq!(get(&t, "team"), "ops");
+    }
+
+    #[test]
+    fn unparseable_tags_report_error_and_keep_prefix() {
+        let t = Tags::parse(r#""env": "prod","owner": "ali"#);
+        assert_eq!(get(&t, "env"), "prod");
+        assert!(!t.kv.contains_key("owner"));
+        assert!(t.parse_error.unwrap().contains("unterminated quote"));
+
+        let t = Tags::parse(r#"{"env": "prod""#);
+        assert_eq!(get(&t, "env"), "prod");
+        assert!(t.parse_error.unwrap().starts_with("invalid JSON tags"));
+
+        let t = Tags::parse("justtext");
+        assert!(t.kv.is_empty());
+        assert!(t.parse_error.is_some());
     }
 }
@@ -0,0 +1,111 @@
+//! Shared unit test fixtures: fluent `BillEntry` setters, `bills(rows)` and `close`.
+//!
+//! ```ignore
+//! let b = bills(vec![row().rg("rg-a").tags(r#""env": "prod""#).cost(10.0)]);
+//! ```
+
+use crate::bills::Bills;
+use crate::bills::bill_entry::BillEntry;
+use crate::bills::tags::Tags;
+use crate::money::{Nzd, Usd};
+
+/// An empty row, fill it in with the setters below.
+pub(crate) fn row() -> BillEntry {
+    BillEntry::default()
+}
+
+impl BillEntry {
+    /// Resource ID, with the resource name set to its last path segment.
+    pub(crate) fn id(mut self, id: &str) -> Self {
+        self.resource_id = id.to_string();
+        self.resource_name = id.rsplit('/').next().unwrap_or(id).to_string();
+        self
+    }
+
+    pub(crate) fn name(mut self, name: &str) -> Self {
+        self.resource_name = name.to_string();
+        self
+    }
+
+    pub(crate) fn rg(mut self, rg: &str) -> Self {
+        self.resource_group = rg.to_string();
+        self
+    }
+
+    pub(crate) fn sub(mut self, sub: &str) -> Self {
+        self.subscription_name = sub.to_string();
+        self
+    }
+
+    pub(crate) fn location(mut self, location: &str) -> Self {
+        self.resource_location = location.to_string();
+        self
+    }
+
+    pub(crate) fn section(mut self, section: &str) -> Self {
+        self.invoice_section = section.to_string();
+        self
+    }
+
+    /// Meter category.
+    pub(crate) fn meter(mut self, meter: &str) -> Self {
+        self.meter_category = meter.to_string();
+        self
+    }
+
+    pub(crate) fn meter_name(mut self, meter: &str) -> Self {
+        self.meter_name = meter.to_string();
+        self
+    }
+
+    pub(crate) fn date(mut self, date: &str) -> Self {
+        self.date = date.to_string();
+        self
+    }
+
+    pub(crate) fn charge_type(mut self, charge_type: &str) -> Self {
+        self.charge_type = charge_type.to_string();
+        self
+    }
+
+    pub(crate) fn quantity(mut self, quantity: f64) -> Self {
+        self.quantity = quantity;
+        self
+    }
+
+    /// Tags column as it appears in the CSV, e.g. `"env": "prod","team": "a"`.
+    pub(crate) fn tags(mut self, tags: &str) -> Self {
+        self.tags = Tags::parse(tags);
+        self
+    }
+
+    /// NZ$ cost, with the US$ cost at 2 NZD per USD unless `usd` sets it.
+    pub(crate) fn cost(mut self, nzd: f64) -> Self {
+        self.cost = Nzd(nzd);
+        self.cost_usd = Usd(nzd / 2.0);
+        self
+    }
+
+    pub(crate) fn usd(mut self, usd: f64) -> Self {
+        self.cost_usd = Usd(usd);
+        self
+    }
+}
+
+/// An NZD bill of `rows`, tag names and totals filled in as after loading a CSV.
+pub(crate) fn bills(rows: Vec<BillEntry>) -> Bills {
+    let mut b = Bills {
+        billing_currency: Some("NZD".to_string()),
+        ..Bills::default()
+    };
+    for e in rows {
+        b.tag_names.extend(e.tags.kv.keys().cloned());
+        b.push(e);
+    }
+    b.calc_all_totals();
+    b
+}
+
+pub(crate) fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
@@ -0,0 +1,405 @@
+//! Cost tree for the default report (`--tree`): subscription → resource group →
+//! resource → meter, every node carrying the rolled-up subtotal of its rows.
+//!
+//! With a previous bill each node also carries the previous cost, so deltas show
+//! where in the hierarchy the bill changed; keys only in the previous bill appear
+//! with a cost of 0. `--tree-depth` stops the tree at a level (subtotals still
+//! include everything below) and `--tree-min` folds nodes whose cost, previous
+//! cost and change are all below the threshold into one "other (N items)" node
+//! per parent.
+
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::BillFilter;
+use crate::bills::cost_type_enum::CostType;
+use crate::f64_to_currency;
+
+/// Tree levels, outermost first.
+pub const LEVELS: [CostType; 4] = [
+    CostType::Subscription,
+    CostType::ResourceGroup,
+    CostType::ResourceName,
+    CostType::MeterName,
+];
+
+/// Depth and threshold of the tree.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeOpts {
+    /// Levels shown, 1 (subscriptions only) to 4 (down to meters).
+    pub depth: usize,
+    /// Nodes with |cost|, |previous| and |change| all below this are folded.
+    pub min_cost: f64,
+}
+
+/// One node; the root is the filtered total.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreeNode {
+    pub key: String,
+    pub cost: f64,
+    /// Previous bill's cost, set when comparing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<f64>,
+    /// Nodes folded into this "other (N items)" node by `min_cost`; 0 for a normal node.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub other_count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl TreeNode {
+    pub fn delta(&self) -> Option<f64> {
+        self.previous.map(|p| self.cost - p)
+    }
+}
+
+#[derive(Default)]
+struct Acc {
+    cost: f64,
+    previous: f64,
+    children: HashMap<String, Acc>,
+}
+
+impl Acc {
+    fn add(&mut self, keys: &[String], cost: f64, is_previous: bool) {
+        if is_previous {
+            self.previous += cost;
+        } else {
+            self.cost += cost;
+        }
+        if let Some((first, rest)) = keys.split_first() {
+            self.children
+                .entry(first.clone())
+                .or_default()
+                .add(rest, cost, is_previous);
+        }
+    }
+
+    fn into_node(self, key: String, compare: bool, min_cost: f64) -> TreeNode {
+        let mut children: Vec<TreeNode> = self
+            .children
+            .into_iter()
+            .map(|(k, acc)| acc.into_node(k, compare, min_cost))
+            .collect();
+        children.sort_by(|a, b| {
+            b.cost
+                .total_cmp(&a.cost)
+                .then_with(|| {
+                    b.previous
+                        .unwrap_or(0.0)
+                        .total_cmp(&a.previous.unwrap_or(0.0))
+                })
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        let (mut shown, folded): (Vec<TreeNode>, Vec<TreeNode>) =
+            children.into_iter().partition(|c| {
+                c.cost.abs() >= min_cost
+                    || c.previous.unwrap_or(0.0).abs() >= min_cost
+                    || c.delta().unwrap_or(0.0).abs() >= min_cost
+            });
+        if !folded.is_empty() {
+            let n = folded.len();
+            shown.push(TreeNode {
+                key: format!("other ({n} item{})", if n == 1 { "" } else { "s" }),
+                cost: folded.iter().map(|c| c.cost).sum(),
+                previous: compare.then(|| folded.iter().filter_map(|c| c.previous).sum()),
+                other_count: n,
+                children: Vec::new(),
+            });
+        }
+        TreeNode {
+            key,
+            cost: self.cost,
+            previous: compare.then_some(self.previous),
+            other_count: 0,
+            children: shown,
+        }
+    }
+}
+
+/// Build the tree of `latest` (and `previous`) rows passing `filter`.
+pub fn build_tree(
+    latest: &Bills,
+    previous: Option<&Bills>,
+    filter: &BillFilter,
+    opts: TreeOpts,
+) -> TreeNode {
+    let depth = opts.depth.clamp(1, LEVELS.len());
+    let mut root = Acc::default();
+    let bills = std::iter::once((latest, false)).chain(previous.map(|p| (p, true)));
+    for (bills, is_previous) in bills {
+        for bill in bills.bills.iter().filter(|b| filter.matches(b)) {
+            let keys: Vec<String> = LEVELS[..depth].iter().map(|t| t.key_of(bill, "")).collect();
+            root.add(&keys, bill.cost.amount(), is_previous);
+        }
+    }
+    root.into_node("Total".to_string(), previous.is_some(), opts.min_cost)
+}
+
+fn money(cur: &str, value: f64) -> String {
+    format!("{cur} {}", f64_to_currency(value, 2))
+}
+
+/// Print the tree with box-drawing branches; changes are red (up) or green (down).
+pub fn print_tree(root: &TreeNode, cur: &str) {
+    let compare = root.previous.is_some();
+    if compare {
+        println!(
+            "{:>16} {:>16} {:>16}  subscription / resource group / resource / meter",
+            "cost", "previous", "change"
+        );
+    } else {
+        println!(
+            "{:>16}  subscription / resource group / resource / meter",
+            "cost"
+        );
+    }
+    fn line(node: &TreeNode, label: &str, cur: &str) {
+        let cost = money(cur, node.cost);
+        match (node.previous, node.delta()) {
+            (Some(prev), Some(delta)) => {
+                let change = money(cur, delta);
+                let change = if delta > 0.0 {
+                    change.red()
+                } else if delta < 0.0 {
+                    change.green()
+                } else {
+                    change.normal()
+                };
+                println!("{cost:>16} {:>16} {change:>16}  {label}", money(cur, prev));
+            }
+            _ => println!("{cost:>16}  {label}"),
+        }
+    }
+    fn walk(node: &TreeNode, prefix: &str, cur: &str) {
+        for (i, child) in node.children.iter().enumerate() {
+            let last = i + 1 == node.children.len();
+            let branch = if last { "└─ " } else { "├─ " };
+            let label = if child.children.is_empty() {
+                child.key.clone()
+            } else {
+                child.key.bold().to_string()
+            };
+            line(child, &format!("{prefix}{branch}{label}"), cur);
+            walk(
+                child,
+                &format!("{prefix}{}", if last { "   " } else { "│  " }),
+                cur,
+            );
+        }
+    }
+    line(root, &root.key.bold().to_string(), cur);
+    walk(root, "", cur);
+}
+
+/// One CSV row per node (root last), with the key of every level down to the node.
+pub fn tree_to_csv(root: &TreeNode) -> Result<String, Box<dyn std::error::Error>> {
+    fn walk(
+        node: &TreeNode,
+        keys: &mut Vec<String>,
+        wtr: &mut csv::Writer<Vec<u8>>,
+    ) -> Result<(), csv::Error> {
+        for child in &node.children {
+            keys.push(child.key.clone());
+            record(child, keys, wtr)?;
+            walk(child, keys, wtr)?;
+            keys.pop();
+        }
+        Ok(())
+    }
+    fn record(
+        node: &TreeNode,
+        keys: &[String],
+        wtr: &mut csv::Writer<Vec<u8>>,
+    ) -> Result<(), csv::Error> {
+        let mut row: Vec<String> = vec![keys.len().to_string()];
+        row.extend((0..LEVELS.len()).map(|i| keys.get(i).cloned().unwrap_or_default()));
+        row.push(format!("{:.2}", node.cost));
+        row.push(node.previous.map(|p| format!("{p:.2}")).unwrap_or_default());
+        row.push(node.delta().map(|d| format!("{d:.2}")).unwrap_or_default());
+        wtr.write_record(&row)
+    }
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record([
+        "level",
+        "subscription",
+        "resource_group",
+        "resource",
+        "meter",
+        "cost",
+        "previous",
+        "change",
+    ])?;
+    walk(root, &mut Vec::new(), &mut wtr)?;
+    record(root, &[], &mut wtr)?;
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// Render the tree as a nested Markdown list.
+pub fn tree_to_markdown(root: &TreeNode, cur: &str) -> String {
+    fn item(out: &mut String, node: &TreeNode, depth: usize, cur: &str) {
+        let _ = write!(
+            out,
+            "{:indent$}- {} — {}",
+            "",
+            node.key,
+            money(cur, node.cost),
+            indent = depth * 2
+        );
+        if let (Some(prev), Some(delta)) = (node.previous, node.delta()) {
+            let _ = write!(
+                out,
+                " (previous {}, change {}{})",
+                money(cur, prev),
+                if delta > 0.0 { "+" } else { "" },
+                f64_to_currency(delta, 2)
+            );
+        }
+        out.push('\n');
+        for child in &node.children {
+            item(out, child, depth + 1, cur);
+        }
+    }
+    let mut out = String::new();
+    item(&mut out, root, 0, cur);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    fn opts(depth: usize, min_cost: f64) -> TreeOpts {
+        TreeOpts { depth, min_cost }
+    }
+
+    #[test]
+    fn subtotals_roll_up_and_depth_stops_tree() {
+        let latest = bills(vec![
+            row()
+                .name("vm-1")
+                .sub("prod")
+                .rg("rg-a")
+                .meter_name("D4s")
+                .cost(10.0),
+            row()
+                .name("vm-1")
+                .sub("prod")
+                .rg("rg-a")
+                .meter_name("Disk")
+                .cost(2.0),
+            row()
+                .name("vm-2")
+                .sub("prod")
+                .rg("rg-b")
+                .meter_name("D4s")
+                .cost(5.0),
+            row()
+                .name("vm-3")
+                .sub("dev")
+                .rg("rg-c")
+                .meter_name("D2s")
+                .cost(1.0),
+        ]);
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let root = build_tree(&latest, None, &filter, opts(4, 0.0));
+        assert_eq!(root.cost, 18.0);
+        let prod = &root.children[0];
+        assert_eq!((prod.key.as_str(), prod.cost), ("prod", 17.0));
+        let vm1 = &prod.children[0].children[0];
+        assert_eq!((vm1.key.as_str(), vm1.cost), ("vm-1", 12.0));
+        let meters: Vec<(&str, f64)> = vm1
+            .children
+            .iter()
+            .map(|m| (m.key.as_str(), m.cost))
+            .collect();
+        assert_eq!(meters, vec![("D4s", 10.0), ("Disk", 2.0)]);
+
+        let shallow = build_tree(&latest, None, &filter, opts(2, 0.0));
+        assert!(shallow.children[0].children[0].children.is_empty());
+        assert_eq!(shallow.children[0].children[0].cost, 12.0);
+
+        let csv = tree_to_csv(&shallow).unwrap();
+        assert!(csv.contains("2,prod,rg-a,,,12.00,,\n"));
+        assert!(csv.ends_with("0,,,,,18.00,,\n"));
+    }
+
+    #[test]
+    fn threshold_folds_small_nodes() {
+        let latest = bills(vec![
+            row()
+                .name("vm-1")
+                .sub("prod")
+                .rg("rg-a")
+                .meter_name("D4s")
+                .cost(50.0),
+            row()
+                .name("vm-2")
+                .sub("prod")
+                .rg("rg-b")
+                .meter_name("D4s")
+                .cost(3.0),
+            row()
+                .name("vm-3")
+                .sub("prod")
+                .rg("rg-c")
+                .meter_name("D4s")
+                .cost(2.0),
+        ]);
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let root = build_tree(&latest, None, &filter, opts(2, 10.0));
+        let rgs: Vec<(&str, f64, usize)> = root.children[0]
+            .children
+            .iter()
+            .map(|n| (n.key.as_str(), n.cost, n.other_count))
+            .collect();
+        assert_eq!(rgs, vec![("rg-a", 50.0, 0), ("other (2 items)", 5.0, 2)]);
+    }
+
+    #[test]
+    fn comparison_deltas_include_removed_keys() {
+        let latest = bills(vec![
+            row()
+                .name("vm-1")
+                .sub("prod")
+                .rg("rg-a")
+                .meter_name("D4s")
+                .cost(10.0),
+        ]);
+        let previous = bills(vec![
+            row()
+                .name("vm-1")
+                .sub("prod")
+                .rg("rg-a")
+                .meter_name("D4s")
+                .cost(4.0),
+            row()
+                .name("vm-9")
+                .sub("prod")
+                .rg("rg-old")
+                .meter_name("D4s")
+                .cost(3.0),
+        ]);
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let root = build_tree(&latest, Some(&previous), &filter, opts(2, 0.0));
+        assert_eq!(root.delta(), Some(3.0));
+        let rgs: Vec<(&str, Option<f64>)> = root.children[0]
+            .children
+            .iter()
+            .map(|n| (n.key.as_str(), n.delta()))
+            .collect();
+        assert_eq!(rgs, vec![("rg-a", Some(6.0)), ("rg-old", Some(-3.0))]);
+        let md = tree_to_markdown(&root, "NZ$");
+        assert!(md.contains("  - prod — NZ$ 10.00 (previous NZ$ 7.00, change +3.00)"));
+    }
+}
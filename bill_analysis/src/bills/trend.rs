@@ -0,0 +1,218 @@
+//! Multi-month cost trend — one row per billing month.
+//!
+//! Replaces the old `run-multi-month.sh` loop that scraped the
+//! "Total cost NZ$" line from the default report.
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::bills::BillFilter;
+use crate::bills::cost_query::round2;
+use crate::bills::repository::BillRepository;
+use crate::find_files;
+
+/// Totals for one billing month, restricted to the rows matching the filter.
+#[derive(Debug, Serialize)]
+pub struct TrendRow {
+    /// `"YYYY-MM"`
+    pub month: String,
+    /// Total cost in the billing currency (NZD).
+    pub total_cost: f64,
+    pub total_cost_usd: f64,
+    pub savings_used_usd: f64,
+    pub savings_unused_usd: f64,
+    /// What the filtered rows would have cost without any reservation.
+    pub no_reservation_usd: f64,
+    pub row_count: usize,
+}
+
+/// Normalise a `YYYY-MM` or `YYYYMM` month argument to `"YYYY-MM"`.
+pub fn normalise_month(month: &str) -> Result<String, String> {
+    match find_files::parse_year_month_path(std::path::Path::new(month)) {
+        Some((year, mon)) if (1..=12).contains(&mon) => Ok(format!("{year:04}-{mon:02}")),
+        _ => Err(format!("Invalid month '{month}', expected YYYY-MM")),
+    }
+}
+
+/// The month before `year`-`mon` as `(year, month)`; `None` before year 0.
+pub fn previous_month(year: u32, mon: u32) -> Option<(u32, u32)> {
+    if mon <= 1 {
+        Some((year.checked_sub(1)?, 12))
+    } else {
+        Some((year, mon - 1))
+    }
+}
+
+/// Keep the `"YYYY-MM"` months that fall within `from..=to` (either bound optional).
+pub fn select_months(available: Vec<String>, from: Option<&str>, to: Option<&str>) -> Vec<String> {
+    available
+        .into_iter()
+        .filter(|m| from.is_none_or(|f| m.as_str() >= f) && to.is_none_or(|t| m.as_str() <= t))
+        .collect()
+}
+
+/// Load each month through `repo` and compute its filtered totals.
+pub async fn monthly_trend(
+    repo: &BillRepository,
+    months: &[String],
+    filter: &BillFilter,
+) -> Result<Vec<TrendRow>, String> {
+    let mut rows = Vec::with_capacity(months.len());
+    for month in months {
+        let (year, mon) = find_files::parse_year_month_path(std::path::Path::new(month))
+            .ok_or_else(|| format!("Invalid month '{month}', expected YYYY-MM"))?;
+        let bills = repo.get(year, mon).await?;
+        let row_count = bills.bills.iter().filter(|b| filter.matches(b)).count();
+        let summary = bills.calc_filtered_totals(filter);
+        rows.push(TrendRow {
+            month: month.clone(),
+            total_cost: round2(summary.total_cost.amount()),
+            total_cost_usd: round2(summary.total_cost_usd.amount()),
+            savings_used_usd: round2(summary.total_savings_used.amount()),
+            savings_unused_usd: round2(summary.total_savings_un_used.amount()),
+            no_reservation_usd: round2(summary.total_no_reservation.amount()),
+            row_count,
+        });
+    }
+    Ok(rows)
+}
+
+/// Render rows as CSV with a header line.
+pub fn trend_to_csv(rows: &[TrendRow]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        wtr.serialize(row)?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+/// Print rows as a coloured terminal table.
+pub fn print_trend(rows: &[TrendRow]) {
+    println!(
+        "{:<8} {:>16} {:>16} {:>14} {:>14} {:>16}",
+        "month", "total_cost", "total_cost_usd", "res_save", "res_unused", "no_reservation"
+    );
+    for row in rows {
+        println!(
+            "{month:<8} {cost:>16} {cost_usd:>16} {save:>14} {unused:>14} {no_res:>16}",
+            month = row.month,
+            cost = format!("{:.2}", row.total_cost).red().bold(),
+            cost_usd = format!("{:.2}", row.total_cost_usd),
+            save = format!("{:.2}", row.savings_used_usd).yellow(),
+            unused = format!("{:.2}", row.savings_unused_usd).red(),
+            no_res = format!("{:.2}", row.no_reservation_usd),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd_parse::FilterOpts;
+
+    fn setup_months(tmp: &tempfile::TempDir) {
+        std::fs::copy(
+            "tests/azure_test_nzd_usd_prev.csv",
+            tmp.path().join("2025-12-Detail_test.csv"),
+        )
+        .unwrap();
+        std::fs::copy(
+            "tests/azure_test_nzd_usd_latest.csv",
+            tmp.path().join("2026-01-Detail_test.csv"),
+        )
+        .unwrap();
+    }
+
+    fn no_filter() -> BillFilter {
+        BillFilter::match_all(&FilterOpts::default())
+    }
+
+    #[test]
+    fn normalise_month_accepts_both_formats() {
+        assert_eq!(normalise_month("2024-03").unwrap(), "2024-03");
+        assert_eq!(normalise_month("202403").unwrap(), "2024-03");
+        assert!(normalise_month("2024-13").is_err());
+        assert!(normalise_month("march").is_err());
+    }
+
+    #[test]
+    fn previous_month_wraps_january() {
+        assert_eq!(previous_month(2026, 4), Some((2026, 3)));
+        assert_eq!(previous_month(2026, 1), Some((2025, 12)));
+        assert_eq!(previous_month(0, 1), None);
+    }
+
+    #[test]
+    fn select_months_applies_inclusive_bounds() {
+        let months: Vec<String> = ["2024-01", "2024-02", "2024-03", "2024-04"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            select_months(months.clone(), Some("2024-02"), Some("2024-03")),
+            vec!["2024-02", "2024-03"]
+        );
+        assert_eq!(select_months(months, None, None).len(), 4);
+    }
+
+    #[tokio::test]
+    async fn trend_one_row_per_month() {
+        let tmp = tempfile::tempdir().unwrap();
+        setup_months(&tmp);
+        let repo = BillRepository::new(tmp.path().to_path_buf(), None);
+        let months = repo.list_months().await;
+        let rows = monthly_trend(&repo, &months, &no_filter()).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].month, "2025-12");
+        assert_eq!(rows[0].total_cost, 120.0);
+        assert_eq!(rows[0].total_cost_usd, 72.0);
+        assert_eq!(rows[1].month, "2026-01");
+        assert_eq!(rows[1].total_cost, 150.0);
+        assert_eq!(rows[1].row_count, 2);
+    }
+
+    #[tokio::test]
+    async fn trend_applies_bill_filter() {
+        let tmp = tempfile::tempdir().unwrap();
+        setup_months(&tmp);
+        let repo = BillRepository::new(tmp.path().to_path_buf(), None);
+        let filter = BillFilter::new(
+            None,
+            Some("rg-delta-test".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &FilterOpts::default(),
+        )
+        .unwrap();
+        let months = repo.list_months().await;
+        let rows = monthly_trend(&repo, &months, &filter).await.unwrap();
+        assert_eq!(rows[0].total_cost, 80.0);
+        assert_eq!(rows[1].total_cost, 100.0);
+        assert_eq!(rows[1].row_count, 1);
+    }
+
+    #[test]
+    fn trend_csv_has_header_and_rows() {
+        let rows = vec![TrendRow {
+            month: "2026-01".to_string(),
+            total_cost: 150.0,
+            total_cost_usd: 90.0,
+            savings_used_usd: 0.0,
+            savings_unused_usd: 0.0,
+            no_reservation_usd: 199.39,
+            row_count: 2,
+        }];
+        let csv = trend_to_csv(&rows).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "month,total_cost,total_cost_usd,savings_used_usd,savings_unused_usd,no_reservation_usd,row_count"
+        );
+        assert_eq!(lines.next().unwrap(), "2026-01,150.0,90.0,0.0,0.0,199.39,2");
+    }
+}
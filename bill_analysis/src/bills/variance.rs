@@ -0,0 +1,319 @@
+//! Cost variance decomposition — why did the bill change between two months?
+//!
+//! Builds on the resource+meter join from `bill_diff::diff_bills` and splits
+//! each record's NZD delta into:
+//!
+//! - **volume**: change in `quantity` at the previous unit price and rate
+//! - **price**: change in unit price (USD per unit) at the latest quantity
+//! - **fx**: change in the bill-level USD→NZD rate (`Summary::exchange_rate`)
+//!   applied to the latest USD cost
+//! - **conversion**: difference between the row-level NZD costs and the
+//!   bill-level rate conversion (rounding, mid-month rate changes)
+//! - **new / removed resources**: resources that only appear in one bill
+//!
+//! The components sum exactly to the filtered total delta printed by
+//! `display_cost_by_filter`.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ops::AddAssign;
+
+use crate::bills::Bills;
+use crate::bills::bill_diff::{ChangeKind, DiffRecord, diff_bills};
+use crate::bills::bill_filter::BillFilter;
+
+/// Delta components in the billing currency (NZD).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct VarianceComponents {
+    pub volume: f64,
+    pub price: f64,
+    pub fx: f64,
+    pub conversion: f64,
+    pub new_resources: f64,
+    pub removed_resources: f64,
+}
+
+impl VarianceComponents {
+    pub fn total(&self) -> f64 {
+        self.volume
+            + self.price
+            + self.fx
+            + self.conversion
+            + self.new_resources
+            + self.removed_resources
+    }
+}
+
+impl AddAssign for VarianceComponents {
+    fn add_assign(&mut self, rhs: Self) {
+        self.volume += rhs.volume;
+        self.price += rhs.price;
+        self.fx += rhs.fx;
+        self.conversion += rhs.conversion;
+        self.new_resources += rhs.new_resources;
+        self.removed_resources += rhs.removed_resources;
+    }
+}
+
+/// Result of [`decompose_variance`]. Group lists are sorted by absolute total, largest first.
+#[derive(Debug, Serialize)]
+pub struct VarianceBreakdown {
+    pub latest_total: f64,
+    pub prev_total: f64,
+    /// USD→NZD rate of each bill; `1.0` when the bills carry no USD costs.
+    pub latest_rate: f64,
+    pub prev_rate: f64,
+    pub total: VarianceComponents,
+    pub by_meter_category: Vec<(String, VarianceComponents)>,
+    pub by_resource_group: Vec<(String, VarianceComponents)>,
+}
+
+impl VarianceBreakdown {
+    pub fn delta(&self) -> f64 {
+        self.latest_total - self.prev_total
+    }
+}
+
+/// Pricing-currency view of one side of a record: (amount, quantity, rate).
+struct Priced {
+    amount: f64,
+    quantity: f64,
+    rate: f64,
+}
+
+fn split_record(
+    r: &DiffRecord,
+    prev: Priced,
+    latest: Priced,
+    resource_in_both: bool,
+) -> VarianceComponents {
+    let mut c = VarianceComponents::default();
+    if !resource_in_both {
+        match r.kind {
+            ChangeKind::Added => c.new_resources = r.delta,
+            _ => c.removed_resources = r.delta,
+        }
+        return c;
+    }
+    // Previous unit price; a meter new to an existing resource is priced at
+    // the latest unit price so its whole cost counts as volume.
+    let unit_price_prev = if prev.quantity != 0.0 {
+        prev.amount / prev.quantity
+    } else if latest.quantity != 0.0 {
+        latest.amount / latest.quantity
+    } else {
+        0.0
+    };
+    c.volume = (latest.quantity - prev.quantity) * unit_price_prev * prev.rate;
+    c.price = (latest.amount - prev.amount) * prev.rate - c.volume;
+    c.fx = latest.amount * (latest.rate - prev.rate);
+    c.conversion = r.delta - (latest.amount * latest.rate - prev.amount * prev.rate);
+    c
+}
+
+fn sorted_groups(map: HashMap<String, VarianceComponents>) -> Vec<(String, VarianceComponents)> {
+    let mut groups: Vec<(String, VarianceComponents)> = map.into_iter().collect();
+    groups.sort_by(|a, b| {
+        b.1.total()
+            .abs()
+            .partial_cmp(&a.1.total().abs())
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    groups
+}
+
+/// Decompose the filtered cost delta `latest - prev` into volume, price, FX,
+/// conversion and resource churn, per meter category and per resource group.
+///
+/// When either bill has no USD costs (old export format) the billing currency
+/// is used as the pricing currency with a rate of `1.0`, so `fx` is zero.
+pub fn decompose_variance(latest: &Bills, prev: &Bills, filter: &BillFilter) -> VarianceBreakdown {
+    let diff = diff_bills(latest, prev, filter, true);
+    let has_usd = latest.summary.exchange_rate > 0.0 && prev.summary.exchange_rate > 0.0;
+    let (latest_rate, prev_rate) = if has_usd {
+        (latest.summary.exchange_rate, prev.summary.exchange_rate)
+    } else {
+        (1.0, 1.0)
+    };
+
+    // A resource is churn only when none of its meters appear in the other bill.
+    let mut in_latest: HashSet<&str> = HashSet::new();
+    let mut in_prev: HashSet<&str> = HashSet::new();
+    for r in &diff.records {
+        if r.kind != ChangeKind::Removed {
+            in_latest.insert(&r.resource_id);
+        }
+        if r.kind != ChangeKind::Added {
+            in_prev.insert(&r.resource_id);
+        }
+    }
+
+    let mut total = VarianceComponents::default();
+    let mut by_meter_category: HashMap<String, VarianceComponents> = HashMap::new();
+    let mut by_resource_group: HashMap<String, VarianceComponents> = HashMap::new();
+    for r in &diff.records {
+        let (prev_amount, latest_amount) = if has_usd {
+            (r.prev_cost_usd, r.latest_cost_usd)
+        } else {
+            (r.prev_cost, r.latest_cost)
+        };
+        let c = split_record(
+            r,
+            Priced {
+                amount: prev_amount,
+                quantity: r.prev_quantity,
+                rate: prev_rate,
+            },
+            Priced {
+                amount: latest_amount,
+                quantity: r.latest_quantity,
+                rate: latest_rate,
+            },
+            in_latest.contains(r.resource_id.as_str()) && in_prev.contains(r.resource_id.as_str()),
+        );
+        total += c;
+        *by_meter_category
+            .entry(r.meter_category.clone())
+            .or_default() += c;
+        *by_resource_group
+            .entry(r.resource_group.clone())
+            .or_default() += c;
+    }
+
+    VarianceBreakdown {
+        latest_total: diff.latest_total,
+        prev_total: diff.prev_total,
+        latest_rate,
+        prev_rate,
+        total,
+        by_meter_category: sorted_groups(by_meter_category),
+        by_resource_group: sorted_groups(by_resource_group),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, close, row};
+    use crate::cmd_parse::FilterOpts;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn volume_price_fx_split() {
+        // prev: 100 h at US$1.00, rate 1.5   → NZ$150
+        // latest: 120 h at US$1.10, rate 1.6 → US$132 → NZ$211.2
+        let prev = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg")
+                .quantity(100.0)
+                .cost(150.0)
+                .usd(100.0),
+        ]);
+        let latest = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg")
+                .quantity(120.0)
+                .cost(211.2)
+                .usd(132.0),
+        ]);
+        let v = decompose_variance(
+            &latest,
+            &prev,
+            &BillFilter::match_all(&FilterOpts::default()),
+        );
+        assert!(
+            close(v.total.volume, 20.0 * 1.0 * 1.5),
+            "volume {}",
+            v.total.volume
+        );
+        assert!(
+            close(v.total.price, 120.0 * 0.1 * 1.5),
+            "price {}",
+            v.total.price
+        );
+        assert!(close(v.total.fx, 132.0 * 0.1), "fx {}", v.total.fx);
+        assert!(close(v.total.conversion, 0.0));
+        assert!(close(v.total.total(), v.delta()));
+    }
+
+    #[test]
+    fn churn_is_resource_level() {
+        let prev = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg-a")
+                .quantity(10.0)
+                .cost(15.0)
+                .usd(10.0),
+            row()
+                .id("gone")
+                .rg("rg-b")
+                .quantity(10.0)
+                .cost(6.0)
+                .usd(4.0),
+        ]);
+        let extra_meter = row()
+            .id("vm1")
+            .rg("rg-a")
+            .meter_name("egress")
+            .quantity(5.0)
+            .cost(7.5)
+            .usd(5.0);
+        let latest = bills(vec![
+            row()
+                .id("vm1")
+                .rg("rg-a")
+                .quantity(10.0)
+                .cost(15.0)
+                .usd(10.0),
+            extra_meter,
+            row().id("new").rg("rg-c").quantity(2.0).cost(3.0).usd(2.0),
+        ]);
+        let v = decompose_variance(
+            &latest,
+            &prev,
+            &BillFilter::match_all(&FilterOpts::default()),
+        );
+        assert!(close(v.total.new_resources, 3.0));
+        assert!(close(v.total.removed_resources, -6.0));
+        // New meter on an existing resource is volume, not churn.
+        let rg_a = &v
+            .by_resource_group
+            .iter()
+            .find(|(k, _)| k == "rg-a")
+            .unwrap()
+            .1;
+        assert!(close(rg_a.new_resources, 0.0));
+        assert!(close(rg_a.total(), 7.5));
+        assert!(close(v.total.total(), v.delta()));
+    }
+
+    #[test]
+    fn reconciles_to_filtered_total_delta() {
+        let opts = FilterOpts::default();
+        let mut latest = Bills::default();
+        latest
+            .parse_csv(&PathBuf::from("tests/azure_test_nzd_usd_latest.csv"), &opts)
+            .unwrap();
+        let mut prev = Bills::default();
+        prev.parse_csv(&PathBuf::from("tests/azure_test_nzd_usd_prev.csv"), &opts)
+            .unwrap();
+        let filter = BillFilter::match_all(&opts);
+        let mut summary = latest.cost_by_any_summary(&filter, &[]);
+        summary.merge_summaries(&prev.cost_by_any_summary(&filter, &[]));
+
+        let v = decompose_variance(&latest, &prev, &filter);
+        assert!(close(v.total.total(), summary.filtered_cost_total.amount()));
+        assert!(close(v.total.new_resources, 50.0));
+        assert!(close(v.total.removed_resources, -40.0));
+        let mc_sum: f64 = v.by_meter_category.iter().map(|(_, c)| c.total()).sum();
+        let rg_sum: f64 = v.by_resource_group.iter().map(|(_, c)| c.total()).sum();
+        assert!(close(mc_sum, v.delta()));
+        assert!(close(rg_sum, v.delta()));
+    }
+}
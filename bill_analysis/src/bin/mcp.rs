@@ -23,12 +23,23 @@ use axum::{
 };
 use bill_analysis::{
     bills::{
-        cost_query::{CostQuery, ResourceSearchQuery, query_cost, round2, search_resources},
-        repository::BillRepository,
+        BillFilter,
+        bill_diff::diff_bills,
+        bill_filter::{Exclude, FilterDimension},
+        cost_query::{query_cost_months, round2, search_resources_months},
+        date_range::DateRange,
+        filter_expr::FilterExpr,
+        query::{self, GroupBy, Metric, Query, SortOrder},
+        repository::{BillRepository, RangeBills},
+        tag_alias::TagAliases,
+        tag_inherit::TagInheritance,
+        tag_predicate::TagPredicate,
+        trend::previous_month,
     },
     blob_source::{BlobSource, BlobSourceConfig},
+    cmd_parse::FilterOpts,
 };
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use oauth_proxy::{
     AppState, CallerIdentity, auth_start_handler, auth_wait_handler, authorize_handler,
     callback_handler, load_entra_config, oauth_metadata_handler, oauth_protected_resource_handler,
@@ -316,6 +327,113 @@ fn handle_initialize(req: &RpcRequest) -> RpcResponse {
 // tools/list
 // ---------------------------------------------------------------------------
 
+const MONTH_DESCRIPTION: &str =
+    "Billing month in YYYY-MM format, e.g. '2026-04'. Required unless 'from' or 'to' is given.";
+const FROM_DESCRIPTION: &str = "First usage date to include: YYYY-MM-DD, YYYY-MM, 'today', 'yesterday' or '<N>d' (N days ago, e.g. '90d' for the last 90 days). Every month the range spans is loaded; takes precedence over 'month'. Omit with 'to' to start at the earliest available month.";
+const TO_DESCRIPTION: &str = "Last usage date to include (inclusive), same formats as 'from'; YYYY-MM means the month's last day. Defaults to open-ended.";
+const TAGS_DESCRIPTION: &str = "Structured tag predicates, all must match. Keys are case-insensitive. 'env' = key has a value, '!env' = key missing or empty, 'env=prod' = exact value, 'env!=prod' = missing or different, 'env~^prod' = value regex. Example: ['environment=prod', '!owner'].";
+const EXCLUDE_DESCRIPTION: &str = "Drop rows matching any of these case-insensitive regexes (a string or an array of strings per key). Keys: name, resource_group, subscription, meter_category, location ('none' = rows without a location), reservation, tag (matched against the lowercase tag string), invoice_section, resource_type, meter_name, charge_type, pricing_model, publisher. Example: {'resource_group': '^mc_', 'subscription': 'sandbox'}.";
+const WHERE_DESCRIPTION: &str = "Boolean filter expression ANDed with the other filters. Fields: name, rg, sub, meter, meter_sub, meter_name, location, reservation, invoice, pricing, charge, publisher, resource_id, tag.KEY (text; = != ~ !~ with quoted values, ~ is a case-insensitive regex) and cost, cost_usd, quantity (numeric; = != < <= > >=). Combine with and, or, not and parentheses. A bare tag.KEY tests that the tag exists. Example: 'rg ~ \"prod\" and (tag.env = \"prod\" or sub ~ \"core\") and not meter ~ \"Bandwidth\" and cost > 10'.";
+
+/// Input schema properties of the arguments read by `filter_from_args`, shared by
+/// every tool that filters rows, so a new filter dimension is described once.
+fn filter_properties() -> serde_json::Map<String, Value> {
+    let regex = |what: &str| {
+        json!({
+            "type": "string",
+            "description": format!("Case-insensitive regex matched against {what}. Plain strings match as substrings. Omit to include all rows.")
+        })
+    };
+    let mut props = serde_json::Map::new();
+    for (key, what) in [
+        (
+            "resource_group",
+            "resource group names, e.g. 'prod' matches 'my-prod-eastus-rg'",
+        ),
+        (
+            "resource_name",
+            "resource names; supports alternation, e.g. 'ingenie|eroad'",
+        ),
+        ("subscription", "subscription names, e.g. 'prod|staging'"),
+        (
+            "meter_category",
+            "the Azure meter category, e.g. 'Virtual Machines', 'Storage', 'Virtual Network'",
+        ),
+        (
+            "location",
+            "the resource location; 'none' = rows without a location",
+        ),
+        (
+            "reservation",
+            "the reservation / savings plan (benefit) name",
+        ),
+        ("invoice_section", "the invoice section"),
+        (
+            "resource_type",
+            "the Azure resource type from the ARM resource ID, e.g. 'publicipaddresses', 'disks', 'microsoft.compute/virtualmachines'",
+        ),
+        ("meter_name", "the meter name, e.g. 'D4s v5'"),
+        (
+            "charge_type",
+            "the charge type, e.g. 'Usage', 'Purchase', 'Refund'",
+        ),
+        (
+            "pricing_model",
+            "the pricing model, e.g. 'OnDemand', 'Reservation', 'SavingsPlan', 'Spot'",
+        ),
+        (
+            "publisher",
+            "the publisher name (Marketplace vendors, 'Microsoft')",
+        ),
+        (
+            "tag_filter",
+            "the full tag string, e.g. 'environment.*prod'. Tag string format: '\"Key\": \"Value\",\"Key2\": \"Value2\"'",
+        ),
+    ] {
+        props.insert(key.to_string(), regex(what));
+    }
+    props.insert(
+        "tags".to_string(),
+        json!({ "type": "array", "items": { "type": "string" }, "description": TAGS_DESCRIPTION }),
+    );
+    props.insert(
+        "exclude".to_string(),
+        json!({ "type": "object", "description": EXCLUDE_DESCRIPTION }),
+    );
+    props.insert(
+        "where".to_string(),
+        json!({ "type": "string", "description": WHERE_DESCRIPTION }),
+    );
+    props
+}
+
+/// `month` / `from` / `to` properties read by `load_period`.
+fn period_properties() -> Value {
+    json!({
+        "month": { "type": "string", "description": MONTH_DESCRIPTION },
+        "from": { "type": "string", "description": FROM_DESCRIPTION },
+        "to": { "type": "string", "description": TO_DESCRIPTION }
+    })
+}
+
+/// Object input schema of `properties` plus the shared filter properties.
+fn filtered_schema(properties: Value, required: &[&str]) -> Value {
+    let mut properties = match properties {
+        Value::Object(map) => map,
+        _ => serde_json::Map::new(),
+    };
+    properties.extend(filter_properties());
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// `a` with the properties of `b` added.
+fn merge(mut a: Value, b: Value) -> Value {
+    if let (Value::Object(a), Value::Object(b)) = (&mut a, b) {
+        a.extend(b);
+    }
+    a
+}
+
 fn handle_tools_list(req: &RpcRequest) -> RpcResponse {
     RpcResponse::ok(
         req.id.clone(),
@@ -331,97 +449,72 @@ fn handle_tools_list(req: &RpcRequest) -> RpcResponse {
                 },
                 {
                     "name": "get_monthly_cost",
-                    "description": "Get the total Azure cost in USD for a given billing month. All filters are case-insensitive regexes — plain strings match as substrings, but anchors, alternation (prod|staging), and wildcards (ingenie.*) are all valid. Returns the total cost, row count, and top contributors.",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "month": {
-                                "type": "string",
-                                "description": "Billing month in YYYY-MM format, e.g. '2026-04'."
-                            },
-                            "resource_group": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against resource group names. Plain strings work as substring filters, e.g. 'prod' matches 'my-prod-eastus-rg'. Omit to include all resource groups."
-                            },
-                            "resource_name": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against resource names. Supports alternation, e.g. 'ingenie|eroad'. Omit to include all resources."
-                            },
-                            "tag_filter": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against the full tag string, e.g. 'environment.*prod' matches resources tagged environment=prod. Tag string format: '\"Key\": \"Value\",\"Key2\": \"Value2\"'. Omit to include all resources regardless of tags."
-                            }
-                        },
-                        "required": ["month"]
-                    }
+                    "description": "Get the total Azure cost in USD for a given billing month, or for a from/to usage-date range (e.g. the 10th to the 20th, or the last 90 days) spanning any number of months. All filters are case-insensitive regexes — plain strings match as substrings, but anchors, alternation (prod|staging), and wildcards (ingenie.*) are all valid. Returns the total cost, row count, and top contributors.",
+                    "inputSchema": filtered_schema(period_properties(), &[])
                 },
                 {
                     "name": "get_daily_cost",
                     "description": "Get the total Azure cost in USD for a specific calendar date. All filters are case-insensitive regexes — plain strings match as substrings, anchors and alternation are also valid. The billing CSV uses UTC calendar dates.",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "date": {
-                                "type": "string",
-                                "description": "Calendar date in YYYY-MM-DD format, e.g. '2026-04-07'."
-                            },
-                            "resource_group": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against resource group names. Omit to include all resource groups."
-                            },
-                            "resource_name": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against resource names. Supports alternation, e.g. 'ingenie|eroad'. Omit to include all resources."
-                            },
-                            "tag_filter": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against the full tag string, e.g. 'environment.*prod'. Omit to include all resources regardless of tags."
-                            }
-                        },
-                        "required": ["date"]
-                    }
+                    "inputSchema": filtered_schema(json!({
+                        "date": {
+                            "type": "string",
+                            "description": "Calendar date in YYYY-MM-DD format, e.g. '2026-04-07'."
+                        }
+                    }), &["date"])
                 },
                 {
                     "name": "search_resources",
-                    "description": "Search for individual Azure resources and their costs for a billing month. Returns one row per unique resource (resource_name + resource_group) with cost, meter category, and Azure resource type (extracted from the ARM resource ID). Use meter_category or resource_type to find all resources of a specific kind (e.g. all Public IPs, all Disks). Results are capped at 50 by default, sorted by cost descending. The response includes total_resources and total_cost_usd for the full matched set (before the limit).",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "month": {
-                                "type": "string",
-                                "description": "Billing month in YYYY-MM format, e.g. '2026-04'."
-                            },
-                            "resource_group": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against resource group names. Omit to include all resource groups."
-                            },
-                            "resource_name": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against resource names. Supports alternation, e.g. 'ingenie|eroad'. Omit to include all resources."
-                            },
-                            "meter_category": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against the Azure meter category, e.g. 'Virtual Machines', 'Storage', 'Virtual Network'. Omit to include all categories."
-                            },
-                            "subscription": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against subscription names. Supports alternation, e.g. 'prod|staging'. Omit to include all subscriptions."
-                            },
-                            "resource_type": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against the Azure resource type extracted from the ARM resource ID (e.g. 'publicipaddresses', 'disks', 'microsoft.compute/virtualmachines'). Omit to include all resource types."
-                            },
-                            "tag_filter": {
-                                "type": "string",
-                                "description": "Case-insensitive regex matched against the full tag string, e.g. 'environment.*prod'. Omit to include all resources regardless of tags."
-                            },
-                            "limit": {
-                                "type": "integer",
-                                "description": "Maximum number of resources to return (default 50, max 200). Results are sorted by cost descending."
-                            }
+                    "description": "Search for individual Azure resources and their costs for a billing month or a from/to usage-date range. Returns one row per unique resource (resource_name + resource_group) with cost, meter category, and Azure resource type (extracted from the ARM resource ID). Use meter_category or resource_type to find all resources of a specific kind (e.g. all Public IPs, all Disks). Results are capped at 50 by default, sorted by cost descending. The response includes total_resources and total_cost_usd for the full matched set (before the limit).",
+                    "inputSchema": filtered_schema(merge(period_properties(), json!({
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of resources to return (default 50, max 200). Results are sorted by cost descending."
+                        }
+                    })), &[])
+                },
+                {
+                    "name": "compare_months",
+                    "description": "Compare two billing months resource by resource. Rows are joined by Azure resource ID (optionally also by meter) and each joined resource is classified as added, removed, increased, decreased or unchanged, with absolute and percentage deltas in both billing currency (NZD) and USD. Results are sorted by absolute delta descending and capped by limit; the totals cover the full matched set.",
+                    "inputSchema": filtered_schema(json!({
+                        "month": {
+                            "type": "string",
+                            "description": "Latest billing month in YYYY-MM format, e.g. '2026-04'."
                         },
-                        "required": ["month"]
-                    }
+                        "previous_month": {
+                            "type": "string",
+                            "description": "Billing month to compare against in YYYY-MM format. Defaults to the month before 'month'."
+                        },
+                        "by_meter": {
+                            "type": "boolean",
+                            "description": "Join on resource ID and meter (sub-category + name) instead of resource ID only. Default false."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of changed resources to return (default 50, max 200)."
+                        }
+                    }), &["month"])
+                },
+                {
+                    "name": "query_costs",
+                    "description": "Group the filtered bill rows of a billing month or a from/to usage-date range by one dimension and rank the groups. Uses the same query engine and group keys as the CLI 'query' subcommand and report sections, so the same arguments give the same numbers. Each row has cost (billing currency), cost_usd, quantity and charge row count; totals cover every matched row, total_groups counts groups before the limit.",
+                    "inputSchema": filtered_schema(merge(json!({
+                        "group_by": {
+                            "type": "string",
+                            "description": "Dimension to group by: 'resource' (name + resource group), 'rg', 'subscription', 'meter_category', 'meter_sub_category', 'meter_name', 'reservation', 'location', 'invoice_section', 'resource_type', 'charge_type', 'pricing_model', 'publisher', 'tag:KEY' (e.g. 'tag:environment') or 'none' for totals only. Default 'rg'."
+                        },
+                        "metric": {
+                            "type": "string",
+                            "description": "Value groups are ranked by: 'cost' (billing currency, default), 'cost_usd', 'quantity' or 'rows'."
+                        },
+                        "sort": {
+                            "type": "string",
+                            "description": "'desc' (largest first, default), 'asc' or 'key' (alphabetical by group key)."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of groups to return (default 20, max 200)."
+                        }
+                    }), period_properties()), &[])
                 }
             ]
         }),
@@ -448,6 +541,8 @@ async fn handle_tools_call(req: &RpcRequest, state: &AppState) -> RpcResponse {
         "get_monthly_cost" => tool_get_monthly_cost(args, state).await,
         "get_daily_cost" => tool_get_daily_cost(args, state).await,
         "search_resources" => tool_search_resources(args, state).await,
+        "compare_months" => tool_compare_months(args, state).await,
+        "query_costs" => tool_query_costs(args, state).await,
         _ => Err(format!("Unknown tool: {tool_name}")),
     };
 
@@ -481,42 +576,24 @@ async fn tool_get_monthly_cost(
     state: &AppState,
 ) -> Result<String, String> {
     let args = args.ok_or_else(|| "Missing arguments".to_string())?;
-    let month = args
-        .get("month")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| "Missing required argument 'month'".to_string())?;
-    let rg_filter = args
-        .get("resource_group")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let name_filter = args
-        .get("resource_name")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let tag_filter = args
-        .get("tag_filter")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
+    let filter = filter_from_args(args)?;
 
-    let (year, mon) = parse_year_month(month)?;
-    let bills = state.repo.get(year, mon).await?;
-    let result = query_cost(
-        &bills,
-        &CostQuery {
-            rg_filter: rg_filter.to_string(),
-            name_filter: name_filter.to_string(),
-            tag_filter: tag_filter.to_string(),
-            date_filter: None,
-        },
-    )?;
+    let period = load_period(args, state).await?;
+    let result = query_cost_months(
+        &period.bills.bills(),
+        filter.with_date_range(period.date_range),
+    );
 
-    Ok(serde_json::to_string_pretty(&json!({
+    let mut out = json!({
         "cost_usd": round2(result.cost_usd),
         "row_count": result.row_count,
-        "period": month,
+        "period": period.label,
         "top_contributors": result.top_contributors,
-    }))
-    .unwrap())
+    });
+    if !period.bills.missing.is_empty() {
+        out["missing_months"] = json!(period.bills.missing);
+    }
+    Ok(serde_json::to_string_pretty(&out).unwrap())
 }
 
 // ---------------------------------------------------------------------------
@@ -532,30 +609,12 @@ async fn tool_get_daily_cost(
         .get("date")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing required argument 'date'".to_string())?;
-    let rg_filter = args
-        .get("resource_group")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let name_filter = args
-        .get("resource_name")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let tag_filter = args
-        .get("tag_filter")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
+    let filter = filter_from_args(args)?;
 
     let (year, mon, _day) = parse_date(date_str)?;
     let bills = state.repo.get(year, mon).await?;
-    let result = query_cost(
-        &bills,
-        &CostQuery {
-            rg_filter: rg_filter.to_string(),
-            name_filter: name_filter.to_string(),
-            tag_filter: tag_filter.to_string(),
-            date_filter: Some(date_str.to_string()),
-        },
-    )?;
+    let day = DateRange::parse(Some(date_str), Some(date_str))?;
+    let result = query_cost_months(&[&bills], filter.with_date_range(day));
 
     Ok(serde_json::to_string_pretty(&json!({
         "cost_usd": round2(result.cost_usd),
@@ -573,72 +632,153 @@ async fn tool_get_daily_cost(
 async fn tool_search_resources(
     args: Option<&serde_json::Map<String, Value>>,
     state: &AppState,
+) -> Result<String, String> {
+    let args = args.ok_or_else(|| "Missing arguments".to_string())?;
+    let filter = filter_from_args(args)?;
+    let limit = args
+        .get("limit")
+        .and_then(|v| v.as_u64())
+        .map(|n| (n as usize).min(200));
+
+    let period = load_period(args, state).await?;
+    let result = search_resources_months(
+        &period.bills.bills(),
+        filter.with_date_range(period.date_range),
+        limit,
+    );
+
+    let mut out = json!({
+        "period": period.label,
+        "total_resources": result.total_resources,
+        "total_cost_usd": result.total_cost_usd,
+        "resources": result.resources,
+    });
+    if !period.bills.missing.is_empty() {
+        out["missing_months"] = json!(period.bills.missing);
+    }
+    Ok(serde_json::to_string_pretty(&out).unwrap())
+}
+
+// ---------------------------------------------------------------------------
+// Tool: compare_months
+// ---------------------------------------------------------------------------
+
+async fn tool_compare_months(
+    args: Option<&serde_json::Map<String, Value>>,
+    state: &AppState,
 ) -> Result<String, String> {
     let args = args.ok_or_else(|| "Missing arguments".to_string())?;
     let month = args
         .get("month")
         .and_then(|v| v.as_str())
         .ok_or_else(|| "Missing required argument 'month'".to_string())?;
-
-    let rg_filter = args
-        .get("resource_group")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    let name_filter = args
-        .get("resource_name")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    let tag_filter = args
-        .get("tag_filter")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    let meter_category_filter = args
-        .get("meter_category")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    let subscription_filter = args
-        .get("subscription")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    let resource_type_filter = args
-        .get("resource_type")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
+    let (year, mon) = parse_year_month(month)?;
+    let (prev_year, prev_mon) = match args.get("previous_month").and_then(|v| v.as_str()) {
+        Some(p) => parse_year_month(p)?,
+        None => previous_month(year, mon).ok_or_else(|| format!("No month before '{month}'"))?,
+    };
+    let filter = filter_from_args(args)?;
+    let by_meter = args
+        .get("by_meter")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
     let limit = args
         .get("limit")
         .and_then(|v| v.as_u64())
-        .map(|n| (n as usize).min(200));
-
-    let (year, mon) = parse_year_month(month)?;
-    let bills = state.repo.get(year, mon).await?;
-    let result = search_resources(
-        &bills,
-        &ResourceSearchQuery {
-            rg_filter,
-            name_filter,
-            tag_filter,
-            meter_category_filter,
-            subscription_filter,
-            resource_type_filter,
-            limit,
-        },
-    )?;
+        .map(|n| (n as usize).min(200))
+        .unwrap_or(50);
+
+    let latest = state.repo.get(year, mon).await?;
+    let prev = state.repo.get(prev_year, prev_mon).await?;
+    let diff = diff_bills(&latest, &prev, &filter, by_meter);
+
+    let counts = diff.count_by_kind();
+    let changes: Vec<Value> = diff
+        .records
+        .iter()
+        .take(limit)
+        .map(|r| {
+            json!({
+                "resource_name": r.resource_name,
+                "resource_group": r.resource_group,
+                "subscription_name": r.subscription_name,
+                "meter_category": r.meter_category,
+                "meter": r.meter,
+                "change": r.kind,
+                "latest_cost": round2(r.latest_cost),
+                "previous_cost": round2(r.prev_cost),
+                "delta": round2(r.delta),
+                "delta_pct": r.delta_pct.map(round2),
+                "latest_cost_usd": round2(r.latest_cost_usd),
+                "previous_cost_usd": round2(r.prev_cost_usd),
+                "delta_usd": round2(r.delta_usd),
+                "delta_pct_usd": r.delta_pct_usd.map(round2),
+            })
+        })
+        .collect();
 
     Ok(serde_json::to_string_pretty(&json!({
         "period": month,
-        "total_resources": result.total_resources,
-        "total_cost_usd": result.total_cost_usd,
-        "resources": result.resources,
+        "previous_period": format!("{prev_year}-{prev_mon:02}"),
+        "latest_total": round2(diff.latest_total),
+        "previous_total": round2(diff.prev_total),
+        "delta": round2(diff.delta()),
+        "latest_total_usd": round2(diff.latest_total_usd),
+        "previous_total_usd": round2(diff.prev_total_usd),
+        "delta_usd": round2(diff.delta_usd()),
+        "total_resources": diff.records.len(),
+        "counts": counts,
+        "changes": changes,
     }))
     .unwrap())
 }
 
+// ---------------------------------------------------------------------------
+// Tool: query_costs
+// ---------------------------------------------------------------------------
+
+async fn tool_query_costs(
+    args: Option<&serde_json::Map<String, Value>>,
+    state: &AppState,
+) -> Result<String, String> {
+    let args = args.ok_or_else(|| "Missing arguments".to_string())?;
+    let str_arg = |key: &str| args.get(key).and_then(|v| v.as_str());
+    let group_by: GroupBy = str_arg("group_by").unwrap_or("rg").parse()?;
+    let metric = parse_value_enum::<Metric>(str_arg("metric"), "metric")?;
+    let sort = parse_value_enum::<SortOrder>(str_arg("sort"), "sort")?;
+    let limit = args
+        .get("limit")
+        .and_then(|v| v.as_u64())
+        .map(|n| (n as usize).min(200))
+        .unwrap_or(20);
+    let filter = filter_from_args(args)?;
+
+    let period = load_period(args, state).await?;
+    let mut result = query::run(
+        &period.bills.bills(),
+        &Query {
+            filter: filter.with_date_range(period.date_range),
+            group_by,
+            metric,
+            sort,
+            limit: Some(limit),
+        },
+    );
+    result.total_cost = round2(result.total_cost);
+    result.total_cost_usd = round2(result.total_cost_usd);
+    for r in &mut result.rows {
+        r.cost = round2(r.cost);
+        r.cost_usd = round2(r.cost_usd);
+    }
+
+    let mut out = serde_json::to_value(&result).map_err(|e| e.to_string())?;
+    out["period"] = json!(period.label);
+    if !period.bills.missing.is_empty() {
+        out["missing_months"] = json!(period.bills.missing);
+    }
+    Ok(serde_json::to_string_pretty(&out).unwrap())
+}
+
 // ---------------------------------------------------------------------------
 // Parse helpers
 // ---------------------------------------------------------------------------
@@ -656,6 +796,170 @@ fn parse_year_month(s: &str) -> Result<(u32, u32), String> {
     Ok((year, mon))
 }
 
+/// Parse the optional `tags` array argument into tag predicates (case-insensitive values).
+fn parse_tag_predicates(
+    args: &serde_json::Map<String, Value>,
+) -> Result<Vec<TagPredicate>, String> {
+    match args.get("tags") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(specs)) => specs
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .ok_or_else(|| "Argument 'tags' must be an array of strings".to_string())
+                    .and_then(|spec| TagPredicate::parse(spec, false))
+            })
+            .collect(),
+        Some(_) => Err("Argument 'tags' must be an array of strings".to_string()),
+    }
+}
+
+/// The `BillFilter` described by the shared filter arguments (all case-insensitive):
+/// `resource_name`, `resource_group`, `subscription`, `meter_category`, `location`,
+/// `reservation`, `tag_filter`, `invoice_section`, `resource_type`, `meter_name`, `charge_type`,
+/// `pricing_model`, `publisher`, `tags`, `where`, `exclude`.
+fn filter_from_args(args: &serde_json::Map<String, Value>) -> Result<BillFilter, String> {
+    let opt_arg = |key: &str| {
+        args.get(key)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    let mut filter = BillFilter::new(
+        opt_arg("resource_name"),
+        opt_arg("resource_group"),
+        opt_arg("subscription"),
+        opt_arg("meter_category"),
+        opt_arg("location"),
+        opt_arg("reservation"),
+        None,
+        opt_arg("tag_filter"),
+        opt_arg("invoice_section"),
+        &FilterOpts {
+            case_sensitive: false,
+        },
+    )
+    .map_err(|e| format!("Invalid filter regex: {e}"))?;
+    for dim in [
+        FilterDimension::ResourceType,
+        FilterDimension::MeterName,
+        FilterDimension::ChargeType,
+        FilterDimension::PricingModel,
+        FilterDimension::Publisher,
+    ] {
+        if let Some(pattern) = opt_arg(dim.arg_name()) {
+            filter = filter
+                .with_pattern(dim, &pattern)
+                .map_err(|e| format!("Invalid filter regex: {e}"))?;
+        }
+    }
+    Ok(filter
+        .with_tag_predicates(parse_tag_predicates(args)?)
+        .with_where(parse_where(args)?)
+        .with_excludes(parse_excludes(args)?))
+}
+
+/// Bills selected by `from` / `to` (any months they span) or else by `month`.
+struct Period {
+    /// `"YYYY-MM"` or `"from..to"`.
+    label: String,
+    /// Row date bounds, unset for a whole month.
+    date_range: DateRange,
+    bills: RangeBills,
+}
+
+async fn load_period(
+    args: &serde_json::Map<String, Value>,
+    state: &AppState,
+) -> Result<Period, String> {
+    let str_arg = |key: &str| args.get(key).and_then(|v| v.as_str());
+    let date_range = DateRange::parse(str_arg("from"), str_arg("to"))?;
+    if date_range.is_set() {
+        return Ok(Period {
+            label: date_range.to_string(),
+            bills: state.repo.get_range(&date_range).await?,
+            date_range,
+        });
+    }
+    let month = str_arg("month")
+        .ok_or_else(|| "Missing required argument 'month' (or 'from' / 'to')".to_string())?;
+    let (year, mon) = parse_year_month(month)?;
+    Ok(Period {
+        label: month.to_string(),
+        date_range,
+        bills: RangeBills {
+            months: vec![state.repo.get(year, mon).await?],
+            missing: Vec::new(),
+        },
+    })
+}
+
+/// Parse the optional `where` expression argument (case-insensitive values).
+fn parse_where(args: &serde_json::Map<String, Value>) -> Result<Option<FilterExpr>, String> {
+    match args.get("where") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) if s.trim().is_empty() => Ok(None),
+        Some(Value::String(s)) => FilterExpr::parse(s, false).map(Some),
+        Some(_) => Err("Argument 'where' must be a string".to_string()),
+    }
+}
+
+/// Parse the optional `exclude` object, `{dimension: regex | [regex, ...]}` (case-insensitive).
+fn parse_excludes(args: &serde_json::Map<String, Value>) -> Result<Vec<Exclude>, String> {
+    let map = match args.get("exclude") {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(Value::Object(map)) => map,
+        Some(_) => return Err("Argument 'exclude' must be an object".to_string()),
+    };
+    let mut excludes = Vec::new();
+    for (key, value) in map {
+        let dim = FilterDimension::from_arg_name(key).ok_or_else(|| {
+            format!(
+                "Unknown exclude key '{key}', expected one of: {}",
+                FilterDimension::ALL.map(|d| d.arg_name()).join(", ")
+            )
+        })?;
+        let patterns: Vec<&str> = match value {
+            Value::String(p) => vec![p.as_str()],
+            Value::Array(items) => items
+                .iter()
+                .map(|v| {
+                    v.as_str().ok_or_else(|| {
+                        format!("Exclude '{key}' must be a string or array of strings")
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            _ => {
+                return Err(format!(
+                    "Exclude '{key}' must be a string or array of strings"
+                ));
+            }
+        };
+        for p in patterns.into_iter().filter(|p| !p.is_empty()) {
+            excludes.push(
+                Exclude::new(dim, p, false)
+                    .map_err(|e| format!("Invalid exclude regex for '{key}': {e}"))?,
+            );
+        }
+    }
+    Ok(excludes)
+}
+
+/// Parse an optional `metric` / `sort` argument by its CLI name (`cost_usd` or `cost-usd`).
+fn parse_value_enum<T: ValueEnum + Default>(value: Option<&str>, key: &str) -> Result<T, String> {
+    match value {
+        None | Some("") => Ok(T::default()),
+        Some(v) => T::from_str(&v.replace('_', "-"), true).map_err(|_| {
+            let names: Vec<String> = T::value_variants()
+                .iter()
+                .filter_map(|t| t.to_possible_value())
+                .map(|p| p.get_name().replace('-', "_"))
+                .collect();
+            format!("Invalid {key} '{v}', expected one of: {}", names.join(", "))
+        }),
+    }
+}
+
 fn parse_date(s: &str) -> Result<(u32, u32, u32), String> {
     let parts: Vec<&str> = s.split('-').collect();
     if parts.len() != 3 {
@@ -673,45 +977,6 @@ fn parse_date(s: &str) -> Result<(u32, u32, u32), String> {
     Ok((year, mon, day))
 }
 
-// ---------------------------------------------------------------------------
-// Tests
-// ---------------------------------------------------------------------------
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    // --- parse_year_month ---
-
-    #[test]
-    fn parse_year_month_valid() {
-        assert_eq!(parse_year_month("2026-04").unwrap(), (2026, 4));
-        assert_eq!(parse_year_month("2025-12").unwrap(), (2025, 12));
-    }
-
-    #[test]
-    fn parse_year_month_invalid() {
-        assert!(parse_year_month("2026").is_err());
-        assert!(parse_year_month("abcd-ef").is_err());
-        assert!(parse_year_month("").is_err());
-    }
-
-    // --- parse_date ---
-
-    #[test]
-    fn parse_date_valid() {
-        assert_eq!(parse_date("2026-04-07").unwrap(), (2026, 4, 7));
-        assert_eq!(parse_date("2025-01-31").unwrap(), (2025, 1, 31));
-    }
-
-    #[test]
-    fn parse_date_invalid() {
-        assert!(parse_date("2026-04").is_err()); // only 2 parts
-        assert!(parse_date("20260407").is_err()); // no separators
-        assert!(parse_date("abc-def-ghi").is_err());
-    }
-}
-
 // ---------------------------------------------------------------------------
 // Entry point
 // ---------------------------------------------------------------------------
@@ -839,8 +1104,28 @@ async fn main() {
         log::warn!("[bill_analysis_mcp] --no-role-check set, BillingViewer App Role not enforced");
     }
 
+    let tag_aliases = match TagAliases::from_env() {
+        Ok(aliases) => aliases.map(Arc::new),
+        Err(e) => {
+            log::error!("[mcp] ✗ {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let tag_inheritance = match TagInheritance::from_env() {
+        Ok(rules) => rules.map(Arc::new),
+        Err(e) => {
+            log::error!("[mcp] ✗ {e}");
+            std::process::exit(1);
+        }
+    };
+
     let state = AppState::new(
-        Arc::new(BillRepository::new(args.data_dir.clone(), blob_source)),
+        Arc::new(
+            BillRepository::new(args.data_dir.clone(), blob_source)
+                .with_tag_aliases(tag_aliases)
+                .with_tag_inheritance(tag_inheritance),
+        ),
         entra,
         no_role_check,
     );
@@ -896,3 +1181,164 @@ async fn main() {
         .await
         .unwrap_or_else(|e| panic!("Server error: {e}"));
 }
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // --- parse_year_month ---
+
+    #[test]
+    fn parse_year_month_valid() {
+        assert_eq!(parse_year_month("2026-04").unwrap(), (2026, 4));
+        assert_eq!(parse_year_month("2025-12").unwrap(), (2025, 12));
+    }
+
+    #[test]
+    fn parse_year_month_invalid() {
+        assert!(parse_year_month("2026").is_err());
+        assert!(parse_year_month("abcd-ef").is_err());
+        assert!(parse_year_month("").is_err());
+    }
+
+    // --- parse_date ---
+
+    #[test]
+    fn parse_date_valid() {
+        assert_eq!(parse_date("2026-04-07").unwrap(), (2026, 4, 7));
+        assert_eq!(parse_date("2025-01-31").unwrap(), (2025, 1, 31));
+    }
+
+    #[test]
+    fn parse_date_invalid() {
+        assert!(parse_date("2026-04").is_err()); // only 2 parts
+        assert!(parse_date("20260407").is_err()); // no separators
+        assert!(parse_date("abc-def-ghi").is_err());
+    }
+
+    // --- parse_tag_predicates ---
+
+    #[test]
+    fn parse_tag_predicates_from_args() {
+        let args = json!({ "tags": ["env=prod", "!owner"] });
+        let preds = parse_tag_predicates(args.as_object().unwrap()).unwrap();
+        assert_eq!(preds.len(), 2);
+        assert_eq!(preds[1].to_string(), "!owner");
+
+        let none = json!({});
+        assert!(
+            parse_tag_predicates(none.as_object().unwrap())
+                .unwrap()
+                .is_empty()
+        );
+        let bad = json!({ "tags": "env=prod" });
+        assert!(parse_tag_predicates(bad.as_object().unwrap()).is_err());
+        let bad_regex = json!({ "tags": ["env~("] });
+        assert!(parse_tag_predicates(bad_regex.as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_excludes_from_args() {
+        let args =
+            json!({ "exclude": { "resource_group": "^mc_", "subscription": ["sandbox", "test"] } });
+        let excludes = parse_excludes(args.as_object().unwrap()).unwrap();
+        assert_eq!(excludes.len(), 3);
+        assert!(
+            parse_excludes(json!({}).as_object().unwrap())
+                .unwrap()
+                .is_empty()
+        );
+        let err = parse_excludes(
+            json!({ "exclude": { "colour": "red" } })
+                .as_object()
+                .unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.contains("Unknown exclude key 'colour'"), "{err}");
+        assert!(
+            parse_excludes(json!({ "exclude": { "name": "(" } }).as_object().unwrap()).is_err()
+        );
+        assert!(parse_excludes(json!({ "exclude": "sandbox" }).as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_where_from_args() {
+        let args = json!({ "where": "rg ~ prod and not tag.owner" });
+        let expr = parse_where(args.as_object().unwrap()).unwrap().unwrap();
+        assert_eq!(expr.to_string(), "rg ~ prod and not tag.owner");
+        assert!(
+            parse_where(json!({}).as_object().unwrap())
+                .unwrap()
+                .is_none()
+        );
+        let err = parse_where(json!({ "where": "rg ~" }).as_object().unwrap()).unwrap_err();
+        assert!(err.contains("column 5"), "{err}");
+        assert!(parse_where(json!({ "where": 1 }).as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn filter_from_args_covers_all_dimensions() {
+        let args = json!({
+            "resource_group": "prod",
+            "location": "none",
+            "invoice_section": "core",
+            "resource_type": "disks",
+            "pricing_model": "spot",
+            "exclude": { "name": "tmp" }
+        });
+        let filter = filter_from_args(args.as_object().unwrap()).unwrap();
+        assert_eq!(filter.resource_group, "prod");
+        assert_eq!(filter.location, "none");
+        assert_eq!(filter.invoice_section, "core");
+        assert_eq!(filter.resource_type, "disks");
+        assert_eq!(filter.pricing_model, "spot");
+        assert_eq!(filter.excludes.len(), 1);
+        let err =
+            filter_from_args(json!({ "resource_type": "(" }).as_object().unwrap()).unwrap_err();
+        assert!(err.starts_with("Invalid filter regex"), "{err}");
+    }
+
+    #[test]
+    fn filtering_tools_describe_every_filter_argument() {
+        let req = RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/list".to_string(),
+            params: None,
+            id: None,
+        };
+        let tools = handle_tools_list(&req).result.unwrap()["tools"].clone();
+        for tool in tools.as_array().unwrap() {
+            if tool["name"] == "list_available_months" {
+                continue;
+            }
+            let props = tool["inputSchema"]["properties"].as_object().unwrap();
+            for key in filter_properties().keys() {
+                assert!(props.contains_key(key), "{} lacks {key}", tool["name"]);
+            }
+        }
+        assert_eq!(
+            tools[1]["inputSchema"]["properties"]["from"]["description"],
+            FROM_DESCRIPTION
+        );
+        assert_eq!(tools[2]["inputSchema"]["required"], json!(["date"]));
+    }
+
+    #[test]
+    fn parse_metric_and_sort() {
+        assert_eq!(parse_value_enum::<Metric>(None, "metric"), Ok(Metric::Cost));
+        assert_eq!(
+            parse_value_enum::<Metric>(Some("cost_usd"), "metric"),
+            Ok(Metric::CostUsd)
+        );
+        assert_eq!(
+            parse_value_enum::<SortOrder>(Some("KEY"), "sort"),
+            Ok(SortOrder::Key)
+        );
+        let err = parse_value_enum::<Metric>(Some("dollars"), "metric").unwrap_err();
+        assert!(err.contains("cost_usd"), "{err}");
+    }
+}
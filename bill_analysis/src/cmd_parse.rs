@@ -1,8 +1,16 @@
 // https://rust-cli-recommendations.sunshowers.io/handling-arguments.html
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
+use crate::bills::BillFilter;
+use crate::bills::bill_filter::{Exclude, FilterDimension};
+use crate::bills::date_range::DateRange;
+use crate::bills::filter_expr::FilterExpr;
+use crate::bills::pivot::PivotLayout;
+use crate::bills::query::{GroupBy, Metric, SortOrder};
+use crate::bills::tag_predicate::TagPredicate;
+
 // Here's my app!
 #[derive(Debug, Parser)]
 #[clap(name = "bill-analysis", version)]
@@ -21,6 +29,32 @@ pub struct App {
     /// Alternative to --bill-prev-subtract-path; takes precedence if both are given.
     #[arg(index = 2)]
     pub bill_prev: Option<PathBuf>,
+    /// With a previous bill: skip the per-resource changes (added/removed/increased/decreased)
+    /// that the comparison report lists before the sections.
+    #[arg(long)]
+    pub no_diff_resources: bool,
+    /// Per-resource changes: join on resource and meter instead of resource only.
+    #[arg(long)]
+    pub diff_by_meter: bool,
+    /// With a previous bill: split the cost change into volume, price, FX and new/removed resources.
+    #[arg(long)]
+    pub variance: bool,
+    /// Show the report as a cost tree, subscription → resource group → resource → meter,
+    /// with subtotals at every level (and changes when a previous bill is given).
+    #[arg(long)]
+    pub tree: bool,
+    /// With --tree: levels shown, 1 (subscriptions) to 4 (meters).
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=4))]
+    pub tree_depth: u8,
+    /// With --tree: fold nodes whose cost, previous cost and change are all below this into "other".
+    #[arg(long, default_value_t = 0.0)]
+    pub tree_min: f64,
+    /// Format of the default report: coloured text, or json/csv/markdown for pipelines.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub output: ReportFormat,
+    /// With --from/--to and no bill: directory every month of the range is loaded from.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
     /// regex find to filter on invoice section name
     #[arg(long)]
     pub invoice_section: Option<String>,
@@ -44,15 +78,171 @@ pub struct App {
     /// regex find to filter on lowercase tag's
     #[arg(long)]
     pub tag_filter: Option<String>,
+    /// regex on the ARM resource type from the resource id, e.g. "disks" or "microsoft.compute/".
+    #[arg(long)]
+    pub resource_type: Option<String>,
+    /// regex on the meter name, e.g. "D4s v5".
+    #[arg(long)]
+    pub meter_name: Option<String>,
+    /// regex on the charge type, e.g. "Usage", "Purchase", "Refund".
+    #[arg(long)]
+    pub charge_type: Option<String>,
+    /// regex on the pricing model, e.g. "OnDemand", "Reservation", "SavingsPlan", "Spot".
+    #[arg(long)]
+    pub pricing_model: Option<String>,
+    /// regex on the publisher name, e.g. Marketplace vendors.
+    #[arg(long)]
+    pub publisher: Option<String>,
+    /// tag predicate, repeat to AND: "env" exists, "!env" missing, "env=prod", "env!=prod", "env~^prod" regex.
+    #[arg(long = "tag", value_name = "SPEC")]
+    pub tag: Vec<String>,
+    /// first usage date to include: YYYY-MM-DD, YYYY-MM, today, yesterday or <N>d (N days ago).
+    #[arg(long, value_name = "DATE")]
+    pub from: Option<String>,
+    /// last usage date to include (inclusive), same formats as --from; YYYY-MM means the month's last day.
+    #[arg(long, value_name = "DATE")]
+    pub to: Option<String>,
+    /// regex to exclude rows by resource name, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_name: Vec<String>,
+    /// regex to exclude resource groups, repeatable, e.g. "^mc_" for AKS node resource groups.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_resource_group: Vec<String>,
+    /// regex to exclude subscriptions, repeatable, e.g. "sandbox".
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_subscription: Vec<String>,
+    /// regex to exclude meter categories, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_meter_category: Vec<String>,
+    /// regex to exclude regions, repeatable; "none" excludes rows without a location.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_location: Vec<String>,
+    /// regex to exclude benefit names, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_reservation: Vec<String>,
+    /// regex to exclude rows by their lowercase tag string, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_tag: Vec<String>,
+    /// regex to exclude invoice sections, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_invoice_section: Vec<String>,
+    /// regex to exclude ARM resource types, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_resource_type: Vec<String>,
+    /// regex to exclude meter names, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_meter_name: Vec<String>,
+    /// regex to exclude charge types, repeatable, e.g. "Purchase" to drop reservation purchases.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_charge_type: Vec<String>,
+    /// regex to exclude pricing models, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_pricing_model: Vec<String>,
+    /// regex to exclude publishers, repeatable.
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_publisher: Vec<String>,
+    /// boolean filter expression ANDed with the other filters, e.g.
+    /// 'rg ~ "prod" and (tag.env = "prod" or sub ~ "core") and not meter ~ "Bandwidth" and cost > 10'.
+    #[arg(long = "where", value_name = "EXPR")]
+    pub where_expr: Option<String>,
     /// tag_summarise single tag all values.
     #[arg(short, long)]
     pub tag_summarise: Option<String>,
 }
 
+impl App {
+    /// Build the `BillFilter` from the top-level filter args.
+    pub fn bill_filter(&self, filter_opts: &FilterOpts) -> Result<BillFilter, String> {
+        let tag_predicates = TagPredicate::parse_all(&self.tag, filter_opts.case_sensitive)?;
+        let where_expr = self
+            .where_expr
+            .as_deref()
+            .map(|e| FilterExpr::parse(e, filter_opts.case_sensitive))
+            .transpose()?;
+        let excludes = [
+            (FilterDimension::Name, &self.exclude_name),
+            (FilterDimension::ResourceGroup, &self.exclude_resource_group),
+            (FilterDimension::Subscription, &self.exclude_subscription),
+            (FilterDimension::MeterCategory, &self.exclude_meter_category),
+            (FilterDimension::Location, &self.exclude_location),
+            (FilterDimension::Reservation, &self.exclude_reservation),
+            (FilterDimension::Tag, &self.exclude_tag),
+            (
+                FilterDimension::InvoiceSection,
+                &self.exclude_invoice_section,
+            ),
+            (FilterDimension::ResourceType, &self.exclude_resource_type),
+            (FilterDimension::MeterName, &self.exclude_meter_name),
+            (FilterDimension::ChargeType, &self.exclude_charge_type),
+            (FilterDimension::PricingModel, &self.exclude_pricing_model),
+            (FilterDimension::Publisher, &self.exclude_publisher),
+        ]
+        .into_iter()
+        .flat_map(|(dim, patterns)| patterns.iter().map(move |p| (dim, p)))
+        .map(|(dim, p)| Exclude::new(dim, p, filter_opts.case_sensitive))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid regex in exclude filter: {e}"))?;
+        let date_range = DateRange::parse(self.from.as_deref(), self.to.as_deref())?;
+        let mut filter = BillFilter::new(
+            self.name_regex.clone(),
+            self.resource_group.clone(),
+            self.subscription.clone(),
+            self.meter_category.clone(),
+            self.location.clone(),
+            self.reservation.clone(),
+            self.tag_summarise.clone(),
+            self.tag_filter.clone(),
+            self.invoice_section.clone(),
+            filter_opts,
+        )
+        .map_err(|e| format!("invalid regex in filter: {e}"))?;
+        for (dim, pattern) in [
+            (FilterDimension::ResourceType, &self.resource_type),
+            (FilterDimension::MeterName, &self.meter_name),
+            (FilterDimension::ChargeType, &self.charge_type),
+            (FilterDimension::PricingModel, &self.pricing_model),
+            (FilterDimension::Publisher, &self.publisher),
+        ] {
+            if let Some(pattern) = pattern {
+                filter = filter
+                    .with_pattern(dim, pattern)
+                    .map_err(|e| format!("invalid regex in filter: {e}"))?;
+            }
+        }
+        Ok(filter
+            .with_tag_predicates(tag_predicates)
+            .with_where(where_expr)
+            .with_excludes(excludes)
+            .with_date_range(date_range))
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     BillSummary(BillSummaryArgs),
     DiskCsvSavings(DiskCsvSavingsArgs),
+    /// One row per month with filtered cost, reservation savings and no-reservation cost.
+    Trend(TrendArgs),
+    /// Reservation / savings plan coverage of eligible usage per flex group and region.
+    Coverage(CoverageArgs),
+    /// Offline reservation purchase advice from the on-demand usage in the last N monthly bills.
+    Recommend(RecommendArgs),
+    /// Spend on resources missing required tags, per key, subscription and resource group.
+    TagCompliance(TagComplianceArgs),
+    /// Split shared pools (networking, monitoring, ...) across teams with an allocation rules file.
+    Allocate(AllocateArgs),
+    /// Write a Markdown and CSV chargeback statement per invoice section, tag value or subscription.
+    Chargeback(ChargebackArgs),
+    /// Write a self-contained HTML report of a month: totals, tables, daily chart, savings.
+    HtmlReport(HtmlReportArgs),
+    /// Browse a bill interactively: subscription → resource group → resource → meter.
+    Tui(TuiArgs),
+    /// Interactive shell: load months once, then set filters, show, compare and export.
+    Repl(ReplArgs),
+    /// Group filtered rows by a dimension and rank them, as the MCP `query_costs` tool does.
+    Query(QueryArgs),
+    /// Pivot filtered cost by several dimensions, e.g. subscription × meter category, with subtotals.
+    Pivot(PivotArgs),
     // /// Number of times to greet
     // #[arg(short, long, default_value_t = 1)]
     // pub count: u8,
@@ -70,6 +260,226 @@ pub struct DiskCsvSavingsArgs {
     #[arg(short, long, default_value = "../Azuredisks-Unattached-20240517.csv")]
     pub diskfile: PathBuf,
 }
+#[derive(Debug, Args)]
+pub struct TrendArgs {
+    /// First month to include, "YYYY-MM". Defaults to the earliest month found.
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Last month to include, "YYYY-MM". Defaults to last month.
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct CoverageArgs {
+    /// Month to report, "YYYY-MM". Defaults to last month.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct RecommendArgs {
+    /// Number of months of history to use.
+    #[arg(long, default_value_t = 3)]
+    pub months: usize,
+    /// Last month to include, "YYYY-MM". Defaults to last month.
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    /// Assumed 1-year reservation discount off on-demand, 0.0..1.0.
+    #[arg(long, default_value_t = 0.35)]
+    pub discount_1y: f64,
+    /// Assumed 3-year reservation discount off on-demand, 0.0..1.0.
+    #[arg(long, default_value_t = 0.55)]
+    pub discount_3y: f64,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct TagComplianceArgs {
+    /// Month to report, "YYYY-MM". Defaults to last month.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Required tag key, repeat or comma separate. Defaults to owner, environment, costcenter.
+    #[arg(long = "required-tag", value_name = "KEY")]
+    pub required_tag: Vec<String>,
+    /// File with required tag keys, one per line, '#' comments. Combined with --required-tag.
+    #[arg(long)]
+    pub required_tags_file: Option<PathBuf>,
+    /// Max resources / scopes listed per section in text output.
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct AllocateArgs {
+    /// Allocation rules JSON: team tag and shared pools with their allocation keys.
+    #[arg(long)]
+    pub rules: PathBuf,
+    /// Month to report, "YYYY-MM". Defaults to last month.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct ChargebackArgs {
+    /// Dimension to issue one statement per value of.
+    #[arg(long, value_enum, default_value_t = ChargebackBy::InvoiceSection)]
+    pub by: ChargebackBy,
+    /// Tag key for `--by tag`, e.g. "team".
+    #[arg(long)]
+    pub tag_key: Option<String>,
+    /// Month to report, "YYYY-MM". Defaults to last month; the month before is used for comparison.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Directory the statement files are written to.
+    #[arg(long, default_value = "./chargeback")]
+    pub out_dir: PathBuf,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct HtmlReportArgs {
+    /// Month to report, "YYYY-MM". Defaults to last month; the month before is used for comparison.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// File the report is written to. Defaults to "./bill-report-YYYY-MM.html".
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct TuiArgs {
+    /// Month to browse, "YYYY-MM". Defaults to last month.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Month to compare with, "YYYY-MM". Defaults to the month before `--month`.
+    #[arg(long)]
+    pub prev_month: Option<String>,
+    /// Start with the comparison off (toggle with `c`).
+    #[arg(long)]
+    pub no_compare: bool,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplArgs {
+    /// Months to load at start, "YYYY-MM"; repeat for several. Defaults to last month.
+    #[arg(long)]
+    pub month: Vec<String>,
+    /// Command history file. Defaults to "~/.bill_analysis_history".
+    #[arg(long)]
+    pub history: Option<PathBuf>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    /// Dimension to group by: none, resource, tag:KEY, rg, subscription, meter_category,
+    /// meter_sub_category, meter_name, reservation, location, invoice_section, resource_type,
+    /// charge_type, pricing_model or publisher.
+    #[arg(long, default_value = "resource_group")]
+    pub group_by: GroupBy,
+    /// Value groups are ranked by.
+    #[arg(long, value_enum, default_value_t = Metric::Cost)]
+    pub metric: Metric,
+    #[arg(long, value_enum, default_value_t = SortOrder::Desc)]
+    pub sort: SortOrder,
+    /// Groups shown, 0 for all.
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+    /// Month to query, "YYYY-MM". Defaults to the months of --from/--to, else last month.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct PivotArgs {
+    /// Comma-separated dimensions, outermost first, e.g. "subscription,meter_category" or "rg,tag:team".
+    /// Each is one of the `query --group-by` values except none.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub by: Vec<GroupBy>,
+    #[arg(long, value_enum, default_value_t = PivotLayout::Nested)]
+    pub layout: PivotLayout,
+    /// Value groups are ranked by.
+    #[arg(long, value_enum, default_value_t = Metric::Cost)]
+    pub metric: Metric,
+    #[arg(long, value_enum, default_value_t = SortOrder::Desc)]
+    pub sort: SortOrder,
+    /// Groups shown per level, 0 for all. Subtotals always include every row.
+    #[arg(long, default_value_t = 0)]
+    pub limit: usize,
+    /// Month to pivot, "YYYY-MM". Defaults to the months of --from/--to, else last month.
+    #[arg(long)]
+    pub month: Option<String>,
+    /// Directory containing the monthly billing CSVs / subfolders.
+    #[arg(long, default_value = "./csv_data")]
+    pub data_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChargebackBy {
+    InvoiceSection,
+    Tag,
+    Subscription,
+}
+
+/// Output format for commands that can emit machine-readable results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+/// Output format of the default report (`--output`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+    Markdown,
+}
+
 /// Options that control bill parsing and regex matching.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FilterOpts {
@@ -86,6 +496,8 @@ pub struct DisplayOpts {
     pub tag_list: bool,
     /// Print debug diagnostics.
     pub debug: bool,
+    /// Keep the N largest rows per section, folding the rest into one "other" row.
+    pub top: Option<usize>,
 }
 
 #[derive(Debug, Args)]
@@ -102,6 +514,9 @@ pub struct GlobalOpts {
     pub case_sensitive: bool,
     #[arg(short, long, default_value = "10.00")]
     pub cost_min_display: f64,
+    /// Show the N largest rows per report section and fold the rest into one "other (N items)" row.
+    #[arg(long, value_name = "N")]
+    pub top: Option<usize>,
     /// list all uniq tags names and number of uniq tags.
     #[arg(long)]
     pub tag_list: bool,
@@ -6,6 +6,8 @@ use bills::Bills;
 use colored::Colorize;
 pub mod cmd_parse;
 pub mod find_files;
+pub mod repl;
+pub mod tui;
 pub use bills::repository::BillRepository;
 use std::{
     f64,
@@ -14,15 +16,13 @@ use std::{
 
 use cmd_parse::FilterOpts;
 
-// use once_cell::sync::Lazy;
-// static RESERVATION_SUMMARY: Lazy<HashMap<&'static str, Vec<&'static str>>> = Lazy::new(|| {
-//     let mut m = HashMap::new();
-//     // k = meter_category to include, v = meter_sub_category to exclude
-//     m.insert("Virtual Machines", vec![]);
-//     m.insert("SQL Managed Instance", vec!["Storage"]);
-//     m.insert("Azure App Service", vec![]);
-//     m
-// });
+/// Reservation-eligible meters for the `--reservation` utilisation summary.
+/// k = meter_category (or meter_sub_category) to include, v = meter_sub_category substrings to exclude
+pub static RESERVATION_SUMMARY: &[(&str, &[&str])] = &[
+    ("Virtual Machines", &[]),
+    ("SQL Managed Instance", &["Storage"]),
+    ("Azure App Service", &[]),
+];
 
 // function calc_subscription_cost
 pub fn calc_subscription_cost(
@@ -57,7 +57,7 @@ fn load_latest_bill(
         let (path, files) = find_files::in_folder(file_or_folder, r".*Detail.*\.csv$", debug);
         path.join(files.last().expect("No files found"))
     };
-    println!("Loading bill from '{:?}'", file_bill);
+    log::info!("Loading bill from '{:?}'", file_bill);
     let mut latest_bill: Bills = Bills::default();
     latest_bill
         .parse_csv(&file_bill, filter_opts)
@@ -141,6 +141,303 @@ pub async fn load_bill_async(
     load_bill(file_or_folder, filter_opts, debug)
 }
 
+/// Tag alias map from `AZ_BILLING_TAG_ALIASES`, exits on an unreadable or invalid file.
+pub fn tag_aliases_from_env() -> Option<std::sync::Arc<bills::tag_alias::TagAliases>> {
+    bills::tag_alias::TagAliases::from_env()
+        .unwrap_or_else(|e| exit_with_error(e))
+        .map(std::sync::Arc::new)
+}
+
+/// Tag inheritance rules from `AZ_BILLING_TAG_INHERITANCE`, exits on an unreadable or invalid file.
+pub fn tag_inheritance_from_env() -> Option<std::sync::Arc<bills::tag_inherit::TagInheritance>> {
+    bills::tag_inherit::TagInheritance::from_env()
+        .unwrap_or_else(|e| exit_with_error(e))
+        .map(std::sync::Arc::new)
+}
+
+/// Alias and inheritance rules from the env, applied to `bills` in that order.
+pub fn enrich_tags_from_env(bills: &mut Bills) {
+    if let Some(aliases) = tag_aliases_from_env() {
+        bills.apply_tag_aliases(&aliases);
+    }
+    if let Some(rules) = tag_inheritance_from_env() {
+        bills.inherit_tags(&rules);
+    }
+}
+
+/// `BillRepository` over `data_dir` with the blob source and tag rules from the env.
+fn open_repository(data_dir: &Path) -> BillRepository {
+    let blob = blob_source::BlobSourceConfig::from_env().map(|cfg| {
+        std::sync::Arc::new(
+            blob_source::BlobSource::new(cfg)
+                .unwrap_or_else(|e| panic!("Failed to create blob source: {e}")),
+        )
+    });
+    BillRepository::new(data_dir.to_path_buf(), blob)
+        .with_tag_aliases(tag_aliases_from_env())
+        .with_tag_inheritance(tag_inheritance_from_env())
+}
+
+/// Log `e` and exit with status 1; subcommands use it for bad arguments and load errors.
+pub fn exit_with_error(e: impl std::fmt::Display) -> ! {
+    log::error!("{e}");
+    std::process::exit(1);
+}
+
+/// Print `value` as CSV from `to_csv` for `OutputFormat::Csv`, else as pretty JSON.
+/// Callers print `OutputFormat::Text` themselves.
+fn emit<T: serde::Serialize + ?Sized>(
+    format: cmd_parse::OutputFormat,
+    to_csv: impl FnOnce(&T) -> Result<String, Box<dyn std::error::Error>>,
+    value: &T,
+) {
+    if format == cmd_parse::OutputFormat::Csv {
+        let csv =
+            to_csv(value).unwrap_or_else(|e| exit_with_error(format!("Failed to write CSV: {e}")));
+        print!("{csv}");
+    } else {
+        print_json(value);
+    }
+}
+
+/// Print `value` as pretty JSON.
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    let json = serde_json::to_string_pretty(value)
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to write JSON: {e}")));
+    println!("{json}");
+}
+
+/// A `YYYY-MM` or `YYYYMM` month argument, default last month, as `("YYYY-MM", year, month)`.
+fn parse_month_arg(month: Option<&str>) -> Result<(String, u32, u32), String> {
+    let month = month
+        .map(bills::trend::normalise_month)
+        .transpose()?
+        .unwrap_or_else(find_files::last_month_shorthand);
+    let (year, mon) =
+        find_files::parse_year_month_path(Path::new(&month)).expect("normalised month is YYYY-MM");
+    Ok((month, year, mon))
+}
+
+/// The bill for the default report over a `--from`/`--to` range: every month the range
+/// touches, loaded from `data_dir` through `BillRepository` and combined into one `Bills`.
+pub async fn load_range_bill(
+    data_dir: &Path,
+    range: &bills::date_range::DateRange,
+) -> Result<Bills, String> {
+    let loaded = open_repository(data_dir).get_range(range).await?;
+    for m in &loaded.missing {
+        log::warn!("No bill for {m}, skipped");
+    }
+    Bills::combine(&loaded.bills(), &range.to_string())
+}
+
+/// Fails when `range` reaches outside the months of `bill`'s rows, so one loaded bill
+/// is not reported as if it covered the whole range.
+pub fn check_range_within_bill(
+    bill: &Bills,
+    range: &bills::date_range::DateRange,
+) -> Result<(), String> {
+    let months = bill.bills.iter().filter_map(|b| b.date.get(..7));
+    let (Some(first), Some(last)) = (months.clone().min(), months.max()) else {
+        return Ok(());
+    };
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let from = range.from.as_deref().and_then(|f| f.get(..7));
+    let to = range.to.as_deref().unwrap_or(&today).get(..7);
+    if from.is_some_and(|f| f >= first) && to.is_some_and(|t| t <= last) {
+        return Ok(());
+    }
+    Err(format!(
+        "--from/--to {range} reaches outside the bill '{}' ({first}..{last}); leave out the \
+         bill to load every month of the range from --data-dir",
+        bill.file_short_name
+    ))
+}
+
+/// Bills of `year`-`mon` with its `"YYYY-MM"` label, as the other side of a comparison;
+/// `None`, with a warning, when that bill cannot be loaded.
+async fn load_comparison_month(
+    repo: &BillRepository,
+    year: u32,
+    mon: u32,
+) -> Option<(String, std::sync::Arc<Bills>)> {
+    match repo.get(year, mon).await {
+        Ok(bills) => Some((format!("{year:04}-{mon:02}"), bills)),
+        Err(e) => {
+            log::warn!("No previous month comparison: {e}");
+            None
+        }
+    }
+}
+
+/// `load_comparison_month` for the month before `year`-`mon`.
+async fn load_previous_month(
+    repo: &BillRepository,
+    year: u32,
+    mon: u32,
+) -> Option<(String, std::sync::Arc<Bills>)> {
+    let (prev_year, prev_mon) = bills::trend::previous_month(year, mon)?;
+    load_comparison_month(repo, prev_year, prev_mon).await
+}
+
+/// Print one row per month for `args.from..=args.to`, restricted to `filter`.
+///
+/// Months are loaded through `BillRepository`, so months missing locally are
+/// fetched from blob storage when the `AZ_BILLING_BLOB_*` env vars are set.
+pub async fn run_trend(args: &cmd_parse::TrendArgs, filter: &bills::BillFilter) {
+    use bills::trend;
+    let from = args
+        .from
+        .as_deref()
+        .map(trend::normalise_month)
+        .transpose()
+        .unwrap_or_else(|e| exit_with_error(e));
+    let (to, _, _) = parse_month_arg(args.to.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let months = trend::select_months(
+        repo.list_months_including_blob().await,
+        from.as_deref(),
+        Some(&to),
+    );
+    log::info!(
+        "Trend over {} month(s) {:?}..={to} from {:?}",
+        months.len(),
+        from,
+        args.data_dir
+    );
+    let rows = trend::monthly_trend(&repo, &months, filter)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    match args.format {
+        cmd_parse::OutputFormat::Text => trend::print_trend(&rows),
+        format => emit(format, trend::trend_to_csv, &rows),
+    }
+}
+
+/// Print reservation coverage for `args.month` (default last month), restricted to `filter`.
+pub async fn run_coverage(args: &cmd_parse::CoverageArgs, filter: &bills::BillFilter) {
+    use bills::coverage;
+    let (month, year, mon) =
+        parse_month_arg(args.month.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let bills = repo
+        .get(year, mon)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let rows = bills.reservation_coverage(filter);
+    match args.format {
+        cmd_parse::OutputFormat::Text => {
+            println!("Reservation coverage {month} from {:?}", args.data_dir);
+            coverage::print_coverage(&rows, &bills.get_billing_currency());
+        }
+        format => emit(format, coverage::coverage_to_csv, &rows),
+    }
+}
+
+/// Print reservation purchase advice from the `args.months` bills up to `args.to`
+/// (default last month), restricted to `filter`.
+pub async fn run_recommend(args: &cmd_parse::RecommendArgs, filter: &bills::BillFilter) {
+    use bills::recommend;
+    for d in [args.discount_1y, args.discount_3y] {
+        if !(0.0..1.0).contains(&d) {
+            exit_with_error(format!("Invalid discount {d}, expected 0.0..1.0"));
+        }
+    }
+    let (to, _, _) = parse_month_arg(args.to.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let mut months =
+        bills::trend::select_months(repo.list_months_including_blob().await, None, Some(&to));
+    months.drain(..months.len().saturating_sub(args.months));
+    if months.is_empty() {
+        exit_with_error(format!("No bills up to {to} in {:?}", args.data_dir));
+    }
+    let mut loaded = Vec::with_capacity(months.len());
+    for month in &months {
+        let (_, year, mon) = parse_month_arg(Some(month)).unwrap_or_else(|e| exit_with_error(e));
+        loaded.push(
+            repo.get(year, mon)
+                .await
+                .unwrap_or_else(|e| exit_with_error(e)),
+        );
+    }
+    let refs: Vec<&Bills> = loaded.iter().map(|b| b.as_ref()).collect();
+    let recs = recommend::recommend(
+        &refs,
+        filter,
+        recommend::TermDiscounts {
+            one_year: args.discount_1y,
+            three_year: args.discount_3y,
+        },
+    );
+    match args.format {
+        cmd_parse::OutputFormat::Text => {
+            println!(
+                "Reservation recommendations from {} month(s) {:?}",
+                months.len(),
+                months
+            );
+            recommend::print_recommendations(&recs, &loaded[0].get_billing_currency());
+        }
+        format => emit(format, recommend::recommend_to_csv, &recs),
+    }
+}
+
+/// Print the default report in a machine-readable `format` (`--output json|csv|markdown`),
+/// as the cost tree when `tree` is set. `ReportFormat::Text` is handled by
+/// `display::display_cost_by_filter` and `display_cost_tree`.
+pub fn print_report(
+    format: cmd_parse::ReportFormat,
+    filter: &bills::BillFilter,
+    latest: &Bills,
+    previous: Option<&Bills>,
+    display_opts: &cmd_parse::DisplayOpts,
+    tree: Option<bills::tree::TreeOpts>,
+) {
+    use bills::report;
+    if let Some(opts) = tree {
+        let root = bills::tree::build_tree(latest, previous, filter, opts);
+        match format {
+            cmd_parse::ReportFormat::Text | cmd_parse::ReportFormat::Markdown => print!(
+                "{}",
+                bills::tree::tree_to_markdown(&root, &latest.get_billing_currency())
+            ),
+            cmd_parse::ReportFormat::Csv => emit(
+                cmd_parse::OutputFormat::Csv,
+                bills::tree::tree_to_csv,
+                &root,
+            ),
+            cmd_parse::ReportFormat::Json => print_json(&root),
+        }
+        return;
+    }
+    let rep = report::build_report(filter, latest, previous, display_opts);
+    match format {
+        cmd_parse::ReportFormat::Text | cmd_parse::ReportFormat::Markdown => {
+            print!("{}", report::report_to_markdown(&rep))
+        }
+        cmd_parse::ReportFormat::Csv => {
+            emit(cmd_parse::OutputFormat::Csv, report::report_to_csv, &rep)
+        }
+        cmd_parse::ReportFormat::Json => print_json(&rep),
+    }
+}
+
+/// Print the `--tree` view of `latest` (minus `previous` when given) under `filter`.
+pub fn display_cost_tree(
+    filter: &bills::BillFilter,
+    latest: &Bills,
+    previous: Option<&Bills>,
+    opts: bills::tree::TreeOpts,
+) {
+    let mut display_date = latest.file_short_name.clone();
+    if let Some(prev) = previous {
+        display_date = format!("{display_date} - {}", prev.file_short_name);
+    }
+    println!("\n## Cost tree '{display_date}'");
+    let root = bills::tree::build_tree(latest, previous, filter, opts);
+    bills::tree::print_tree(&root, &latest.get_billing_currency());
+}
+
 pub fn display_total_cost_summary(bills: &Bills, description: &str) {
     println!(
         "\n===  Displaying Azure cost summary.  {description} {} ===",
@@ -239,6 +536,292 @@ fn f64_to_currency(value: f64, decimal_places: usize) -> String {
     }
 }
 
+/// Print the tag compliance report for `args.month` (default last month), restricted to `filter`.
+pub async fn run_tag_compliance(args: &cmd_parse::TagComplianceArgs, filter: &bills::BillFilter) {
+    use bills::tag_compliance;
+    let from_file = match &args.required_tags_file {
+        Some(path) => {
+            tag_compliance::load_required_tags(path).unwrap_or_else(|e| exit_with_error(e))
+        }
+        None => Vec::new(),
+    };
+    // file keys first, then --required-tag, deduplicated in one pass
+    let mut required = tag_compliance::parse_required_tags(
+        from_file
+            .iter()
+            .chain(&args.required_tag)
+            .map(String::as_str),
+    );
+    if required.is_empty() {
+        required = tag_compliance::DEFAULT_REQUIRED_TAGS
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+    }
+    let (month, year, mon) =
+        parse_month_arg(args.month.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let bills = repo
+        .get(year, mon)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let report = tag_compliance::tag_compliance(&bills, filter, &required);
+    match args.format {
+        cmd_parse::OutputFormat::Text => {
+            println!("Tag compliance {month} from {:?}", args.data_dir);
+            tag_compliance::print_compliance(&report, args.top, &bills.get_billing_currency());
+        }
+        format => emit(format, tag_compliance::compliance_to_csv, &report),
+    }
+}
+
+/// Print the shared-cost allocation for `args.month` (default last month), restricted to `filter`.
+pub async fn run_allocate(
+    args: &cmd_parse::AllocateArgs,
+    filter: &bills::BillFilter,
+    filter_opts: &FilterOpts,
+) {
+    use bills::allocation;
+    let rules = allocation::AllocationRules::load(&args.rules, filter_opts)
+        .unwrap_or_else(|e| exit_with_error(e));
+    let (month, year, mon) =
+        parse_month_arg(args.month.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let bills = repo
+        .get(year, mon)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let table = allocation::allocate(&bills, filter, &rules);
+    match args.format {
+        cmd_parse::OutputFormat::Text => {
+            println!(
+                "Shared-cost allocation {month} by tag '{}' from {:?}",
+                rules.team_tag, args.data_dir
+            );
+            allocation::print_allocation(&table, &bills.get_billing_currency());
+        }
+        format => emit(format, allocation::allocation_to_csv, &table),
+    }
+}
+
+/// Write one chargeback statement per `args.by` value of `args.month` (default last month) to `args.out_dir`.
+pub async fn run_chargeback(args: &cmd_parse::ChargebackArgs, filter: &bills::BillFilter) {
+    use bills::chargeback::{self, ChargebackDimension};
+    let dimension = match (args.by, &args.tag_key) {
+        (cmd_parse::ChargebackBy::InvoiceSection, _) => ChargebackDimension::InvoiceSection,
+        (cmd_parse::ChargebackBy::Subscription, _) => ChargebackDimension::Subscription,
+        (cmd_parse::ChargebackBy::Tag, Some(key)) if !key.trim().is_empty() => {
+            ChargebackDimension::Tag(key.trim().to_lowercase())
+        }
+        (cmd_parse::ChargebackBy::Tag, _) => exit_with_error("--by tag requires --tag-key"),
+    };
+    let (month, year, mon) =
+        parse_month_arg(args.month.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let latest = repo
+        .get(year, mon)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let prev = load_previous_month(&repo, year, mon).await;
+    let statements = chargeback::build_statements(
+        &latest,
+        &month,
+        prev.as_ref().map(|(m, p)| (p.as_ref(), m.as_str())),
+        filter,
+        &dimension,
+    );
+    let files = chargeback::write_statements(&statements, &args.out_dir).unwrap_or_else(|e| {
+        exit_with_error(format!(
+            "Failed to write statements to {:?}: {e}",
+            args.out_dir
+        ))
+    });
+    println!(
+        "Chargeback {month} by {}: {} statements written to {:?}",
+        dimension.label(),
+        statements.len(),
+        args.out_dir
+    );
+    for (s, stem) in statements
+        .iter()
+        .zip(chargeback::statement_file_stems(&statements))
+    {
+        let change = if s.prev_month.is_some() {
+            format!("  change {}", money::Nzd(s.total.delta()))
+        } else {
+            String::new()
+        };
+        println!(
+            "{:>18}  {}{change}  {}",
+            money::Nzd(s.total.cost).to_string(),
+            s.value.bold(),
+            stem
+        );
+    }
+    log::debug!("Wrote {} files", files.len());
+}
+
+/// Bills of `month`, or of every month in `filter`'s `--from`/`--to` range when no month
+/// is given (an open start begins at the earliest month), else last month; with a label
+/// for the output header.
+async fn load_month_or_range(
+    data_dir: &Path,
+    month: Option<&str>,
+    filter: &bills::BillFilter,
+) -> Result<(String, Vec<std::sync::Arc<bills::Bills>>), String> {
+    let repo = open_repository(data_dir);
+    if month.is_none() && filter.date_range.is_set() {
+        let range = repo.get_range(&filter.date_range).await?;
+        for m in &range.missing {
+            log::warn!("No bill for {m}, skipped");
+        }
+        return Ok((filter.date_range.to_string(), range.months));
+    }
+    let (month, year, mon) = parse_month_arg(month)?;
+    let bills = repo.get(year, mon).await?;
+    Ok((month, vec![bills]))
+}
+
+/// Write the HTML report of `args.month` (default last month), compared with the month before.
+pub async fn run_html_report(
+    args: &cmd_parse::HtmlReportArgs,
+    filter: &bills::BillFilter,
+    display_opts: &cmd_parse::DisplayOpts,
+) {
+    use bills::html_report;
+    let (month, year, mon) =
+        parse_month_arg(args.month.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let latest = repo
+        .get(year, mon)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let prev = load_previous_month(&repo, year, mon).await;
+    let report = html_report::build_html_report(
+        &latest,
+        &month,
+        prev.as_ref().map(|(m, p)| (p.as_ref(), m.as_str())),
+        filter,
+        display_opts,
+    );
+    let out = args
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("bill-report-{month}.html")));
+    std::fs::write(&out, html_report::render_html(&report))
+        .unwrap_or_else(|e| exit_with_error(format!("Failed to write report to {out:?}: {e}")));
+    println!(
+        "HTML report {month} written to {out:?}  total {}",
+        money::Nzd(report.total_cost)
+    );
+}
+
+/// Load `args.month` (default last month) and the previous month once and browse them in the TUI.
+pub async fn run_tui(args: &cmd_parse::TuiArgs, filter: &bills::BillFilter) {
+    let (_, year, mon) =
+        parse_month_arg(args.month.as_deref()).unwrap_or_else(|e| exit_with_error(e));
+    let repo = open_repository(&args.data_dir);
+    let latest = repo
+        .get(year, mon)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let prev = match args.prev_month.as_deref() {
+        Some(m) => {
+            let (_, prev_year, prev_mon) =
+                parse_month_arg(Some(m)).unwrap_or_else(|e| exit_with_error(e));
+            load_comparison_month(&repo, prev_year, prev_mon).await
+        }
+        None => load_previous_month(&repo, year, mon).await,
+    };
+    let mut state = tui::DrillState::new(latest, prev.map(|(_, p)| p), filter.clone());
+    if args.no_compare {
+        state.compare = false;
+        state.refresh();
+    }
+    tui::run(state).unwrap_or_else(|e| exit_with_error(format!("Terminal error: {e}")));
+}
+
+/// Load `args.month` (default last month) and read `repl` commands until `quit`.
+pub async fn run_repl(args: &cmd_parse::ReplArgs, filter: &bills::BillFilter) {
+    let months: Vec<String> = if args.month.is_empty() {
+        vec![None]
+    } else {
+        args.month.iter().map(|m| Some(m.as_str())).collect()
+    }
+    .into_iter()
+    .map(|m| parse_month_arg(m).map(|(month, _, _)| month))
+    .collect::<Result<_, _>>()
+    .unwrap_or_else(|e| exit_with_error(e));
+    let mut session = repl::Session::new(open_repository(&args.data_dir), filter.clone());
+    if let Err(e) = session.execute(repl::Command::Load(months)).await {
+        log::warn!("{e}");
+    }
+    let history = args.history.clone().or_else(|| {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".bill_analysis_history"))
+    });
+    repl::run(session, history.as_deref())
+        .await
+        .unwrap_or_else(|e| exit_with_error(format!("Terminal error: {e}")));
+}
+
+/// Group the rows passing `filter` by `args.group_by` and print the ranked groups.
+/// Reads `--month`, or every month of the filter's `--from`/`--to` range, else last month.
+pub async fn run_query(args: &cmd_parse::QueryArgs, filter: bills::BillFilter) {
+    use bills::query::{self, Query};
+    let (label, loaded) = load_month_or_range(&args.data_dir, args.month.as_deref(), &filter)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let months: Vec<&bills::Bills> = loaded.iter().map(|b| b.as_ref()).collect();
+    let q = Query {
+        filter,
+        group_by: args.group_by.clone(),
+        metric: args.metric,
+        sort: args.sort,
+        limit: (args.limit > 0).then_some(args.limit),
+    };
+    let result = query::run(&months, &q);
+    match args.format {
+        cmd_parse::OutputFormat::Text => {
+            println!("Query {label} by {} from {:?}", q.group_by, args.data_dir);
+            query::print_query(&result, &months[0].get_billing_currency());
+        }
+        format => emit(format, query::query_to_csv, &result),
+    }
+}
+
+/// Pivot the rows passing `filter` by `args.by` and print the table with subtotals.
+pub async fn run_pivot(args: &cmd_parse::PivotArgs, filter: &bills::BillFilter) {
+    use bills::pivot::{self, PivotLayout, PivotSpec};
+    if args.by.contains(&bills::query::GroupBy::None) {
+        exit_with_error("--by cannot include 'none'");
+    }
+    let (label, loaded) = load_month_or_range(&args.data_dir, args.month.as_deref(), filter)
+        .await
+        .unwrap_or_else(|e| exit_with_error(e));
+    let months: Vec<&bills::Bills> = loaded.iter().map(|b| b.as_ref()).collect();
+    let spec = PivotSpec {
+        dimensions: args.by.clone(),
+        metric: args.metric,
+        sort: args.sort,
+        limit: (args.limit > 0).then_some(args.limit),
+    };
+    let table = pivot::pivot(&months, filter, &spec);
+    match args.format {
+        cmd_parse::OutputFormat::Text => {
+            println!(
+                "Pivot {label} by {} from {:?}",
+                table.dimensions.join(" × "),
+                args.data_dir
+            );
+            pivot::print_pivot(&table, args.layout, &months[0].get_billing_currency());
+        }
+        cmd_parse::OutputFormat::Json if args.layout == PivotLayout::Flat => {
+            print_json(&pivot::flatten(&table))
+        }
+        format => emit(format, pivot::pivot_to_csv, &table),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::BillRepository;
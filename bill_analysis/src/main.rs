@@ -1,5 +1,5 @@
 use bill_analysis::bills;
-use bill_analysis::cmd_parse::{Commands, DisplayOpts, FilterOpts};
+use bill_analysis::cmd_parse::{Commands, DisplayOpts, FilterOpts, OutputFormat, ReportFormat};
 use clap::Parser;
 
 #[tokio::main]
@@ -24,7 +24,11 @@ async fn main() {
         cost_min_display: app.global_opts.cost_min_display,
         tag_list: app.global_opts.tag_list,
         debug,
+        top: app.global_opts.top,
     };
+    let filter = app
+        .bill_filter(&filter_opts)
+        .unwrap_or_else(|e| bill_analysis::exit_with_error(e));
     match app.command {
         Some(Commands::BillSummary(args)) => {
             log::debug!("Running BillSummary command {:?}", args);
@@ -45,65 +49,197 @@ async fn main() {
                 debug,
             );
         }
+        Some(Commands::Trend(args)) => {
+            log::debug!("Running Trend command {:?}", args);
+            bill_analysis::run_trend(&args, &filter).await;
+            if args.format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
+        Some(Commands::Coverage(args)) => {
+            log::debug!("Running Coverage command {:?}", args);
+            bill_analysis::run_coverage(&args, &filter).await;
+            if args.format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
+        Some(Commands::Recommend(args)) => {
+            log::debug!("Running Recommend command {:?}", args);
+            bill_analysis::run_recommend(&args, &filter).await;
+            if args.format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
+        Some(Commands::TagCompliance(args)) => {
+            log::debug!("Running TagCompliance command {:?}", args);
+            bill_analysis::run_tag_compliance(&args, &filter).await;
+            if args.format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
+        Some(Commands::Allocate(args)) => {
+            log::debug!("Running Allocate command {:?}", args);
+            bill_analysis::run_allocate(&args, &filter, &filter_opts).await;
+            if args.format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
+        Some(Commands::Chargeback(args)) => {
+            log::debug!("Running Chargeback command {:?}", args);
+            bill_analysis::run_chargeback(&args, &filter).await;
+        }
+        Some(Commands::HtmlReport(args)) => {
+            log::debug!("Running HtmlReport command {:?}", args);
+            bill_analysis::run_html_report(&args, &filter, &display_opts).await;
+        }
+        Some(Commands::Tui(args)) => {
+            log::debug!("Running Tui command {:?}", args);
+            bill_analysis::run_tui(&args, &filter).await;
+            return; // the terminal was handed back on quit
+        }
+        Some(Commands::Repl(args)) => {
+            log::debug!("Running Repl command {:?}", args);
+            bill_analysis::run_repl(&args, &filter).await;
+            return;
+        }
+        Some(Commands::Pivot(args)) => {
+            log::debug!("Running Pivot command {:?}", args);
+            bill_analysis::run_pivot(&args, &filter).await;
+            if args.format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
+        Some(Commands::Query(args)) => {
+            log::debug!("Running Query command {:?}", args);
+            let format = args.format;
+            bill_analysis::run_query(&args, filter).await;
+            if format != OutputFormat::Text {
+                return; // keep machine-readable output clean
+            }
+        }
         None => {
             if debug {
                 log::debug!("No command specified #1 {:?}", app);
                 log::debug!("No command specified #2 {:?}", app.name_regex);
             }
             // Positional args take precedence over named flags
-            let bill_path = app.bill.or(app.global_opts.bill_path).unwrap_or_else(|| {
-                let default = bill_analysis::find_files::last_month_shorthand();
-                log::info!("No bill specified, defaulting to last month: {default}");
-                std::path::PathBuf::from(default)
-            });
+            let bill_path = app.bill.or(app.global_opts.bill_path);
             let prev_path = app.bill_prev.or(app.global_opts.bill_prev_subtract_path);
-            let (latest_bill, file_name) =
-                bill_analysis::load_bill_async(&bill_path, &filter_opts, debug).await;
+            let range = &filter.date_range;
+            let (latest_bill, file_name) = match bill_path {
+                // --from/--to without a bill: every month of the range
+                None if range.is_set() => {
+                    let bill = bill_analysis::load_range_bill(&app.data_dir, range)
+                        .await
+                        .unwrap_or_else(|e| bill_analysis::exit_with_error(e));
+                    let name = bill.file_short_name.clone();
+                    (bill, name)
+                }
+                bill_path => {
+                    let bill_path = bill_path.unwrap_or_else(|| {
+                        let default = bill_analysis::find_files::last_month_shorthand();
+                        log::info!("No bill specified, defaulting to last month: {default}");
+                        std::path::PathBuf::from(default)
+                    });
+                    let (mut bill, name) =
+                        bill_analysis::load_bill_async(&bill_path, &filter_opts, debug).await;
+                    if range.is_set() {
+                        bill_analysis::check_range_within_bill(&bill, range)
+                            .unwrap_or_else(|e| bill_analysis::exit_with_error(e));
+                    }
+                    bill_analysis::enrich_tags_from_env(&mut bill);
+                    (bill, name)
+                }
+            };
             log::info!("Loaded latest bill from '{}'", file_name);
-            bill_analysis::display_total_cost_summary(&latest_bill, "Latest bill");
-            // If set read previous bill and subtract it from latest bill
+            let text_output = app.output == ReportFormat::Text;
+            if text_output {
+                bill_analysis::display_total_cost_summary(&latest_bill, "Latest bill");
+            }
+            // If set read the previous bill to compare the latest bill with
             let previous_bill: Option<bills::Bills> = if let Some(ref bill_prev_subtract_path) =
                 prev_path
             {
-                let (prev_bill, prev_file_name) =
+                let (mut prev_bill, prev_file_name) =
                     bill_analysis::load_bill_async(bill_prev_subtract_path, &filter_opts, debug)
                         .await;
+                bill_analysis::enrich_tags_from_env(&mut prev_bill);
                 if prev_bill.get_billing_currency() != latest_bill.get_billing_currency() {
                     panic!("Currency mismatch between bills");
                 }
-                log::info!(
-                    "Removing previous bill from latest bill '{}' (Filter matching resource ID's)",
-                    prev_file_name
-                );
-                bill_analysis::display_total_cost_summary(&prev_bill, "Previous bill");
+                log::info!("Comparing with previous bill '{}'", prev_file_name);
+                if text_output {
+                    bill_analysis::display_total_cost_summary(&prev_bill, "Previous bill");
+                }
                 Some(prev_bill)
             } else {
                 None
             };
+            let tree_opts = app.tree.then_some(bills::tree::TreeOpts {
+                depth: app.tree_depth as usize,
+                min_cost: app.tree_min,
+            });
+            if !text_output {
+                if app.variance {
+                    log::warn!("--variance only applies to --output text, ignoring");
+                }
+                bill_analysis::print_report(
+                    app.output,
+                    &filter,
+                    &latest_bill,
+                    previous_bill.as_ref(),
+                    &display_opts,
+                    tree_opts,
+                );
+                return; // keep machine-readable output clean
+            }
+            // Comparison report: per-resource changes, unless --no-diff-resources
+            if let Some(prev_bill) = previous_bill.as_ref().filter(|_| !app.no_diff_resources) {
+                let diff = bills::bill_diff::diff_bills(
+                    &latest_bill,
+                    prev_bill,
+                    &filter,
+                    app.diff_by_meter,
+                );
+                println!();
+                bills::display::display_resource_diff(
+                    &diff,
+                    &latest_bill.get_billing_currency(),
+                    &display_opts,
+                );
+            }
+            if app.variance {
+                match &previous_bill {
+                    Some(prev_bill) => {
+                        let variance =
+                            bills::variance::decompose_variance(&latest_bill, prev_bill, &filter);
+                        println!();
+                        bills::display::display_variance(
+                            &variance,
+                            &latest_bill.get_billing_currency(),
+                            &display_opts,
+                        );
+                    }
+                    None => log::warn!("--variance needs a previous bill, ignoring"),
+                }
+            }
             // Display latest_bill ( - previous bill if set)
             // using regex filters if set
-            let filter = bill_analysis::bills::BillFilter::new(
-                app.name_regex,
-                app.resource_group,
-                app.subscription,
-                app.meter_category,
-                app.location,
-                app.reservation,
-                app.tag_summarise,
-                app.tag_filter,
-                app.invoice_section,
-                &filter_opts,
-            )
-            .unwrap_or_else(|e| {
-                log::error!("invalid regex in filter: {e}");
-                std::process::exit(1);
-            });
-            bill_analysis::bills::display::display_cost_by_filter(
-                &filter,
-                latest_bill,
-                previous_bill,
-                &display_opts,
-            )
+            if let Some(opts) = tree_opts {
+                bill_analysis::display_cost_tree(
+                    &filter,
+                    &latest_bill,
+                    previous_bill.as_ref(),
+                    opts,
+                );
+            } else {
+                bill_analysis::bills::display::display_cost_by_filter(
+                    &filter,
+                    latest_bill,
+                    previous_bill,
+                    &display_opts,
+                )
+            }
         }
     }
     println!(
@@ -0,0 +1,682 @@
+//! Interactive shell (`repl` subcommand) over bills kept in memory.
+//!
+//! Months are loaded once through `BillRepository`; every `show`, `compare` and
+//! `export` then runs the query engine (`bills::query`) over the loaded `Bills`
+//! with the global `BillFilter` narrowed by the `set` patterns. Line editing,
+//! history and tab completion (commands, dimensions, months and the dimension
+//! values of the loaded bills) come from rustyline.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use colored::Colorize;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::{BillFilter, FilterDimension};
+use crate::bills::cost_type_enum::CostType;
+use crate::bills::query::{self, GroupBy, Metric, Query, QueryResult, SortOrder};
+use crate::bills::trend::normalise_month;
+use crate::f64_to_currency;
+use crate::find_files;
+
+const COMMANDS: [&str; 13] = [
+    "load", "use", "months", "set", "unset", "clear", "filter", "show", "compare", "export",
+    "help", "quit", "exit",
+];
+
+/// Group-by names offered by completion, as accepted by `GroupBy::from_str`.
+const GROUP_BY: [&str; 15] = [
+    "none",
+    "resource",
+    "tag:",
+    "resource_group",
+    "subscription",
+    "meter_category",
+    "meter_sub_category",
+    "meter_name",
+    "reservation",
+    "location",
+    "invoice_section",
+    "resource_type",
+    "charge_type",
+    "pricing_model",
+    "publisher",
+];
+
+const HELP: &str = "\
+load MONTH [MONTH ..]      load bills (YYYY-MM); the last one becomes current
+use MONTH                  switch the current month
+months                     list loaded months
+set DIM REGEX              filter a dimension (name, resource_group, subscription, meter_category,
+                           location, reservation, tag, invoice_section, resource_type, meter_name,
+                           charge_type, pricing_model, publisher)
+unset DIM | clear          drop one / all `set` filters
+filter                     show the active filters
+show GROUP [N]             top N groups of the current month (GROUP as for `query --group-by`;
+                           N defaults to 20, 0 shows all)
+compare MONTH [GROUP] [N]  current month vs MONTH per group (default resource_group),
+                           largest changes first
+export FILE [GROUP]        write the groups of the current month as CSV (or JSON for *.json)
+quit | exit                leave (Ctrl-D)";
+
+/// One parsed input line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Load(Vec<String>),
+    Use(String),
+    Months,
+    Set(FilterDimension, String),
+    Unset(FilterDimension),
+    Clear,
+    Filter,
+    Show(GroupBy, Option<usize>),
+    Compare(String, GroupBy, Option<usize>),
+    Export(PathBuf, GroupBy),
+    Help,
+    Quit,
+}
+
+fn dimension(name: &str) -> Result<FilterDimension, String> {
+    let name = match name {
+        "rg" => "resource_group",
+        "sub" => "subscription",
+        other => other,
+    };
+    FilterDimension::from_arg_name(name).ok_or_else(|| {
+        format!(
+            "Unknown dimension '{name}', expected one of: {}",
+            FilterDimension::ALL
+                .iter()
+                .map(|d| d.arg_name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Rows shown by `show` / `compare`: 20 by default, 0 for all.
+fn rows_shown(n: Option<usize>) -> Option<usize> {
+    match n {
+        None => Some(20),
+        Some(0) => None,
+        n => n,
+    }
+}
+
+fn limit(arg: Option<&str>) -> Result<Option<usize>, String> {
+    arg.map(|n| {
+        n.parse::<usize>()
+            .map_err(|_| format!("Invalid row count '{n}'"))
+    })
+    .transpose()
+}
+
+/// Parse one line; `Ok(None)` for a blank line.
+pub fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((&cmd, args)) = words.split_first() else {
+        return Ok(None);
+    };
+    let group = |i: usize| -> Result<GroupBy, String> {
+        args.get(i)
+            .map_or(Ok(GroupBy::Dimension(CostType::ResourceGroup)), |g| {
+                g.parse()
+            })
+    };
+    let command = match (cmd, args.len()) {
+        ("load", n) if n > 0 => Command::Load(
+            args.iter()
+                .map(|m| normalise_month(m))
+                .collect::<Result<_, _>>()?,
+        ),
+        ("use", 1) => Command::Use(normalise_month(args[0])?),
+        ("months", 0) => Command::Months,
+        ("set", n) if n >= 2 => {
+            // the regex is the rest of the line, so it may contain spaces
+            let dim = dimension(args[0])?;
+            let rest = line.trim_start()[3..].trim_start()[args[0].len()..].trim();
+            Command::Set(dim, rest.to_string())
+        }
+        ("unset", 1) => Command::Unset(dimension(args[0])?),
+        ("clear", 0) => Command::Clear,
+        ("filter", 0) => Command::Filter,
+        ("show", 1 | 2) => Command::Show(args[0].parse()?, limit(args.get(1).copied())?),
+        ("compare", 1..=3) => Command::Compare(
+            normalise_month(args[0])?,
+            group(1)?,
+            limit(args.get(2).copied())?,
+        ),
+        ("export", 1 | 2) => Command::Export(PathBuf::from(args[0]), group(1)?),
+        ("help" | "?", _) => Command::Help,
+        ("quit" | "exit", _) => Command::Quit,
+        _ if COMMANDS.contains(&cmd) => {
+            return Err(format!("Wrong arguments for '{cmd}', see 'help'"));
+        }
+        _ => return Err(format!("Unknown command '{cmd}', see 'help'")),
+    };
+    Ok(Some(command))
+}
+
+/// A group in `compare`: cost in the current month and in the other month.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareRow {
+    pub key: String,
+    pub cost: f64,
+    pub other: f64,
+}
+
+impl CompareRow {
+    pub fn delta(&self) -> f64 {
+        self.cost - self.other
+    }
+}
+
+/// Join two query results on the group key, largest absolute change first.
+pub fn compare_results(current: &QueryResult, other: &QueryResult) -> Vec<CompareRow> {
+    let label = |r: &query::QueryRow| {
+        if r.resource_group.is_empty() {
+            r.key.clone()
+        } else {
+            format!("{} ({})", r.key, r.resource_group)
+        }
+    };
+    let mut rows: BTreeMap<String, CompareRow> = BTreeMap::new();
+    for r in &current.rows {
+        let key = label(r);
+        rows.entry(key.clone())
+            .or_insert(CompareRow {
+                key,
+                cost: 0.0,
+                other: 0.0,
+            })
+            .cost += r.cost;
+    }
+    for r in &other.rows {
+        let key = label(r);
+        rows.entry(key.clone())
+            .or_insert(CompareRow {
+                key,
+                cost: 0.0,
+                other: 0.0,
+            })
+            .other += r.cost;
+    }
+    let mut rows: Vec<CompareRow> = rows.into_values().collect();
+    rows.sort_by(|a, b| b.delta().abs().total_cmp(&a.delta().abs()));
+    rows
+}
+
+/// Words offered by tab completion, refreshed after every `load`.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionWords {
+    pub months: Vec<String>,
+    /// Distinct values per `FilterDimension::arg_name` in the loaded bills.
+    pub values: HashMap<&'static str, Vec<String>>,
+}
+
+impl CompletionWords {
+    /// Start of the word at the end of `line` and the candidates for it.
+    pub fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let ends_with_space = line.ends_with(char::is_whitespace);
+        let index = if ends_with_space {
+            words.len()
+        } else {
+            words.len().saturating_sub(1)
+        };
+        let partial = if ends_with_space {
+            ""
+        } else {
+            words.last().copied().unwrap_or("")
+        };
+        let mut start = line.len() - partial.len();
+        let cmd = words.first().copied().unwrap_or("");
+        let owned = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let dims = || {
+            FilterDimension::ALL
+                .iter()
+                .map(|d| d.arg_name().to_string())
+                .collect::<Vec<_>>()
+        };
+        let (partial, candidates) = match (index, cmd) {
+            (0, _) => (partial, owned(&COMMANDS)),
+            (1, "set" | "unset") => (partial, dims()),
+            (_, "set") => {
+                // the value is the rest of the line after the dimension
+                let dim = words.get(1).and_then(|d| dimension(d).ok());
+                let after_dim = line
+                    .find(words[1])
+                    .map(|i| i + words[1].len())
+                    .unwrap_or(line.len());
+                let value = line[after_dim..].trim_start();
+                start = line.len() - value.len();
+                let values = dim
+                    .and_then(|d| self.values.get(d.arg_name()))
+                    .cloned()
+                    .unwrap_or_default();
+                (value, values)
+            }
+            (_, "load" | "use") | (1, "compare") => (partial, self.months.clone()),
+            (1, "show") | (2, "compare" | "export") => (partial, owned(&GROUP_BY)),
+            _ => (partial, Vec::new()),
+        };
+        let lower = partial.to_lowercase();
+        let matches = candidates
+            .into_iter()
+            .filter(|c| c.to_lowercase().starts_with(&lower))
+            .collect();
+        (start, matches)
+    }
+}
+
+struct ReplHelper {
+    words: CompletionWords,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.words.complete(&line[..pos]))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+impl Highlighter for ReplHelper {}
+impl Validator for ReplHelper {}
+impl Helper for ReplHelper {}
+
+/// Loaded months, current month and `set` filters.
+pub struct Session {
+    repo: crate::BillRepository,
+    base: BillFilter,
+    pub months: BTreeMap<String, Arc<Bills>>,
+    pub current: Option<String>,
+    pub patterns: Vec<(FilterDimension, String)>,
+}
+
+impl Session {
+    pub fn new(repo: crate::BillRepository, base: BillFilter) -> Self {
+        Session {
+            repo,
+            base,
+            months: BTreeMap::new(),
+            current: None,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// The global filter narrowed by the `set` patterns: a row must match both
+    /// the command-line pattern and the `set` pattern of a dimension.
+    pub fn filter(&self) -> Result<BillFilter, String> {
+        self.patterns
+            .iter()
+            .try_fold(self.base.clone(), |f, (dim, pattern)| {
+                f.and_pattern(*dim, pattern)
+                    .map_err(|e| format!("Invalid regex '{pattern}': {e}"))
+            })
+    }
+
+    fn month(&self, month: &str) -> Result<&Bills, String> {
+        self.months
+            .get(month)
+            .map(|b| b.as_ref())
+            .ok_or_else(|| format!("{month} is not loaded, use 'load {month}'"))
+    }
+
+    fn current(&self) -> Result<(&str, &Bills), String> {
+        let month = self
+            .current
+            .as_deref()
+            .ok_or("No month loaded, use 'load YYYY-MM'")?;
+        Ok((month, self.month(month)?))
+    }
+
+    fn run_query(
+        &self,
+        bills: &Bills,
+        group_by: &GroupBy,
+        limit: Option<usize>,
+    ) -> Result<QueryResult, String> {
+        let q = Query {
+            filter: self.filter()?,
+            group_by: group_by.clone(),
+            metric: Metric::Cost,
+            sort: SortOrder::Desc,
+            limit,
+        };
+        Ok(query::run(&[bills], &q))
+    }
+
+    /// Completion words for the loaded bills.
+    pub fn completion_words(&self) -> CompletionWords {
+        let mut values: HashMap<&'static str, BTreeSet<String>> = HashMap::new();
+        for bills in self.months.values() {
+            for bill in &bills.bills {
+                // the tag dimension matches the whole tag string, not worth offering
+                for dim in FilterDimension::ALL
+                    .into_iter()
+                    .filter(|d| *d != FilterDimension::Tag)
+                {
+                    let value = dim.value(bill);
+                    if !value.is_empty() {
+                        values
+                            .entry(dim.arg_name())
+                            .or_default()
+                            .insert(value.into_owned());
+                    }
+                }
+            }
+        }
+        CompletionWords {
+            months: self.months.keys().cloned().collect(),
+            values: values
+                .into_iter()
+                .map(|(k, v)| (k, v.into_iter().collect()))
+                .collect(),
+        }
+    }
+
+    /// Run one command; returns `false` on `quit`.
+    pub async fn execute(&mut self, command: Command) -> Result<bool, String> {
+        match command {
+            Command::Load(months) => {
+                for month in months {
+                    let (year, mon) = find_files::parse_year_month_path(Path::new(&month))
+                        .expect("normalised month is YYYY-MM");
+                    let bills = self.repo.get(year, mon).await?;
+                    println!("Loaded {month}: {} rows", bills.bills.len());
+                    self.months.insert(month.clone(), bills);
+                    self.current = Some(month);
+                }
+            }
+            Command::Use(month) => {
+                self.month(&month)?;
+                self.current = Some(month);
+            }
+            Command::Months => {
+                for month in self.months.keys() {
+                    let mark = if self.current.as_ref() == Some(month) {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    println!("{mark} {month}");
+                }
+            }
+            Command::Set(dim, pattern) => {
+                let old = self.patterns.clone();
+                self.patterns.retain(|(d, _)| *d != dim);
+                self.patterns.push((dim, pattern));
+                if let Err(e) = self.filter() {
+                    self.patterns = old;
+                    return Err(e);
+                }
+            }
+            Command::Unset(dim) => self.patterns.retain(|(d, _)| *d != dim),
+            Command::Clear => self.patterns.clear(),
+            Command::Filter => {
+                if self.patterns.is_empty() {
+                    println!("No filters set (global command-line filters still apply)");
+                }
+                for (dim, pattern) in &self.patterns {
+                    println!("{} ~ {pattern}", dim.arg_name());
+                }
+            }
+            Command::Show(group_by, n) => {
+                let (month, bills) = self.current()?;
+                let result = self.run_query(bills, &group_by, rows_shown(n))?;
+                println!(
+                    "{month} by {group_by}: {} of {} groups",
+                    result.rows.len(),
+                    result.total_groups
+                );
+                query::print_query(&result, &bills.get_billing_currency());
+            }
+            Command::Compare(other_month, group_by, n) => {
+                let (month, bills) = self.current()?;
+                let other = self.month(&other_month)?;
+                let current = self.run_query(bills, &group_by, None)?;
+                let previous = self.run_query(other, &group_by, None)?;
+                let rows = compare_results(&current, &previous);
+                let cur = bills.get_billing_currency();
+                println!(
+                    "{:>16} {:>16} {:>16}  {group_by}",
+                    month, other_month, "change"
+                );
+                for r in rows.iter().take(rows_shown(n).unwrap_or(usize::MAX)) {
+                    let delta = format!("{cur} {}", f64_to_currency(r.delta(), 2));
+                    let delta = if r.delta() > 0.0 {
+                        delta.red()
+                    } else {
+                        delta.green()
+                    };
+                    println!(
+                        "{:>16} {:>16} {:>16}  '{}'",
+                        f64_to_currency(r.cost, 2),
+                        f64_to_currency(r.other, 2),
+                        delta,
+                        r.key
+                    );
+                }
+                println!(
+                    "{:>16} {:>16} {:>16}  Total",
+                    f64_to_currency(current.total_cost, 2).bold(),
+                    f64_to_currency(previous.total_cost, 2),
+                    f64_to_currency(current.total_cost - previous.total_cost, 2).bold(),
+                );
+            }
+            Command::Export(path, group_by) => {
+                let (_, bills) = self.current()?;
+                let result = self.run_query(bills, &group_by, None)?;
+                let text = if path.extension().is_some_and(|e| e == "json") {
+                    serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?
+                } else {
+                    query::query_to_csv(&result).map_err(|e| e.to_string())?
+                };
+                std::fs::write(&path, text)
+                    .map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+                println!("Wrote {} groups to {path:?}", result.rows.len());
+            }
+            Command::Help => println!("{HELP}"),
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Read commands until `quit` or end of input. History is kept in `history` when given.
+pub async fn run(mut session: Session, history: Option<&Path>) -> rustyline::Result<()> {
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ReplHelper {
+        words: session.completion_words(),
+    }));
+    if let Some(path) = history {
+        // a missing history file is normal on first use
+        let _ = editor.load_history(path);
+    }
+    println!("bill_analysis repl, 'help' for commands");
+    loop {
+        let prompt = format!("{}> ", session.current.as_deref().unwrap_or("bill"));
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+        if !line.trim().is_empty() {
+            editor.add_history_entry(line.as_str())?;
+        }
+        let keep_going = match parse_command(&line) {
+            Ok(Some(command)) => {
+                let reload = matches!(command, Command::Load(_));
+                let result = session.execute(command).await;
+                if reload && let Some(helper) = editor.helper_mut() {
+                    helper.words = session.completion_words();
+                }
+                result.unwrap_or_else(|e| {
+                    println!("{}", e.red());
+                    true
+                })
+            }
+            Ok(None) => true,
+            Err(e) => {
+                println!("{}", e.red());
+                true
+            }
+        };
+        if !keep_going {
+            break;
+        }
+    }
+    if let Some(path) = history
+        && let Err(e) = editor.save_history(path)
+    {
+        log::warn!("Could not save history to {path:?}: {e}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bills::test_support::row;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse_command("  ").unwrap(), None);
+        assert_eq!(
+            parse_command("set rg prod (core|edge)").unwrap(),
+            Some(Command::Set(
+                FilterDimension::ResourceGroup,
+                "prod (core|edge)".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_command("show meter_category 5").unwrap(),
+            Some(Command::Show(
+                GroupBy::Dimension(CostType::MeterCategory),
+                Some(5)
+            ))
+        );
+        assert_eq!(
+            parse_command("compare 2025-09").unwrap(),
+            Some(Command::Compare(
+                "2025-09".to_string(),
+                GroupBy::Dimension(CostType::ResourceGroup),
+                None
+            ))
+        );
+        assert!(
+            parse_command("show")
+                .unwrap_err()
+                .contains("Wrong arguments")
+        );
+        assert!(
+            parse_command("set colour red")
+                .unwrap_err()
+                .contains("Unknown dimension")
+        );
+        assert!(
+            parse_command("frobnicate")
+                .unwrap_err()
+                .contains("Unknown command")
+        );
+    }
+
+    #[test]
+    fn completion_by_position() {
+        let words = CompletionWords {
+            months: vec!["2025-09".to_string(), "2025-10".to_string()],
+            values: HashMap::from([(
+                "resource_group",
+                vec!["rg-core prod".to_string(), "rg-edge".to_string()],
+            )]),
+        };
+        assert_eq!(words.complete("sh"), (0, vec!["show".to_string()]));
+        assert_eq!(
+            words.complete("set resource_g"),
+            (4, vec!["resource_group".to_string()])
+        );
+        assert_eq!(
+            words.complete("set resource_group RG-C"),
+            (19, vec!["rg-core prod".to_string()])
+        );
+        assert_eq!(
+            words.complete("compare 2025-1"),
+            (8, vec!["2025-10".to_string()])
+        );
+        assert_eq!(
+            words.complete("show meter_s"),
+            (5, vec!["meter_sub_category".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn set_narrows_the_command_line_filter() {
+        let base = BillFilter::match_all(&crate::cmd_parse::FilterOpts::default())
+            .with_pattern(FilterDimension::ResourceGroup, "prod")
+            .unwrap();
+        let mut session = Session::new(crate::BillRepository::new(PathBuf::new(), None), base);
+        session
+            .execute(Command::Set(
+                FilterDimension::ResourceGroup,
+                "web".to_string(),
+            ))
+            .await
+            .unwrap();
+        let filter = session.filter().unwrap();
+        let rg = |name: &str| row().rg(name);
+        assert!(filter.matches(&rg("prod-web")));
+        assert!(
+            !filter.matches(&rg("dev-web")),
+            "--resource-group prod still applies"
+        );
+        assert!(!filter.matches(&rg("prod-db")));
+    }
+
+    #[test]
+    fn compare_joins_on_key() {
+        let result = |rows: &[(&str, f64)]| QueryResult {
+            group_by: "ResourceGroup".to_string(),
+            metric: Metric::Cost,
+            total_cost: rows.iter().map(|r| r.1).sum(),
+            total_cost_usd: 0.0,
+            total_quantity: 0.0,
+            row_count: rows.len(),
+            total_groups: rows.len(),
+            rows: rows
+                .iter()
+                .map(|(k, c)| query::QueryRow {
+                    key: k.to_string(),
+                    cost: *c,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let rows = compare_results(
+            &result(&[("rg-a", 10.0), ("rg-new", 3.0)]),
+            &result(&[("rg-a", 4.0), ("rg-gone", 1.0)]),
+        );
+        let deltas: Vec<(&str, f64)> = rows.iter().map(|r| (r.key.as_str(), r.delta())).collect();
+        assert_eq!(
+            deltas,
+            vec![("rg-a", 6.0), ("rg-new", 3.0), ("rg-gone", -1.0)]
+        );
+    }
+}
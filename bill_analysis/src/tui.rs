@@ -0,0 +1,580 @@
+//! Interactive drill-down (`tui` subcommand).
+//!
+//! The bill (and optionally the previous month) is loaded once; each view is a
+//! `cost_by_any_summary` over the global `BillFilter` narrowed by the drill path,
+//! subscription → resource group → resource → meter. [`DrillState`] holds the
+//! navigation state and handles keys without a terminal, so it is tested
+//! directly; [`run`] draws it with ratatui until `q`.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::bills::Bills;
+use crate::bills::bill_filter::{BillFilter, FilterDimension};
+use crate::bills::cost_type_enum::CostType;
+use crate::f64_to_currency;
+
+/// Drill-down levels, outermost first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Subscription,
+    ResourceGroup,
+    Resource,
+    Meter,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [
+        Level::Subscription,
+        Level::ResourceGroup,
+        Level::Resource,
+        Level::Meter,
+    ];
+
+    fn cost_type(self) -> CostType {
+        match self {
+            Level::Subscription => CostType::Subscription,
+            Level::ResourceGroup => CostType::ResourceGroup,
+            Level::Resource => CostType::ResourceName,
+            Level::Meter => CostType::MeterName,
+        }
+    }
+
+    fn dimension(self) -> FilterDimension {
+        match self {
+            Level::Subscription => FilterDimension::Subscription,
+            Level::ResourceGroup => FilterDimension::ResourceGroup,
+            Level::Resource => FilterDimension::Name,
+            Level::Meter => FilterDimension::MeterName,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Level::Subscription => "Subscription",
+            Level::ResourceGroup => "Resource group",
+            Level::Resource => "Resource",
+            Level::Meter => "Meter",
+        }
+    }
+}
+
+/// Column the rows are sorted by; `s` cycles through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    Name,
+    Cost,
+    Previous,
+    Change,
+}
+
+impl SortColumn {
+    fn next(self, compare: bool) -> Self {
+        match (self, compare) {
+            (SortColumn::Name, _) => SortColumn::Cost,
+            (SortColumn::Cost, true) => SortColumn::Previous,
+            (SortColumn::Previous, _) => SortColumn::Change,
+            (SortColumn::Cost, false) | (SortColumn::Change, _) => SortColumn::Name,
+        }
+    }
+}
+
+/// One line of the current level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrillRow {
+    pub name: String,
+    pub cost: f64,
+    /// Previous bill's cost, when comparing; `None` for a key new this month.
+    pub previous: Option<f64>,
+}
+
+impl DrillRow {
+    pub fn change(&self) -> f64 {
+        self.cost - self.previous.unwrap_or(0.0)
+    }
+}
+
+/// Navigation state of the TUI.
+pub struct DrillState {
+    latest: Arc<Bills>,
+    previous: Option<Arc<Bills>>,
+    base: BillFilter,
+    /// Keys drilled into, one per level above the current one.
+    pub path: Vec<String>,
+    /// Live regex per level, edited with `/`; applies while that level is listed.
+    level_filters: [String; 4],
+    pub compare: bool,
+    pub sort: SortColumn,
+    pub descending: bool,
+    pub rows: Vec<DrillRow>,
+    pub total: f64,
+    pub previous_total: Option<f64>,
+    pub selected: usize,
+    /// Filter text being typed after `/`.
+    pub input: Option<String>,
+    pub status: String,
+}
+
+impl DrillState {
+    pub fn new(latest: Arc<Bills>, previous: Option<Arc<Bills>>, base: BillFilter) -> Self {
+        let mut state = DrillState {
+            compare: previous.is_some(),
+            latest,
+            previous,
+            base,
+            path: Vec::new(),
+            level_filters: Default::default(),
+            sort: SortColumn::Cost,
+            descending: true,
+            rows: Vec::new(),
+            total: 0.0,
+            previous_total: None,
+            selected: 0,
+            input: None,
+            status: String::new(),
+        };
+        state.refresh();
+        state
+    }
+
+    pub fn level(&self) -> Level {
+        Level::ALL[self.path.len()]
+    }
+
+    /// The global filter narrowed to the drill path and the current level's live regex.
+    pub fn filter(&self) -> Result<BillFilter, regex::Error> {
+        let mut filter = self.base.clone();
+        for (level, key) in Level::ALL.iter().zip(&self.path) {
+            filter = filter.and_pattern(level.dimension(), &format!("^{}$", regex::escape(key)))?;
+        }
+        let live = &self.level_filters[self.path.len()];
+        if !live.is_empty() {
+            filter = filter.and_pattern(self.level().dimension(), live)?;
+        }
+        Ok(filter)
+    }
+
+    fn costs(
+        bills: &Bills,
+        filter: &BillFilter,
+        cost_type: CostType,
+    ) -> (HashMap<String, f64>, f64) {
+        let summary = bills.cost_by_any_summary(filter, &[cost_type]);
+        let costs = summary
+            .per_type
+            .iter()
+            .filter(|((t, _), _)| *t == cost_type)
+            .map(|((_, name), c)| (name.clone(), c.cost.amount()))
+            .collect();
+        (costs, summary.filtered_cost_total.amount())
+    }
+
+    /// Recompute the rows of the current level.
+    pub fn refresh(&mut self) {
+        let filter = match self.filter() {
+            Ok(filter) => filter,
+            Err(e) => {
+                self.status = format!("Invalid filter: {e}");
+                return;
+            }
+        };
+        let cost_type = self.level().cost_type();
+        let (latest, total) = Self::costs(&self.latest, &filter, cost_type);
+        let previous = match (&self.previous, self.compare) {
+            (Some(prev), true) => Some(Self::costs(prev, &filter, cost_type)),
+            _ => None,
+        };
+        let mut rows: Vec<DrillRow> = latest
+            .iter()
+            .map(|(name, cost)| DrillRow {
+                name: name.clone(),
+                cost: *cost,
+                previous: previous.as_ref().and_then(|(p, _)| p.get(name).copied()),
+            })
+            .collect();
+        if let Some((prev, _)) = &previous {
+            // keys that only appear in the previous bill
+            rows.extend(
+                prev.iter()
+                    .filter(|(name, _)| !latest.contains_key(*name))
+                    .map(|(name, cost)| DrillRow {
+                        name: name.clone(),
+                        cost: 0.0,
+                        previous: Some(*cost),
+                    }),
+            );
+        }
+        self.rows = rows;
+        self.total = total;
+        self.previous_total = previous.map(|(_, t)| t);
+        self.sort_rows();
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    fn sort_rows(&mut self) {
+        let sort = self.sort;
+        self.rows.sort_by(|a, b| {
+            let ord = match sort {
+                SortColumn::Name => a.name.cmp(&b.name),
+                SortColumn::Cost => a.cost.total_cmp(&b.cost),
+                SortColumn::Previous => a
+                    .previous
+                    .unwrap_or(0.0)
+                    .total_cmp(&b.previous.unwrap_or(0.0)),
+                SortColumn::Change => a.change().total_cmp(&b.change()),
+            };
+            ord.then_with(|| a.name.cmp(&b.name))
+        });
+        if self.descending {
+            self.rows.reverse();
+        }
+    }
+
+    /// Handle one key press; returns `false` to quit.
+    pub fn handle_key(&mut self, key: KeyCode) -> bool {
+        if let Some(input) = &mut self.input {
+            match key {
+                KeyCode::Enter => {
+                    let text = input.trim().to_string();
+                    self.input = None;
+                    let old = std::mem::replace(&mut self.level_filters[self.path.len()], text);
+                    self.selected = 0;
+                    if let Err(e) = self.filter() {
+                        self.level_filters[self.path.len()] = old;
+                        self.status = format!("Invalid filter: {e}");
+                    } else {
+                        self.status.clear();
+                        self.refresh();
+                    }
+                }
+                KeyCode::Esc => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        match key {
+            KeyCode::Char('q') => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1))
+            }
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(20),
+            KeyCode::PageDown => {
+                self.selected = (self.selected + 20).min(self.rows.len().saturating_sub(1))
+            }
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = self.rows.len().saturating_sub(1),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                if self.path.len() + 1 < Level::ALL.len()
+                    && let Some(row) = self.rows.get(self.selected)
+                {
+                    self.path.push(row.name.clone());
+                    self.selected = 0;
+                    self.refresh();
+                }
+            }
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(key) = self.path.pop() {
+                    self.refresh();
+                    self.selected = self.rows.iter().position(|r| r.name == key).unwrap_or(0);
+                }
+            }
+            KeyCode::Char('c') => {
+                if self.previous.is_some() {
+                    self.compare = !self.compare;
+                    if !self.compare
+                        && matches!(self.sort, SortColumn::Previous | SortColumn::Change)
+                    {
+                        self.sort = SortColumn::Cost;
+                    }
+                    self.refresh();
+                } else {
+                    self.status = "No previous bill loaded".to_string();
+                }
+            }
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next(self.compare);
+                self.sort_rows();
+            }
+            KeyCode::Char('r') => {
+                self.descending = !self.descending;
+                self.sort_rows();
+            }
+            KeyCode::Char('/') => {
+                self.input = Some(self.level_filters[self.path.len()].clone());
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+fn money(value: f64) -> String {
+    f64_to_currency(value, 2)
+}
+
+fn draw(frame: &mut Frame, state: &DrillState, table_state: &mut TableState) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let cur = state.latest.billing_currency.clone().unwrap_or_default();
+    let mut bills = state.latest.file_short_name.clone();
+    if let (true, Some(prev)) = (state.compare, &state.previous) {
+        bills = format!("{bills} vs {}", prev.file_short_name);
+    }
+    let mut crumbs = vec!["All".to_string()];
+    crumbs.extend(state.path.iter().cloned());
+    let live = &state.level_filters[state.path.len()];
+    let mut info = format!(
+        "{} ({} rows)  total {cur} {}",
+        state.level().label(),
+        state.rows.len(),
+        money(state.total)
+    );
+    if let Some(prev) = state.previous_total {
+        info.push_str(&format!(
+            "  previous {}  change {}",
+            money(prev),
+            money(state.total - prev)
+        ));
+    }
+    if !live.is_empty() {
+        info.push_str(&format!("  filter /{live}/"));
+    }
+    frame.render_widget(
+        Paragraph::new(vec![Line::from(crumbs.join(" › ")), Line::from(info)])
+            .block(Block::default().borders(Borders::BOTTOM).title(bills)),
+        header,
+    );
+
+    let arrow = if state.descending { "▼" } else { "▲" };
+    let title = |text: &str, column: SortColumn| {
+        if state.sort == column {
+            format!("{text} {arrow}")
+        } else {
+            text.to_string()
+        }
+    };
+    let mut head = vec![
+        title("Name", SortColumn::Name),
+        title(&format!("Cost ({cur})"), SortColumn::Cost),
+        "%".to_string(),
+    ];
+    let mut widths = vec![
+        Constraint::Min(30),
+        Constraint::Length(16),
+        Constraint::Length(7),
+    ];
+    if state.compare {
+        head.push(title("Previous", SortColumn::Previous));
+        head.push(title("Change", SortColumn::Change));
+        widths.extend([Constraint::Length(16), Constraint::Length(16)]);
+    }
+    let rows = state.rows.iter().map(|r| {
+        let pct = if state.total != 0.0 {
+            r.cost / state.total * 100.0
+        } else {
+            0.0
+        };
+        let mut cells = vec![
+            Cell::from(r.name.clone()),
+            Cell::from(Line::from(money(r.cost)).right_aligned()),
+            Cell::from(Line::from(format!("{pct:.1}")).right_aligned()),
+        ];
+        if state.compare {
+            let change = r.change();
+            let colour = if change > 0.0 {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            cells.push(Cell::from(
+                Line::from(r.previous.map(money).unwrap_or_else(|| "new".to_string()))
+                    .right_aligned(),
+            ));
+            cells.push(
+                Cell::from(Line::from(money(change)).right_aligned())
+                    .style(Style::default().fg(colour)),
+            );
+        }
+        Row::new(cells)
+    });
+    let table = Table::new(rows, widths)
+        .header(Row::new(head).style(Style::default().add_modifier(Modifier::BOLD)))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    table_state.select((!state.rows.is_empty()).then_some(state.selected));
+    frame.render_stateful_widget(table, body, table_state);
+
+    let help = match &state.input {
+        Some(input) => format!(
+            "{} regex: {input}_  (Enter apply, Esc cancel)",
+            state.level().label()
+        ),
+        None if !state.status.is_empty() => state.status.clone(),
+        None => "↑↓ move  Enter drill in  ← back  / filter  s sort  r reverse  c compare  q quit"
+            .to_string(),
+    };
+    frame.render_widget(Paragraph::new(help), footer);
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, state: &mut DrillState) -> io::Result<()> {
+    let mut table_state = TableState::default();
+    loop {
+        terminal.draw(|frame| draw(frame, state, &mut table_state))?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !state.handle_key(key.code)
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Run the TUI on the terminal until the user quits.
+pub fn run(mut state: DrillState) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut state);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bills::test_support::{bills, row};
+    use crate::cmd_parse::FilterOpts;
+
+    fn names(state: &DrillState) -> Vec<&str> {
+        state.rows.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn drill_down_and_back() {
+        let latest = Arc::new(bills(vec![
+            row().name("vm-1").sub("prod").rg("rg-a").cost(10.0),
+            row().name("vm-2").sub("prod").rg("rg-b").cost(30.0),
+            row().name("vm-3").sub("dev").rg("rg-a").cost(5.0),
+        ]));
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let mut state = DrillState::new(latest, None, filter);
+        assert_eq!(names(&state), vec!["prod", "dev"]);
+        assert_eq!(state.total, 45.0);
+
+        state.handle_key(KeyCode::Enter);
+        assert_eq!(state.level(), Level::ResourceGroup);
+        assert_eq!(names(&state), vec!["rg-b", "rg-a"]);
+        state.handle_key(KeyCode::Down);
+        state.handle_key(KeyCode::Enter);
+        assert_eq!(state.path, vec!["prod", "rg-a"]);
+        assert_eq!(names(&state), vec!["vm-1"]);
+
+        state.handle_key(KeyCode::Left);
+        assert_eq!(state.selected, 1, "back keeps the drilled row selected");
+        state.handle_key(KeyCode::Char('s'));
+        state.handle_key(KeyCode::Char('r'));
+        assert_eq!(state.sort, SortColumn::Name);
+        assert_eq!(names(&state), vec!["rg-a", "rg-b"]);
+        assert!(!state.handle_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn live_filter_narrows_the_command_line_filter() {
+        let latest = Arc::new(bills(vec![
+            row().name("vm-1").sub("prod").rg("rg-web").cost(10.0),
+            row().name("vm-2").sub("prod").rg("rg-db").cost(4.0),
+            row().name("vm-3").sub("prod").rg("dev-web").cost(2.0),
+        ]));
+        let filter = BillFilter::match_all(&FilterOpts::default())
+            .with_pattern(
+                crate::bills::bill_filter::FilterDimension::ResourceGroup,
+                "^rg-",
+            )
+            .unwrap();
+        let mut state = DrillState::new(latest, None, filter);
+        state.handle_key(KeyCode::Enter);
+        assert_eq!(names(&state), vec!["rg-web", "rg-db"]);
+        for key in [
+            KeyCode::Char('/'),
+            KeyCode::Char('w'),
+            KeyCode::Char('e'),
+            KeyCode::Char('b'),
+            KeyCode::Enter,
+        ] {
+            state.handle_key(key);
+        }
+        assert_eq!(
+            names(&state),
+            vec!["rg-web"],
+            "--resource-group ^rg- still applies"
+        );
+    }
+
+    #[test]
+    fn live_filter_and_comparison() {
+        let latest = Arc::new(bills(vec![
+            row().name("vm-1").sub("prod").rg("rg-a").cost(10.0),
+            row().name("vm-2").sub("prod").rg("rg-new").cost(4.0),
+        ]));
+        let previous = Arc::new(bills(vec![
+            row().name("vm-1").sub("prod").rg("rg-a").cost(12.0),
+            row().name("vm-9").sub("prod").rg("rg-gone").cost(3.0),
+        ]));
+        let filter = BillFilter::match_all(&FilterOpts::default());
+        let mut state = DrillState::new(latest, Some(previous), filter);
+        state.handle_key(KeyCode::Enter);
+        state.handle_key(KeyCode::Char('s'));
+        state.handle_key(KeyCode::Char('s'));
+        assert_eq!(state.sort, SortColumn::Change);
+        let changes: Vec<(&str, f64)> = state
+            .rows
+            .iter()
+            .map(|r| (r.name.as_str(), r.change()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![("rg-new", 4.0), ("rg-a", -2.0), ("rg-gone", -3.0)]
+        );
+        assert_eq!(state.previous_total, Some(15.0));
+
+        for key in [
+            KeyCode::Char('/'),
+            KeyCode::Char('n'),
+            KeyCode::Char('e'),
+            KeyCode::Enter,
+        ] {
+            state.handle_key(key);
+        }
+        assert_eq!(names(&state), vec!["rg-new", "rg-gone"]);
+        for key in [KeyCode::Char('/'), KeyCode::Char('('), KeyCode::Enter] {
+            state.handle_key(key);
+        }
+        assert!(state.status.starts_with("Invalid filter"));
+        assert_eq!(
+            names(&state),
+            vec!["rg-new", "rg-gone"],
+            "bad regex keeps the old one"
+        );
+
+        state.handle_key(KeyCode::Char('c'));
+        assert!(!state.compare);
+        assert_eq!(state.sort, SortColumn::Cost);
+        assert_eq!(names(&state), vec!["rg-new"]);
+    }
+}